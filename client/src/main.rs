@@ -0,0 +1,430 @@
+use std::{
+    io,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use clap::Parser;
+
+use futures::stream::{StreamExt, TryStreamExt};
+use hackernews_crawler::{
+    client::{ClientConfig, ClientError, HnClient},
+    core::{
+        CrawlTarget, DigestFormat, DomainPostRequest, Listing, PostId, UserOrder, UserPostRequest,
+    },
+};
+use output::{
+    ChangeField, Column, DomainField, Field, Format, Output, PostStatsField, StatusField,
+    TrendingField, UserField,
+};
+use tonic::transport::{Certificate, ClientTlsConfig, Identity};
+
+/// Module with table, json, ndjson & csv output of posts and stats
+mod output;
+
+/// Module with terminal browser of posts
+mod tui;
+
+/// Module with live view of the front page
+mod watch;
+
+#[derive(thiserror::Error, Debug)]
+enum Error {
+    #[error("{}", watch::describe(.0))]
+    Client(#[from] ClientError),
+    #[error("failed to read `{path}`: {source}")]
+    Read { path: PathBuf, source: io::Error },
+    #[error("wrong page range {first_page}-{last_page}, pages start from 1")]
+    PageRange { first_page: usize, last_page: usize },
+    #[error("failed to run tui: {0}")]
+    Tui(io::Error),
+    #[error("failed to write output: {0}")]
+    Output(#[from] io::Error),
+}
+
+#[derive(clap::Parser, Debug)]
+struct Args {
+    #[arg(short, long, default_value = "http://0.0.0.0:7777")]
+    address: String,
+    /// Key for servers with api keys
+    #[arg(long, env = "HN_API_KEY", hide_env_values = true)]
+    api_key: Option<String>,
+    /// PEM CA of the server certificate, system roots are used without it
+    #[arg(long)]
+    ca_cert: Option<PathBuf>,
+    /// PEM certificate for servers, which require client certificates
+    #[arg(long, requires = "client_key")]
+    client_cert: Option<PathBuf>,
+    #[arg(long, requires = "client_cert")]
+    client_key: Option<PathBuf>,
+    #[command(subcommand)]
+    action: Action,
+}
+
+#[allow(clippy::enum_variant_names)]
+#[derive(clap::Subcommand, Debug)]
+enum Action {
+    TopPosts {
+        #[command(flatten)]
+        output: OutputArgs<Field>,
+    },
+    UserPosts {
+        user: String,
+        #[command(flatten)]
+        output: OutputArgs<Field>,
+    },
+    /// Posts of a user, which were at the first page, the longest there first
+    UserTopPosts {
+        user: String,
+        #[command(flatten)]
+        output: OutputArgs<Field>,
+    },
+    /// Live view of the front page, new posts and rank changes are highlighted
+    Watch {
+        /// Seconds between refreshes
+        #[arg(long, default_value_t = 30, value_parser = clap::value_parser!(u64).range(1..))]
+        interval: u64,
+    },
+    /// Terminal browser of top posts, posts of a user and posts of a domain
+    Tui {
+        /// User of the user tab
+        #[arg(long)]
+        user: Option<String>,
+        /// Domain of the domain tab, e.g. `example.com`
+        #[arg(long)]
+        domain: Option<String>,
+    },
+    /// Posts linking to a domain, e.g. `example.com`
+    DomainPosts {
+        domain: String,
+        /// Only posts, which were at the first page
+        #[arg(long)]
+        first_page_only: bool,
+        #[command(flatten)]
+        output: OutputArgs<Field>,
+    },
+    /// Domains ranked by their appearances at the first page
+    TopDomains {
+        #[arg(long, default_value_t = 7)]
+        days: u32,
+        #[arg(long, default_value_t = 10)]
+        limit: u32,
+        #[command(flatten)]
+        output: OutputArgs<DomainField>,
+    },
+    /// Posts below the first page, which gain points the fastest
+    Trending {
+        /// Window of score snapshots
+        #[arg(long, default_value_t = 60)]
+        minutes: u32,
+        #[arg(long, default_value_t = 10)]
+        limit: u32,
+        #[command(flatten)]
+        output: OutputArgs<TrendingField>,
+    },
+    /// Posts, which entered, left or moved within the front page listing
+    Diff {
+        /// Compare the latest snapshot with the one this many hours ago
+        #[arg(long, default_value_t = 1)]
+        hours: u32,
+        #[command(flatten)]
+        output: OutputArgs<ChangeField>,
+    },
+    /// The best posts of each crawled listing
+    Digest {
+        /// Window till now, e.g. `24h` or `7d`
+        #[arg(long, default_value = "24h", value_parser = parse_window)]
+        since: chrono::Duration,
+        #[arg(long, default_value_t = DigestFormat::default())]
+        format: DigestFormat,
+        /// Posts per listing
+        #[arg(long, default_value_t = 10)]
+        limit: u32,
+    },
+    /// Time a post spent at the first page
+    PostStats {
+        post_id: PostId,
+        #[command(flatten)]
+        output: OutputArgs<PostStatsField>,
+    },
+    /// Stats of all crawled posts of a user
+    UserStats {
+        user: String,
+        #[command(flatten)]
+        output: OutputArgs<UserField>,
+    },
+    /// Authors of posts published in the last days ranked by their stats
+    Leaderboard {
+        #[arg(long, default_value_t = UserOrder::default())]
+        by: UserOrder,
+        #[arg(long, default_value_t = 30)]
+        days: u32,
+        #[arg(long, default_value_t = 10)]
+        limit: u32,
+        #[command(flatten)]
+        output: OutputArgs<UserField>,
+    },
+    /// Queue a crawl of listing pages
+    TriggerCrawl {
+        #[arg(long, default_value_t = Listing::Top)]
+        listing: Listing,
+        #[arg(long, default_value_t = 1)]
+        first_page: usize,
+        #[arg(long, default_value_t = CrawlTarget::DEFAULT_LAST_PAGE)]
+        last_page: usize,
+    },
+    /// Stop crawling, in-flight crawl is rolled back
+    PauseCrawler,
+    ResumeCrawler,
+    CrawlerStatus {
+        #[command(flatten)]
+        output: OutputArgs<StatusField>,
+    },
+}
+
+/// Output of commands with rows of `C` columns
+#[derive(clap::Args, Debug)]
+struct OutputArgs<C: Column + std::fmt::Debug + Send + Sync> {
+    /// `table`, `json`, `ndjson` or `csv`
+    #[arg(long, default_value_t = Format::default())]
+    format: Format,
+    /// Columns, e.g. `title,author,link`, a wrong one lists all of them
+    #[arg(long, value_delimiter = ',', value_parser = output::parse_field::<C>)]
+    fields: Vec<C>,
+}
+
+impl<C: Column + std::fmt::Debug + Send + Sync> OutputArgs<C> {
+    fn output(self) -> Output<std::io::StdoutLock<'static>, C> {
+        Output::new(std::io::stdout().lock(), self.format, self.fields)
+    }
+
+    fn print(self, rows: impl IntoIterator<Item = C::Row>) -> std::io::Result<()> {
+        let mut output = self.output();
+        for row in rows {
+            output.write(&row)?;
+        }
+        output.finish()
+    }
+}
+
+/// Window like `30m`, `24h` or `7d`
+fn parse_window(window: &str) -> Result<chrono::Duration, String> {
+    let error = || format!("expected e.g. `30m`, `24h` or `7d`, not `{window}`");
+    let (value, unit) = window.split_at(window.len().saturating_sub(1));
+    let value: i64 = value.parse().map_err(|_| error())?;
+    match unit {
+        "m" => Ok(chrono::Duration::minutes(value)),
+        "h" => Ok(chrono::Duration::hours(value)),
+        "d" => Ok(chrono::Duration::days(value)),
+        _ => Err(error()),
+    }
+}
+
+fn read(path: &Path) -> Result<Vec<u8>, Error> {
+    std::fs::read(path).map_err(|source| Error::Read {
+        path: path.to_owned(),
+        source,
+    })
+}
+
+/// Tls with custom certificates, `https://` addresses without them are
+/// checked against system roots
+fn tls_config(args: &Args) -> Result<Option<ClientTlsConfig>, Error> {
+    if args.ca_cert.is_none() && args.client_cert.is_none() {
+        return Ok(None);
+    }
+
+    let mut tls = ClientTlsConfig::new();
+    if let Some(ca_cert) = &args.ca_cert {
+        tls = tls.ca_certificate(Certificate::from_pem(read(ca_cert)?));
+    }
+    if let (Some(cert), Some(key)) = (&args.client_cert, &args.client_key) {
+        tls = tls.identity(Identity::from_pem(read(cert)?, read(key)?));
+    }
+    Ok(Some(tls))
+}
+
+async fn run(client: HnClient, action: Action) -> Result<(), Error> {
+    let now = chrono::Local::now().naive_utc();
+    let (mut stream, output) = match action {
+        Action::TopPosts { output } => (client.top_posts().await?, output),
+        Action::UserPosts { user, output } => (
+            client.user_posts(UserPostRequest::All { user }).await?,
+            output,
+        ),
+        Action::UserTopPosts { user, output } => (
+            client
+                .user_posts(UserPostRequest::WasAtFirstPage { user })
+                .await?,
+            output,
+        ),
+        Action::DomainPosts {
+            domain,
+            first_page_only,
+            output,
+        } => (
+            client
+                .domain_posts(DomainPostRequest {
+                    domain,
+                    was_at_first_page: first_page_only,
+                })
+                .await?,
+            output,
+        ),
+        Action::Watch { interval } => {
+            let fetch = || async { client.top_posts().await?.try_collect().await };
+            watch::run(fetch, Duration::from_secs(interval)).await;
+            return Ok(());
+        }
+        Action::Tui { user, domain } => {
+            return tui::run(client, user, domain).await.map_err(Error::Tui);
+        }
+        Action::TopDomains {
+            days,
+            limit,
+            output,
+        } => {
+            let since = now - chrono::Duration::days(days.into());
+            let domains = client.top_domains(since, None, limit).await?;
+            output.print(domains)?;
+            return Ok(());
+        }
+        Action::Trending {
+            minutes,
+            limit,
+            output,
+        } => {
+            let posts = client.trending_posts(minutes, limit).await?;
+            output.print(posts)?;
+            return Ok(());
+        }
+        Action::Diff { hours, output } => {
+            let from = now - chrono::Duration::hours(hours.into());
+            let diff = client.diff_snapshots(from, None).await?;
+            output.print(diff.changes)?;
+            return Ok(());
+        }
+        Action::Digest {
+            since,
+            format,
+            limit,
+        } => {
+            print!("{}", client.digest(now - since, None, limit, format).await?);
+            return Ok(());
+        }
+        Action::PostStats { post_id, output } => {
+            let stats = client.post_stats(post_id).await?;
+            if stats.is_none() {
+                eprintln!("The post was never crawled");
+            }
+            output.print(stats)?;
+            return Ok(());
+        }
+        Action::UserStats { user, output } => {
+            let stats = client.user_stats(user).await?;
+            if stats.is_none() {
+                eprintln!("No crawled posts of the user");
+            }
+            output.print(stats)?;
+            return Ok(());
+        }
+        Action::Leaderboard {
+            by,
+            days,
+            limit,
+            output,
+        } => {
+            let since = now - chrono::Duration::days(days.into());
+            let users = client.top_users(since, None, by, limit).await?;
+            output.print(users)?;
+            return Ok(());
+        }
+        Action::TriggerCrawl {
+            listing,
+            first_page,
+            last_page,
+        } => {
+            let target =
+                CrawlTarget::new(listing, first_page, last_page).ok_or(Error::PageRange {
+                    first_page,
+                    last_page,
+                })?;
+            client.trigger_crawl(target).await?;
+            return Ok(());
+        }
+        Action::PauseCrawler => {
+            client.pause_crawler().await?;
+            return Ok(());
+        }
+        Action::ResumeCrawler => {
+            client.resume_crawler().await?;
+            return Ok(());
+        }
+        Action::CrawlerStatus { output } => {
+            let status = client.crawler_status().await?;
+            output.print([status])?;
+            return Ok(());
+        }
+    };
+
+    let mut output = output.output();
+    while let Some(post) = stream.next().await {
+        output.write(&post?)?;
+    }
+    output.finish()?;
+    Ok(())
+}
+
+#[tokio::main]
+async fn main() {
+    let args = Args::parse();
+    tracing::info!("Run with args {args:?}");
+
+    let client = tls_config(&args).and_then(|tls| {
+        Ok(HnClient::new(ClientConfig {
+            tls,
+            address: args.address,
+            api_key: args.api_key,
+            ..ClientConfig::default()
+        })?)
+    });
+    let result = match client {
+        Ok(client) => run(client, args.action).await,
+        Err(err) => Err(err),
+    };
+    if let Err(err) = result {
+        eprintln!("error: {err}");
+        std::process::exit(1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tls_config() {
+        let args = Args::parse_from(["client", "top-posts"]);
+        assert!(tls_config(&args).unwrap().is_none());
+
+        let args = Args::parse_from(["client", "--ca-cert", "/missing/ca.pem", "top-posts"]);
+        let err = tls_config(&args).unwrap_err();
+        assert!(matches!(&err, Error::Read { path, .. } if path == Path::new("/missing/ca.pem")));
+    }
+
+    #[tokio::test]
+    async fn test_wrong_page_range() {
+        let client = HnClient::new(ClientConfig::default()).unwrap();
+        for (first_page, last_page) in [(0, 10), (5, 2)] {
+            let action = Action::TriggerCrawl {
+                listing: Listing::Top,
+                first_page,
+                last_page,
+            };
+            assert!(matches!(
+                run(client.clone(), action).await,
+                Err(Error::PageRange { .. })
+            ));
+        }
+    }
+}
@@ -0,0 +1,678 @@
+use std::{
+    fmt::Display,
+    io::{self, Write},
+    str::FromStr,
+};
+
+use hackernews_crawler::core::{
+    CrawlerStatus, DateTime, DomainStats, Post, PostStats, SnapshotChange, TrendingPost, UserStats,
+};
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, strum::Display, strum::EnumString)]
+#[strum(serialize_all = "lowercase")]
+pub enum Format {
+    /// Aligned columns for people
+    #[default]
+    Table,
+    /// One JSON array of all rows
+    Json,
+    /// One JSON object per line
+    Ndjson,
+    /// CSV with a header
+    Csv,
+}
+
+/// Column of output rows of one kind, `--fields` are parsed into it
+pub trait Column: Copy + Display + FromStr + strum::VariantNames + 'static {
+    type Row;
+
+    /// Columns without `--fields`
+    const DEFAULT: &'static [Self];
+
+    fn value(&self, row: &Self::Row) -> Value;
+}
+
+/// Column of `--fields`, the error lists the known ones
+pub fn parse_field<C: Column>(field: &str) -> Result<C, String> {
+    field
+        .parse()
+        .map_err(|_| format!("expected one of {}", C::VARIANTS.join(", ")))
+}
+
+/// Column of post output
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, strum::Display, strum::EnumString, strum::EnumVariantNames,
+)]
+#[strum(serialize_all = "snake_case")]
+pub enum Field {
+    PostId,
+    Title,
+    Author,
+    Url,
+    Link,
+    Domain,
+    Score,
+    PublicationMoment,
+    LastSnapshotMoment,
+}
+
+impl Column for Field {
+    type Row = Post;
+
+    const DEFAULT: &'static [Field] = &[
+        Field::PostId,
+        Field::PublicationMoment,
+        Field::Score,
+        Field::Author,
+        Field::Title,
+        Field::Link,
+    ];
+
+    fn value(&self, post: &Post) -> Value {
+        match self {
+            Field::PostId => Value::Int(post.post_id),
+            Field::Title => Value::Text(post.title.clone()),
+            Field::Author => Value::Text(post.author.clone()),
+            Field::Url => Value::Text(post.url.clone()),
+            Field::Link => post.link.clone().map_or(Value::Null, Value::Text),
+            Field::Domain => post.domain().map_or(Value::Null, Value::Text),
+            Field::Score => post.score.map_or(Value::Null, Value::Int),
+            Field::PublicationMoment => Value::Moment(post.publication_moment),
+            Field::LastSnapshotMoment => Value::Moment(post.last_snapshot_moment),
+        }
+    }
+}
+
+/// Column of domain output
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, strum::Display, strum::EnumString, strum::EnumVariantNames,
+)]
+#[strum(serialize_all = "snake_case")]
+pub enum DomainField {
+    Domain,
+    Posts,
+    Appearances,
+    LastSeen,
+}
+
+impl Column for DomainField {
+    type Row = DomainStats;
+
+    const DEFAULT: &'static [DomainField] = &[
+        DomainField::Domain,
+        DomainField::Posts,
+        DomainField::Appearances,
+        DomainField::LastSeen,
+    ];
+
+    fn value(&self, domain: &DomainStats) -> Value {
+        match self {
+            DomainField::Domain => Value::Text(domain.domain.clone()),
+            DomainField::Posts => Value::Int(domain.posts),
+            DomainField::Appearances => Value::Int(domain.appearances),
+            DomainField::LastSeen => Value::Moment(domain.last_seen),
+        }
+    }
+}
+
+/// Column of trending posts output
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, strum::Display, strum::EnumString, strum::EnumVariantNames,
+)]
+#[strum(serialize_all = "snake_case")]
+pub enum TrendingField {
+    PostId,
+    Title,
+    Author,
+    Link,
+    Score,
+    Rank,
+    PointsPerHour,
+    RankVelocity,
+}
+
+impl Column for TrendingField {
+    type Row = TrendingPost;
+
+    const DEFAULT: &'static [TrendingField] = &[
+        TrendingField::PostId,
+        TrendingField::Rank,
+        TrendingField::Score,
+        TrendingField::PointsPerHour,
+        TrendingField::RankVelocity,
+        TrendingField::Title,
+    ];
+
+    fn value(&self, trending: &TrendingPost) -> Value {
+        let post = &trending.post;
+        match self {
+            TrendingField::PostId => Field::PostId.value(post),
+            TrendingField::Title => Field::Title.value(post),
+            TrendingField::Author => Field::Author.value(post),
+            TrendingField::Link => Field::Link.value(post),
+            TrendingField::Score => Field::Score.value(post),
+            TrendingField::Rank => post.rank.map_or(Value::Null, Value::Int),
+            TrendingField::PointsPerHour => {
+                trending.points_per_hour.map_or(Value::Null, Value::Float)
+            }
+            TrendingField::RankVelocity => trending.rank_velocity.map_or(Value::Null, Value::Float),
+        }
+    }
+}
+
+/// Column of front page changes output
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, strum::Display, strum::EnumString, strum::EnumVariantNames,
+)]
+#[strum(serialize_all = "snake_case")]
+pub enum ChangeField {
+    PostId,
+    Title,
+    Author,
+    Link,
+    Score,
+    OldRank,
+    NewRank,
+}
+
+impl Column for ChangeField {
+    type Row = SnapshotChange;
+
+    const DEFAULT: &'static [ChangeField] = &[
+        ChangeField::OldRank,
+        ChangeField::NewRank,
+        ChangeField::PostId,
+        ChangeField::Title,
+    ];
+
+    fn value(&self, change: &SnapshotChange) -> Value {
+        let post = &change.post;
+        match self {
+            ChangeField::PostId => Field::PostId.value(post),
+            ChangeField::Title => Field::Title.value(post),
+            ChangeField::Author => Field::Author.value(post),
+            ChangeField::Link => Field::Link.value(post),
+            ChangeField::Score => Field::Score.value(post),
+            ChangeField::OldRank => change.old_rank.map_or(Value::Null, Value::Int),
+            ChangeField::NewRank => change.new_rank.map_or(Value::Null, Value::Int),
+        }
+    }
+}
+
+/// Column of post stats output
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, strum::Display, strum::EnumString, strum::EnumVariantNames,
+)]
+#[strum(serialize_all = "snake_case")]
+pub enum PostStatsField {
+    PostId,
+    FirstSeen,
+    LastSeen,
+    FrontPageMinutes,
+    Appearances,
+    BestRank,
+}
+
+impl Column for PostStatsField {
+    type Row = PostStats;
+
+    const DEFAULT: &'static [PostStatsField] = &[
+        PostStatsField::PostId,
+        PostStatsField::FirstSeen,
+        PostStatsField::LastSeen,
+        PostStatsField::FrontPageMinutes,
+        PostStatsField::Appearances,
+        PostStatsField::BestRank,
+    ];
+
+    fn value(&self, stats: &PostStats) -> Value {
+        match self {
+            PostStatsField::PostId => Value::Int(stats.post_id),
+            PostStatsField::FirstSeen => stats.first_seen.map_or(Value::Null, Value::Moment),
+            PostStatsField::LastSeen => stats.last_seen.map_or(Value::Null, Value::Moment),
+            PostStatsField::FrontPageMinutes => Value::Float(stats.front_page_minutes),
+            PostStatsField::Appearances => Value::Int(stats.appearances),
+            PostStatsField::BestRank => stats.best_rank.map_or(Value::Null, Value::Int),
+        }
+    }
+}
+
+/// Column of user stats output
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, strum::Display, strum::EnumString, strum::EnumVariantNames,
+)]
+#[strum(serialize_all = "snake_case")]
+pub enum UserField {
+    User,
+    Posts,
+    FirstPagePosts,
+    BestRank,
+    FrontPageMinutes,
+    AverageScore,
+}
+
+impl Column for UserField {
+    type Row = UserStats;
+
+    const DEFAULT: &'static [UserField] = &[
+        UserField::User,
+        UserField::Posts,
+        UserField::FirstPagePosts,
+        UserField::BestRank,
+        UserField::FrontPageMinutes,
+        UserField::AverageScore,
+    ];
+
+    fn value(&self, user: &UserStats) -> Value {
+        match self {
+            UserField::User => Value::Text(user.user.clone()),
+            UserField::Posts => Value::Int(user.posts),
+            UserField::FirstPagePosts => Value::Int(user.first_page_posts),
+            UserField::BestRank => user.best_rank.map_or(Value::Null, Value::Int),
+            UserField::FrontPageMinutes => Value::Float(user.front_page_minutes),
+            UserField::AverageScore => user.average_score.map_or(Value::Null, Value::Float),
+        }
+    }
+}
+
+/// Column of crawler status output
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, strum::Display, strum::EnumString, strum::EnumVariantNames,
+)]
+#[strum(serialize_all = "snake_case")]
+pub enum StatusField {
+    State,
+    Listing,
+    FirstPage,
+    LastPage,
+    Page,
+    LastError,
+    LastSnapshot,
+}
+
+impl Column for StatusField {
+    type Row = CrawlerStatus;
+
+    const DEFAULT: &'static [StatusField] = &[
+        StatusField::State,
+        StatusField::Listing,
+        StatusField::Page,
+        StatusField::LastSnapshot,
+        StatusField::LastError,
+    ];
+
+    fn value(&self, status: &CrawlerStatus) -> Value {
+        let target = status.crawl.as_ref().map(|crawl| &crawl.target);
+        let page = |page: usize| Value::Int(page as i64);
+        match self {
+            StatusField::State => Value::Text(
+                match (status.paused, &status.crawl) {
+                    (true, _) => "paused",
+                    (false, Some(_)) => "crawling",
+                    (false, None) => "idle",
+                }
+                .to_owned(),
+            ),
+            StatusField::Listing => target.map_or(Value::Null, |target| {
+                Value::Text(target.listing.to_string())
+            }),
+            StatusField::FirstPage => {
+                target.map_or(Value::Null, |target| page(target.first_page.get()))
+            }
+            StatusField::LastPage => {
+                target.map_or(Value::Null, |target| page(target.last_page.get()))
+            }
+            StatusField::Page => status
+                .crawl
+                .as_ref()
+                .and_then(|crawl| crawl.page)
+                .map_or(Value::Null, page),
+            StatusField::LastError => status.last_error.clone().map_or(Value::Null, Value::Text),
+            StatusField::LastSnapshot => status.last_snapshot.map_or(Value::Null, Value::Moment),
+        }
+    }
+}
+
+pub enum Value {
+    Null,
+    Int(i64),
+    Float(f64),
+    Text(String),
+    Moment(DateTime),
+}
+
+impl Value {
+    /// Moments are UTC, tables show them shorter
+    fn text(&self, format: Format) -> String {
+        match (self, format) {
+            (Value::Null, _) => String::new(),
+            (Value::Int(value), _) => value.to_string(),
+            (Value::Float(value), Format::Table) => format!("{value:.1}"),
+            (Value::Float(value), _) => value.to_string(),
+            (Value::Text(value), _) => value.clone(),
+            (Value::Moment(moment), Format::Table) => moment.format("%Y-%m-%d %H:%M").to_string(),
+            (Value::Moment(moment), _) => moment.format("%Y-%m-%dT%H:%M:%SZ").to_string(),
+        }
+    }
+
+    fn json(&self, format: Format) -> serde_json::Value {
+        match self {
+            Value::Null => serde_json::Value::Null,
+            Value::Int(value) => (*value).into(),
+            Value::Float(value) => {
+                serde_json::Number::from_f64(*value).map_or(serde_json::Value::Null, Into::into)
+            }
+            value => value.text(format).into(),
+        }
+    }
+}
+
+/// Writes rows one by one in `format`, tables are aligned, so they are
+/// written by [`Output::finish`] only
+pub struct Output<W: Write, C: Column> {
+    writer: W,
+    format: Format,
+    fields: Vec<C>,
+    /// Rows written so far
+    written: usize,
+    /// Cells of the table
+    rows: Vec<Vec<String>>,
+}
+
+impl<W: Write, C: Column> Output<W, C> {
+    pub fn new(writer: W, format: Format, fields: Vec<C>) -> Self {
+        let fields = if fields.is_empty() {
+            C::DEFAULT.to_vec()
+        } else {
+            fields
+        };
+        Self {
+            writer,
+            format,
+            fields,
+            written: 0,
+            rows: Vec::new(),
+        }
+    }
+
+    fn header(&self) -> Vec<String> {
+        self.fields.iter().map(ToString::to_string).collect()
+    }
+
+    fn cells(&self, row: &C::Row) -> Vec<String> {
+        self.fields
+            .iter()
+            .map(|field| field.value(row).text(self.format))
+            .collect()
+    }
+
+    fn object(&self, row: &C::Row) -> serde_json::Value {
+        self.fields
+            .iter()
+            .map(|field| (field.to_string(), field.value(row).json(self.format)))
+            .collect::<serde_json::Map<_, _>>()
+            .into()
+    }
+
+    fn write_csv_record(&mut self, record: Vec<String>) -> io::Result<()> {
+        let mut csv = csv::WriterBuilder::new()
+            .has_headers(false)
+            .from_writer(&mut self.writer);
+        csv.write_record(record)?;
+        csv.flush()
+    }
+
+    pub fn write(&mut self, row: &C::Row) -> io::Result<()> {
+        match self.format {
+            Format::Table => {
+                let cells = self.cells(row);
+                self.rows.push(cells);
+            }
+            Format::Json => {
+                let separator = if self.written == 0 { "[\n" } else { ",\n" };
+                let object = self.object(row);
+                write!(self.writer, "{separator}  {object}")?;
+            }
+            Format::Ndjson => {
+                let object = self.object(row);
+                writeln!(self.writer, "{object}")?;
+            }
+            Format::Csv => {
+                if self.written == 0 {
+                    self.write_csv_record(self.header())?;
+                }
+                let cells = self.cells(row);
+                self.write_csv_record(cells)?;
+            }
+        }
+        self.written += 1;
+        Ok(())
+    }
+
+    pub fn finish(mut self) -> io::Result<()> {
+        match self.format {
+            Format::Table => {
+                let header = self.header();
+                write_table(&mut self.writer, &header, &self.rows)?;
+            }
+            Format::Json if self.written == 0 => writeln!(self.writer, "[]")?,
+            Format::Json => writeln!(self.writer, "\n]")?,
+            Format::Ndjson => {}
+            Format::Csv if self.written == 0 => self.write_csv_record(self.header())?,
+            Format::Csv => {}
+        }
+        self.writer.flush()
+    }
+}
+
+/// Lines of the table with the header first. Columns are padded to the
+/// widest cell, lines are trimmed, so long titles don't leave trailing spaces
+pub fn table(header: &[String], rows: &[Vec<String>]) -> Vec<String> {
+    let width = |cell: &String| cell.chars().count();
+    let widths = (0..header.len())
+        .map(|column| {
+            std::iter::once(header)
+                .chain(rows.iter().map(Vec::as_slice))
+                .map(|row| width(&row[column]))
+                .max()
+                .unwrap_or_default()
+        })
+        .collect::<Vec<_>>();
+
+    std::iter::once(header)
+        .chain(rows.iter().map(Vec::as_slice))
+        .map(|row| {
+            let line = row
+                .iter()
+                .zip(&widths)
+                .map(|(cell, width)| format!("{cell:<width$}  "))
+                .collect::<String>();
+            line.trim_end().to_owned()
+        })
+        .collect()
+}
+
+fn write_table(writer: &mut impl Write, header: &[String], rows: &[Vec<String>]) -> io::Result<()> {
+    for line in table(header, rows) {
+        writeln!(writer, "{line}")?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn get_posts() -> Vec<Post> {
+        let moment = |hour| {
+            chrono::NaiveDate::from_ymd_opt(2023, 1, 15)
+                .unwrap()
+                .and_hms_opt(hour, 30, 0)
+                .unwrap()
+        };
+        vec![
+            Post {
+                post_id: 1,
+                title: "Rust, \"SQLite\"".to_owned(),
+                author: "pg".to_owned(),
+                url: "https://news.ycombinator.com/item?id=1".to_owned(),
+                link: Some("https://www.example.com/".to_owned()),
+                publication_moment: moment(8),
+                last_snapshot_moment: moment(12),
+                rank: None,
+                listing: None,
+                score: Some(120),
+            },
+            Post {
+                post_id: 22,
+                title: "Ask HN: Why?".to_owned(),
+                author: "dang".to_owned(),
+                url: "https://news.ycombinator.com/item?id=22".to_owned(),
+                link: None,
+                publication_moment: moment(9),
+                last_snapshot_moment: moment(12),
+                rank: None,
+                listing: None,
+                score: None,
+            },
+        ]
+    }
+
+    fn render(format: Format, fields: Vec<Field>) -> String {
+        let mut buffer = Vec::new();
+        let mut output = Output::new(&mut buffer, format, fields);
+        for post in get_posts() {
+            output.write(&post).unwrap();
+        }
+        output.finish().unwrap();
+        String::from_utf8(buffer).unwrap()
+    }
+
+    #[test]
+    fn test_parse_fields() {
+        assert_eq!("post_id".parse(), Ok(Field::PostId));
+        assert_eq!(
+            "last_snapshot_moment".parse(),
+            Ok(Field::LastSnapshotMoment)
+        );
+        assert!("rank".parse::<Field>().is_err());
+        assert_eq!(parse_field("last_seen"), Ok(DomainField::LastSeen));
+        assert_eq!(
+            parse_field::<DomainField>("rank"),
+            Err("expected one of domain, posts, appearances, last_seen".to_owned())
+        );
+    }
+
+    #[test]
+    fn test_stats() {
+        let user = UserStats {
+            user: "pg".to_owned(),
+            posts: 3,
+            first_page_posts: 2,
+            best_rank: None,
+            front_page_minutes: 92.25,
+            average_score: Some(41.5),
+        };
+        let render = |format, fields| {
+            let mut buffer = Vec::new();
+            let mut output = Output::new(&mut buffer, format, fields);
+            output.write(&user).unwrap();
+            output.finish().unwrap();
+            String::from_utf8(buffer).unwrap()
+        };
+
+        assert_eq!(
+            render(
+                Format::Table,
+                vec![
+                    UserField::User,
+                    UserField::BestRank,
+                    UserField::FrontPageMinutes
+                ]
+            ),
+            "user  best_rank  front_page_minutes\n\
+             pg               92.2\n"
+        );
+        assert_eq!(
+            render(Format::Ndjson, Vec::new()),
+            "{\"user\":\"pg\",\"posts\":3,\"first_page_posts\":2,\"best_rank\":null,\
+             \"front_page_minutes\":92.25,\"average_score\":41.5}\n"
+        );
+    }
+
+    #[test]
+    fn test_crawler_status() {
+        let status = CrawlerStatus {
+            paused: false,
+            crawl: Some(hackernews_crawler::core::CrawlProgress {
+                target: hackernews_crawler::core::CrawlTarget::default(),
+                page: Some(3),
+            }),
+            last_error: None,
+            last_snapshot: None,
+        };
+        let mut buffer = Vec::new();
+        let mut output = Output::new(&mut buffer, Format::Csv, Vec::<StatusField>::new());
+        output.write(&status).unwrap();
+        output.finish().unwrap();
+
+        assert_eq!(
+            String::from_utf8(buffer).unwrap(),
+            "state,listing,page,last_snapshot,last_error\ncrawling,top,3,,\n"
+        );
+    }
+
+    #[test]
+    fn test_table() {
+        assert_eq!(
+            render(
+                Format::Table,
+                vec![Field::PostId, Field::Score, Field::Title]
+            ),
+            "post_id  score  title\n\
+             1        120    Rust, \"SQLite\"\n\
+             22              Ask HN: Why?\n"
+        );
+        assert!(render(Format::Table, Vec::new())
+            .starts_with("post_id  publication_moment  score  author  title"));
+    }
+
+    #[test]
+    fn test_json() {
+        let json = render(
+            Format::Json,
+            vec![Field::PostId, Field::Link, Field::PublicationMoment],
+        );
+        let json: serde_json::Value = serde_json::from_str(&json).unwrap();
+
+        assert_eq!(
+            json,
+            serde_json::json!([
+                {"post_id": 1, "link": "https://www.example.com/", "publication_moment": "2023-01-15T08:30:00Z"},
+                {"post_id": 22, "link": null, "publication_moment": "2023-01-15T09:30:00Z"},
+            ])
+        );
+
+        let mut buffer = Vec::new();
+        Output::new(&mut buffer, Format::Json, Vec::<Field>::new())
+            .finish()
+            .unwrap();
+        assert_eq!(buffer, b"[]\n");
+    }
+
+    #[test]
+    fn test_ndjson() {
+        assert_eq!(
+            render(Format::Ndjson, vec![Field::PostId, Field::Domain]),
+            "{\"post_id\":1,\"domain\":\"example.com\"}\n{\"post_id\":22,\"domain\":null}\n"
+        );
+    }
+
+    #[test]
+    fn test_csv() {
+        assert_eq!(
+            render(Format::Csv, vec![Field::Title, Field::Author, Field::Score]),
+            "title,author,score\n\"Rust, \"\"SQLite\"\"\",pg,120\nAsk HN: Why?,dang,\n"
+        );
+    }
+}
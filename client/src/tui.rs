@@ -0,0 +1,797 @@
+use std::{
+    collections::HashMap,
+    future::Future,
+    io::{self, Stdout},
+};
+
+use crossterm::{
+    event::{Event, EventStream, KeyCode, KeyEvent, KeyEventKind, KeyModifiers},
+    execute,
+    terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
+};
+use futures::{StreamExt, TryStreamExt};
+use hackernews_crawler::{
+    client::{ClientError, HnClient, PostStream},
+    core::{DomainPostRequest, Post, PostId, PostSnapshot, PostStats, UserPostRequest},
+};
+use ratatui::{
+    backend::CrosstermBackend,
+    layout::{Constraint, Direction, Layout, Rect},
+    style::{Modifier, Style},
+    text::{Line, Text},
+    widgets::{Block, Borders, Cell, Paragraph, Row, Table, TableState, Tabs, Wrap},
+    Frame, Terminal,
+};
+use tokio::sync::mpsc::{self, UnboundedSender};
+
+use crate::watch::describe;
+
+/// Rows of post history in the detail pane, the latest ones
+const HISTORY_ROWS: usize = 50;
+/// Rows moved by page up & down
+const PAGE: usize = 10;
+
+const HELP: &str =
+    "q quit  tab switch  ↑↓ move  enter open link  c open comments  u user  d domain  r reload";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Tab {
+    Top,
+    User,
+    Domain,
+}
+
+impl Tab {
+    fn next(self) -> Self {
+        match self {
+            Tab::Top => Tab::User,
+            Tab::User => Tab::Domain,
+            Tab::Domain => Tab::Top,
+        }
+    }
+
+    fn previous(self) -> Self {
+        match self {
+            Tab::Top => Tab::Domain,
+            Tab::User => Tab::Top,
+            Tab::Domain => Tab::User,
+        }
+    }
+}
+
+/// Posts of one tab
+#[derive(Debug, Default)]
+struct PostList {
+    posts: Vec<Post>,
+    state: TableState,
+    loading: bool,
+    error: Option<String>,
+}
+
+impl PostList {
+    fn selected(&self) -> Option<&Post> {
+        self.posts.get(self.state.selected()?)
+    }
+
+    /// Moves the selection by `offset` rows within the list
+    fn scroll(&mut self, offset: isize) {
+        if self.posts.is_empty() {
+            return;
+        }
+        let selected = self.state.selected().unwrap_or_default();
+        let last = self.posts.len() - 1;
+        self.state
+            .select(Some(selected.saturating_add_signed(offset).min(last)));
+    }
+
+    fn load(&mut self, posts: Result<Vec<Post>, String>) {
+        self.loading = false;
+        match posts {
+            Ok(posts) => {
+                let selected = self
+                    .state
+                    .selected()
+                    .map(|selected| selected.min(posts.len().saturating_sub(1)));
+                self.state
+                    .select(selected.or(Some(0)).filter(|_| !posts.is_empty()));
+                self.posts = posts;
+                self.error = None;
+            }
+            Err(error) => self.error = Some(error),
+        }
+    }
+}
+
+/// Stats & history of a post for the detail pane
+#[derive(Debug, Clone, PartialEq)]
+struct Details {
+    stats: PostStats,
+    history: Vec<PostSnapshot>,
+}
+
+/// Work of the runner, the app itself doesn't do io
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Command {
+    LoadTop,
+    LoadUser(String),
+    LoadDomain(String),
+    LoadDetails(PostId),
+    Open(String),
+    Quit,
+}
+
+/// Result of a load command
+#[derive(Debug)]
+enum Message {
+    Posts(Tab, Result<Vec<Post>, String>),
+    Details(PostId, Result<Details, String>),
+}
+
+#[derive(Debug)]
+struct App {
+    tab: Tab,
+    top: PostList,
+    user: PostList,
+    domain: PostList,
+    /// Author of posts of the user tab
+    user_name: String,
+    /// Domain of posts of the domain tab
+    domain_name: String,
+    /// The user name or the domain of the current tab is being typed
+    editing: bool,
+    /// `None` while details are loaded
+    details: HashMap<PostId, Option<Result<Details, String>>>,
+    /// The latest error of a command, e.g. of opening a browser
+    status: Option<String>,
+}
+
+impl App {
+    fn new(user: Option<String>, domain: Option<String>) -> (Self, Vec<Command>) {
+        let mut app = Self {
+            tab: Tab::Top,
+            top: PostList {
+                loading: true,
+                ..PostList::default()
+            },
+            user: PostList::default(),
+            domain: PostList::default(),
+            user_name: user.clone().unwrap_or_default(),
+            domain_name: domain.clone().unwrap_or_default(),
+            editing: false,
+            details: HashMap::new(),
+            status: None,
+        };
+        let mut commands = vec![Command::LoadTop];
+        if let Some(user) = user {
+            app.user.loading = true;
+            commands.push(Command::LoadUser(user));
+        }
+        if let Some(domain) = domain {
+            app.domain.loading = true;
+            commands.push(Command::LoadDomain(domain));
+        }
+        (app, commands)
+    }
+
+    fn list(&self) -> &PostList {
+        match self.tab {
+            Tab::Top => &self.top,
+            Tab::User => &self.user,
+            Tab::Domain => &self.domain,
+        }
+    }
+
+    fn list_mut(&mut self) -> &mut PostList {
+        match self.tab {
+            Tab::Top => &mut self.top,
+            Tab::User => &mut self.user,
+            Tab::Domain => &mut self.domain,
+        }
+    }
+
+    /// User name or domain, which is typed in the current tab
+    fn query_mut(&mut self) -> Option<&mut String> {
+        match self.tab {
+            Tab::Top => None,
+            Tab::User => Some(&mut self.user_name),
+            Tab::Domain => Some(&mut self.domain_name),
+        }
+    }
+
+    /// Loads posts of the current tab, `None` until its user or domain is typed
+    fn load_command(&self) -> Option<Command> {
+        match self.tab {
+            Tab::Top => Some(Command::LoadTop),
+            Tab::User if !self.user_name.is_empty() => {
+                Some(Command::LoadUser(self.user_name.clone()))
+            }
+            Tab::Domain if !self.domain_name.is_empty() => {
+                Some(Command::LoadDomain(self.domain_name.clone()))
+            }
+            Tab::User | Tab::Domain => None,
+        }
+    }
+
+    /// Switches to `tab` and asks for its user or domain, if it's empty
+    fn switch_tab(&mut self, tab: Tab) {
+        self.tab = tab;
+        self.editing = self.load_command().is_none();
+    }
+
+    fn selected(&self) -> Option<&Post> {
+        self.list().selected()
+    }
+
+    /// Loads details of the selected post, if they aren't loaded yet
+    fn load_details(&mut self) -> Vec<Command> {
+        let Some(post_id) = self.selected().map(|post| post.post_id) else {
+            return Vec::new();
+        };
+        if self.details.contains_key(&post_id) {
+            return Vec::new();
+        }
+        self.details.insert(post_id, None);
+        vec![Command::LoadDetails(post_id)]
+    }
+
+    fn reload(&mut self) -> Vec<Command> {
+        let Some(command) = self.load_command() else {
+            self.editing = true;
+            return Vec::new();
+        };
+        self.list_mut().loading = true;
+        if let Some(post_id) = self.selected().map(|post| post.post_id) {
+            self.details.remove(&post_id);
+        }
+        vec![command]
+    }
+
+    fn on_key(&mut self, key: KeyEvent) -> Vec<Command> {
+        if key.modifiers.contains(KeyModifiers::CONTROL) && key.code == KeyCode::Char('c') {
+            return vec![Command::Quit];
+        }
+        if self.editing {
+            return self.on_edit_key(key);
+        }
+        self.status = None;
+
+        match key.code {
+            KeyCode::Char('q') | KeyCode::Esc => return vec![Command::Quit],
+            KeyCode::Tab | KeyCode::Right => self.switch_tab(self.tab.next()),
+            KeyCode::BackTab | KeyCode::Left => self.switch_tab(self.tab.previous()),
+            KeyCode::Char('1') => self.tab = Tab::Top,
+            KeyCode::Char('2') => self.tab = Tab::User,
+            KeyCode::Char('3') => self.tab = Tab::Domain,
+            KeyCode::Down | KeyCode::Char('j') => self.list_mut().scroll(1),
+            KeyCode::Up | KeyCode::Char('k') => self.list_mut().scroll(-1),
+            KeyCode::PageDown => self.list_mut().scroll(PAGE as isize),
+            KeyCode::PageUp => self.list_mut().scroll(-(PAGE as isize)),
+            KeyCode::Home | KeyCode::Char('g') => self.list_mut().scroll(isize::MIN),
+            KeyCode::End | KeyCode::Char('G') => self.list_mut().scroll(isize::MAX),
+            KeyCode::Enter | KeyCode::Char('o') => {
+                return self
+                    .selected()
+                    .map(|post| Command::Open(post.href().to_owned()))
+                    .into_iter()
+                    .collect()
+            }
+            KeyCode::Char('c') => {
+                return self
+                    .selected()
+                    .map(|post| Command::Open(post.url.clone()))
+                    .into_iter()
+                    .collect()
+            }
+            KeyCode::Char('u') | KeyCode::Char('/') => {
+                self.tab = Tab::User;
+                self.editing = true;
+            }
+            KeyCode::Char('d') => {
+                self.tab = Tab::Domain;
+                self.editing = true;
+            }
+            KeyCode::Char('r') => return self.reload(),
+            _ => {}
+        }
+        self.load_details()
+    }
+
+    fn on_edit_key(&mut self, key: KeyEvent) -> Vec<Command> {
+        let Some(query) = self.query_mut() else {
+            self.editing = false;
+            return Vec::new();
+        };
+        match key.code {
+            KeyCode::Char(char) => query.push(char),
+            KeyCode::Backspace => {
+                query.pop();
+            }
+            KeyCode::Esc => self.editing = false,
+            KeyCode::Enter if !query.trim().is_empty() => {
+                *query = query.trim().to_owned();
+                self.editing = false;
+                *self.list_mut() = PostList {
+                    loading: true,
+                    ..PostList::default()
+                };
+                return self.load_command().into_iter().collect();
+            }
+            _ => {}
+        }
+        Vec::new()
+    }
+
+    fn on_message(&mut self, message: Message) -> Vec<Command> {
+        match message {
+            Message::Posts(Tab::Top, posts) => self.top.load(posts),
+            Message::Posts(Tab::User, posts) => self.user.load(posts),
+            Message::Posts(Tab::Domain, posts) => self.domain.load(posts),
+            Message::Details(post_id, details) => {
+                self.details.insert(post_id, Some(details));
+            }
+        }
+        self.load_details()
+    }
+
+    fn draw(&mut self, frame: &mut Frame) {
+        let [tabs, body, footer] = split(
+            frame.size(),
+            Direction::Vertical,
+            [
+                Constraint::Length(1),
+                Constraint::Min(3),
+                Constraint::Length(1),
+            ],
+        );
+        let [posts, details] = split(
+            body,
+            Direction::Horizontal,
+            [Constraint::Percentage(60), Constraint::Percentage(40)],
+        );
+
+        let query_tab = |name: &str, query: &str| {
+            if query.is_empty() {
+                format!(" {name} ")
+            } else {
+                format!(" {name}: {query} ")
+            }
+        };
+        frame.render_widget(
+            Tabs::new(vec![
+                " Top ".to_owned(),
+                query_tab("User", &self.user_name),
+                query_tab("Domain", &self.domain_name),
+            ])
+            .select(match self.tab {
+                Tab::Top => 0,
+                Tab::User => 1,
+                Tab::Domain => 2,
+            })
+            .highlight_style(Style::default().add_modifier(Modifier::REVERSED)),
+            tabs,
+        );
+
+        self.draw_posts(frame, posts);
+        frame.render_widget(self.details_pane(), details);
+
+        let footer_text = if self.editing {
+            let (name, query) = match self.tab {
+                Tab::Domain => ("domain", &self.domain_name),
+                Tab::Top | Tab::User => ("user", &self.user_name),
+            };
+            format!("{name}: {query}▏ enter load  esc cancel")
+        } else if let Some(status) = &self.status {
+            status.clone()
+        } else {
+            HELP.to_owned()
+        };
+        frame.render_widget(Paragraph::new(footer_text), footer);
+    }
+
+    fn draw_posts(&mut self, frame: &mut Frame, area: Rect) {
+        let list = self.list();
+        let title = match (&list.error, list.loading) {
+            (Some(error), _) => format!(" {error} "),
+            (None, true) => " loading... ".to_owned(),
+            (None, false) => format!(" {} posts ", list.posts.len()),
+        };
+        let rows = list.posts.iter().map(|post| {
+            Row::new(vec![
+                Cell::from(post.rank.map(|rank| rank.to_string()).unwrap_or_default()),
+                Cell::from(
+                    post.score
+                        .map(|score| score.to_string())
+                        .unwrap_or_default(),
+                ),
+                Cell::from(post.author.clone()),
+                Cell::from(post.title.clone()),
+            ])
+        });
+        let table = Table::new(
+            rows,
+            [
+                Constraint::Length(4),
+                Constraint::Length(5),
+                Constraint::Length(15),
+                Constraint::Min(10),
+            ],
+        )
+        .header(
+            Row::new(["rank", "score", "author", "title"])
+                .style(Style::default().add_modifier(Modifier::BOLD)),
+        )
+        .block(Block::default().borders(Borders::ALL).title(title))
+        .highlight_style(Style::default().add_modifier(Modifier::REVERSED));
+
+        frame.render_stateful_widget(table, area, &mut self.list_mut().state);
+    }
+
+    fn details_pane(&self) -> Paragraph<'_> {
+        let block = Block::default().borders(Borders::ALL).title(" post ");
+        let Some(post) = self.selected() else {
+            return Paragraph::new("").block(block);
+        };
+
+        let mut lines = vec![
+            Line::styled(
+                post.title.clone(),
+                Style::default().add_modifier(Modifier::BOLD),
+            ),
+            Line::from(format!("by {}", post.author)),
+            Line::from(post.href().to_owned()),
+            Line::from(post.url.clone()),
+            Line::from(format!(
+                "published {}",
+                post.publication_moment.format("%Y-%m-%d %H:%M")
+            )),
+            Line::from(""),
+        ];
+        match self.details.get(&post.post_id) {
+            None | Some(None) => lines.push(Line::from("loading...")),
+            Some(Some(Err(error))) => lines.push(Line::from(format!("error: {error}"))),
+            Some(Some(Ok(details))) => lines.extend(details_lines(details)),
+        }
+
+        Paragraph::new(Text::from(lines))
+            .block(block)
+            .wrap(Wrap { trim: false })
+    }
+}
+
+fn split<const N: usize>(
+    area: Rect,
+    direction: Direction,
+    constraints: [Constraint; N],
+) -> [Rect; N] {
+    let areas = Layout::default()
+        .direction(direction)
+        .constraints(constraints)
+        .split(area);
+    std::array::from_fn(|index| areas[index])
+}
+
+fn details_lines(details: &Details) -> Vec<Line<'static>> {
+    let stats = &details.stats;
+    let mut lines = vec![Line::from(match (stats.first_seen, stats.last_seen) {
+        (Some(first), Some(last)) => format!(
+            "first page {} - {}, {:.0} min, {} snapshots",
+            first.format("%Y-%m-%d %H:%M"),
+            last.format("%Y-%m-%d %H:%M"),
+            stats.front_page_minutes,
+            stats.appearances
+        ),
+        _ => "never at the first page".to_owned(),
+    })];
+    if let Some(best_rank) = stats.best_rank {
+        lines.push(Line::from(format!("best rank {best_rank}")));
+    }
+
+    lines.push(Line::from(""));
+    lines.push(Line::styled(
+        "history",
+        Style::default().add_modifier(Modifier::BOLD),
+    ));
+    for snapshot in details.history.iter().rev().take(HISTORY_ROWS) {
+        let mut line = snapshot
+            .snapshot_moment
+            .format("%Y-%m-%d %H:%M")
+            .to_string();
+        if let (Some(rank), Some(listing)) = (snapshot.rank, snapshot.listing) {
+            line.push_str(&format!("  #{rank} {listing}"));
+        }
+        if let Some(score) = snapshot.score {
+            line.push_str(&format!("  {score} points"));
+        }
+        if snapshot.was_at_first_page {
+            line.push_str("  first page");
+        }
+        lines.push(Line::from(line));
+    }
+    lines
+}
+
+async fn load_details(client: &HnClient, post_id: PostId) -> Result<Details, String> {
+    let stats = client
+        .post_stats(post_id)
+        .await
+        .map_err(|err| describe(&err))?
+        .ok_or_else(|| "the post was never crawled".to_owned())?;
+    let history = client
+        .post_history(post_id)
+        .await
+        .map_err(|err| describe(&err))?;
+
+    Ok(Details { stats, history })
+}
+
+async fn load_posts(
+    posts: impl Future<Output = Result<PostStream, ClientError>>,
+) -> Result<Vec<Post>, String> {
+    let posts = posts.await.map_err(|err| describe(&err))?;
+    posts.try_collect().await.map_err(|err| describe(&err))
+}
+
+/// Runs the command in background, its result comes back as a message
+fn spawn(client: &HnClient, command: Command, sender: UnboundedSender<Message>) {
+    let client = client.clone();
+    tokio::spawn(async move {
+        let message = match command {
+            Command::LoadTop => Message::Posts(Tab::Top, load_posts(client.top_posts()).await),
+            Command::LoadUser(user) => Message::Posts(
+                Tab::User,
+                load_posts(client.user_posts(UserPostRequest::All { user })).await,
+            ),
+            Command::LoadDomain(domain) => Message::Posts(
+                Tab::Domain,
+                load_posts(client.domain_posts(DomainPostRequest {
+                    domain,
+                    was_at_first_page: false,
+                }))
+                .await,
+            ),
+            Command::LoadDetails(post_id) => {
+                Message::Details(post_id, load_details(&client, post_id).await)
+            }
+            Command::Open(_) | Command::Quit => return,
+        };
+        // The app is closed, when the receiver is dropped
+        let _ = sender.send(message);
+    });
+}
+
+/// Alternate screen in raw mode, the terminal is restored on drop,
+/// so errors and panics don't leave it broken
+struct Screen(Terminal<CrosstermBackend<Stdout>>);
+
+impl Screen {
+    fn enter() -> io::Result<Self> {
+        enable_raw_mode()?;
+        execute!(io::stdout(), EnterAlternateScreen)?;
+        Ok(Self(Terminal::new(CrosstermBackend::new(io::stdout()))?))
+    }
+}
+
+impl Drop for Screen {
+    fn drop(&mut self) {
+        let _ = disable_raw_mode();
+        let _ = execute!(io::stdout(), LeaveAlternateScreen);
+    }
+}
+
+/// Browser of top posts, posts of `user` and posts of `domain`, till the user quits
+pub async fn run(client: HnClient, user: Option<String>, domain: Option<String>) -> io::Result<()> {
+    let (mut app, mut commands) = App::new(user, domain);
+    let (sender, mut receiver) = mpsc::unbounded_channel();
+    let mut events = EventStream::new();
+    let mut screen = Screen::enter()?;
+
+    loop {
+        for command in commands.drain(..) {
+            match command {
+                Command::Quit => return Ok(()),
+                Command::Open(url) => {
+                    if let Err(err) = open::that_detached(&url) {
+                        app.status = Some(format!("failed to open {url}: {err}"));
+                    }
+                }
+                command => spawn(&client, command, sender.clone()),
+            }
+        }
+
+        screen.0.draw(|frame| app.draw(frame))?;
+
+        commands = tokio::select! {
+            event = events.next() => match event {
+                Some(Ok(Event::Key(key))) if key.kind == KeyEventKind::Press => app.on_key(key),
+                Some(Ok(_)) => Vec::new(),
+                Some(Err(err)) => return Err(err),
+                None => return Ok(()),
+            },
+            Some(message) = receiver.recv() => app.on_message(message),
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ratatui::backend::TestBackend;
+
+    use super::*;
+
+    fn post(post_id: PostId, rank: i64) -> Post {
+        let moment = chrono::NaiveDate::from_ymd_opt(2023, 1, 15)
+            .unwrap()
+            .and_hms_opt(8, 30, 0)
+            .unwrap();
+        Post {
+            post_id,
+            title: format!("post {post_id}"),
+            author: "pg".to_owned(),
+            url: format!("https://news.ycombinator.com/item?id={post_id}"),
+            link: Some(format!("https://example.com/{post_id}")),
+            publication_moment: moment,
+            last_snapshot_moment: moment,
+            rank: Some(rank),
+            listing: None,
+            score: Some(10),
+        }
+    }
+
+    fn key(code: KeyCode) -> KeyEvent {
+        KeyEvent::new(code, KeyModifiers::NONE)
+    }
+
+    fn get_app() -> App {
+        let (mut app, commands) = App::new(None, None);
+        assert_eq!(commands, vec![Command::LoadTop]);
+        let commands = app.on_message(Message::Posts(
+            Tab::Top,
+            Ok(vec![post(1, 1), post(2, 2), post(3, 3)]),
+        ));
+        assert_eq!(commands, vec![Command::LoadDetails(1)]);
+        app
+    }
+
+    #[test]
+    fn test_navigation() {
+        let mut app = get_app();
+
+        assert_eq!(
+            app.on_key(key(KeyCode::Down)),
+            vec![Command::LoadDetails(2)]
+        );
+        assert_eq!(app.on_key(key(KeyCode::Up)), Vec::new());
+        assert_eq!(app.on_key(key(KeyCode::End)), vec![Command::LoadDetails(3)]);
+        assert_eq!(app.on_key(key(KeyCode::Char('j'))), Vec::new());
+        assert_eq!(app.selected().unwrap().post_id, 3);
+        assert_eq!(
+            app.on_key(key(KeyCode::Enter)),
+            vec![Command::Open("https://example.com/3".to_owned())]
+        );
+        assert_eq!(
+            app.on_key(key(KeyCode::Char('c'))),
+            vec![Command::Open(
+                "https://news.ycombinator.com/item?id=3".to_owned()
+            )]
+        );
+        assert_eq!(app.on_key(key(KeyCode::Char('q'))), vec![Command::Quit]);
+    }
+
+    #[test]
+    fn test_user_tab() {
+        let mut app = get_app();
+
+        // The user is asked for, when the tab is empty
+        assert_eq!(app.on_key(key(KeyCode::Tab)), Vec::new());
+        assert!(app.editing);
+        for char in " pg".chars() {
+            app.on_key(key(KeyCode::Char(char)));
+        }
+        assert_eq!(
+            app.on_key(key(KeyCode::Enter)),
+            vec![Command::LoadUser("pg".to_owned())]
+        );
+        assert!(!app.editing && app.user.loading);
+
+        // Details of posts of both tabs are shared
+        assert_eq!(
+            app.on_message(Message::Posts(Tab::User, Ok(vec![post(1, 1), post(4, 4)]))),
+            Vec::new()
+        );
+        assert_eq!(
+            app.on_key(key(KeyCode::Down)),
+            vec![Command::LoadDetails(4)]
+        );
+        assert_eq!(
+            app.on_key(key(KeyCode::Char('r'))),
+            vec![Command::LoadUser("pg".to_owned())]
+        );
+
+        app.on_message(Message::Posts(
+            Tab::User,
+            Err("server is unavailable".to_owned()),
+        ));
+        assert_eq!(app.user.error.as_deref(), Some("server is unavailable"));
+        assert_eq!(app.user.posts.len(), 2);
+    }
+
+    #[test]
+    fn test_domain_tab() {
+        let mut app = get_app();
+
+        // Back tab goes from the top tab to the domain one
+        assert_eq!(app.on_key(key(KeyCode::BackTab)), Vec::new());
+        assert!(app.editing && app.tab == Tab::Domain);
+        for char in "example.com ".chars() {
+            app.on_key(key(KeyCode::Char(char)));
+        }
+        assert_eq!(
+            app.on_key(key(KeyCode::Enter)),
+            vec![Command::LoadDomain("example.com".to_owned())]
+        );
+        assert!(app.domain.loading && app.user_name.is_empty());
+
+        assert_eq!(
+            app.on_message(Message::Posts(Tab::Domain, Ok(vec![post(5, 5)]))),
+            vec![Command::LoadDetails(5)]
+        );
+        assert_eq!(
+            app.on_key(key(KeyCode::Char('r'))),
+            vec![Command::LoadDomain("example.com".to_owned())]
+        );
+
+        // The user tab still asks for its user
+        assert_eq!(app.on_key(key(KeyCode::BackTab)), Vec::new());
+        assert!(app.editing && app.tab == Tab::User);
+        app.on_key(key(KeyCode::Esc));
+        // Details of the selected post were dropped by the reload
+        assert_eq!(
+            app.on_key(key(KeyCode::Char('d'))),
+            vec![Command::LoadDetails(5)]
+        );
+        assert!(app.editing && app.tab == Tab::Domain);
+    }
+
+    #[test]
+    fn test_draw() {
+        let mut app = get_app();
+        let moment = app.top.posts[0].publication_moment;
+        app.on_message(Message::Details(
+            1,
+            Ok(Details {
+                stats: PostStats {
+                    post_id: 1,
+                    first_seen: Some(moment),
+                    last_seen: Some(moment),
+                    front_page_minutes: 42.0,
+                    appearances: 3,
+                    best_rank: Some(1),
+                },
+                history: vec![PostSnapshot {
+                    snapshot_moment: moment,
+                    listing: Some(hackernews_crawler::core::Listing::Top),
+                    rank: Some(7),
+                    score: Some(95),
+                    was_at_first_page: true,
+                }],
+            }),
+        ));
+
+        let mut terminal = Terminal::new(TestBackend::new(140, 20)).unwrap();
+        terminal.draw(|frame| app.draw(frame)).unwrap();
+        let screen = terminal
+            .backend()
+            .buffer()
+            .content
+            .chunks(140)
+            .map(|line| line.iter().map(|cell| cell.symbol()).collect::<String>())
+            .collect::<Vec<_>>()
+            .join("\n");
+
+        assert!(screen.contains(" 3 posts "));
+        assert!(screen.contains("1    10    pg              post 1"));
+        assert!(screen.contains("best rank 1"));
+        assert!(screen.contains("2023-01-15 08:30  #7 top  95 points  first page"));
+        assert!(screen.contains("q quit"));
+    }
+}
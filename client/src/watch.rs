@@ -0,0 +1,304 @@
+use std::{
+    collections::HashMap,
+    future::Future,
+    io::{IsTerminal, Write},
+    time::Duration,
+};
+
+use hackernews_crawler::{
+    client::ClientError,
+    core::{Post, PostId},
+};
+
+use crate::output;
+
+/// The first retry after an error, next ones double up to the refresh interval
+const RETRY_DELAY: Duration = Duration::from_secs(1);
+
+const CLEAR_SCREEN: &str = "\x1b[2J\x1b[H";
+const BOLD_GREEN: &str = "\x1b[1;32m";
+const GREEN: &str = "\x1b[32m";
+const RED: &str = "\x1b[31m";
+const RESET: &str = "\x1b[0m";
+
+/// Change of a post since the previous refresh
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Change {
+    New,
+    Up(i64),
+    Down(i64),
+    Same,
+}
+
+impl Change {
+    /// Nothing is new at the first refresh
+    fn of(previous: Option<&HashMap<PostId, Option<i64>>>, post: &Post) -> Self {
+        let Some(previous) = previous else {
+            return Change::Same;
+        };
+        match (previous.get(&post.post_id), post.rank) {
+            (None, _) => Change::New,
+            (Some(Some(old)), Some(new)) if old > &new => Change::Up(old - new),
+            (Some(Some(old)), Some(new)) if old < &new => Change::Down(new - old),
+            _ => Change::Same,
+        }
+    }
+
+    fn text(&self) -> String {
+        match self {
+            Change::New => "new".to_owned(),
+            Change::Up(ranks) => format!("▲{ranks}"),
+            Change::Down(ranks) => format!("▼{ranks}"),
+            Change::Same => String::new(),
+        }
+    }
+
+    fn color(&self) -> Option<&'static str> {
+        match self {
+            Change::New => Some(BOLD_GREEN),
+            Change::Up(_) => Some(GREEN),
+            Change::Down(_) => Some(RED),
+            Change::Same => None,
+        }
+    }
+}
+
+/// Front page of the latest successful refresh
+#[derive(Debug, Default)]
+struct FrontPage {
+    posts: Vec<Post>,
+    changes: Vec<Change>,
+    /// Posts of the previous refresh, which are gone
+    left: usize,
+    /// Ranks of posts, `None` before the first refresh
+    ranks: Option<HashMap<PostId, Option<i64>>>,
+}
+
+impl FrontPage {
+    fn update(&mut self, posts: Vec<Post>) {
+        let ranks = posts
+            .iter()
+            .map(|post| (post.post_id, post.rank))
+            .collect::<HashMap<_, _>>();
+        self.changes = posts
+            .iter()
+            .map(|post| Change::of(self.ranks.as_ref(), post))
+            .collect();
+        self.left = self.ranks.as_ref().map_or(0, |previous| {
+            previous
+                .keys()
+                .filter(|post_id| !ranks.contains_key(post_id))
+                .count()
+        });
+        self.ranks = Some(ranks);
+        self.posts = posts;
+    }
+
+    /// Screen with the posts and a status line, `error` is of the latest
+    /// refresh, the posts of the one before stay on screen then
+    fn render(&self, status: &str, error: Option<&str>, color: bool) -> String {
+        let mut screen = String::new();
+        if color {
+            screen.push_str(CLEAR_SCREEN);
+        }
+
+        let snapshot = self
+            .posts
+            .iter()
+            .map(|post| post.last_snapshot_moment)
+            .max();
+        screen.push_str("Hacker News front page");
+        if let Some(snapshot) = snapshot {
+            screen.push_str(&format!(
+                ", snapshot {} UTC",
+                snapshot.format("%Y-%m-%d %H:%M")
+            ));
+        }
+        screen.push_str(&format!(", {status}\n"));
+        match error {
+            Some(error) if color => screen.push_str(&format!("{RED}error: {error}{RESET}\n")),
+            Some(error) => screen.push_str(&format!("error: {error}\n")),
+            None => {}
+        }
+        if self.left > 0 {
+            screen.push_str(&format!(
+                "left the front page since the last refresh: {}\n",
+                self.left
+            ));
+        }
+        screen.push('\n');
+
+        let header = ["rank", "change", "score", "author", "title"].map(str::to_owned);
+        let rows = self
+            .posts
+            .iter()
+            .zip(&self.changes)
+            .map(|(post, change)| {
+                vec![
+                    post.rank.map(|rank| rank.to_string()).unwrap_or_default(),
+                    change.text(),
+                    post.score
+                        .map(|score| score.to_string())
+                        .unwrap_or_default(),
+                    post.author.clone(),
+                    post.title.clone(),
+                ]
+            })
+            .collect::<Vec<_>>();
+        let mut lines = output::table(&header, &rows).into_iter();
+        screen.extend(lines.next().map(|header| header + "\n"));
+        for (line, change) in lines.zip(&self.changes) {
+            match change.color().filter(|_| color) {
+                Some(code) => screen.push_str(&format!("{code}{line}{RESET}\n")),
+                None => screen.push_str(&format!("{line}\n")),
+            }
+        }
+        screen
+    }
+}
+
+/// Error for people, the down server is the usual one
+pub fn describe(err: &ClientError) -> String {
+    match err {
+        ClientError::Status(status) if status.code() == tonic::Code::Unavailable => {
+            format!("server is unavailable: {}", status.message())
+        }
+        err => err.to_string(),
+    }
+}
+
+/// Refreshes the front page every `interval` till the process is stopped.
+/// Errors are shown over the latest posts and refreshes are retried sooner,
+/// the channel of `fetch` must reconnect by itself
+pub async fn run<F, Fut>(mut fetch: F, interval: Duration)
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<Vec<Post>, ClientError>>,
+{
+    let color = std::io::stdout().is_terminal();
+    let mut page = FrontPage::default();
+    let mut retry = RETRY_DELAY;
+
+    loop {
+        let (error, delay) = match fetch().await {
+            Ok(posts) => {
+                page.update(posts);
+                retry = RETRY_DELAY;
+                (None, interval)
+            }
+            Err(err) => {
+                let delay = retry.min(interval);
+                retry = (retry * 2).min(interval);
+                (
+                    Some(format!(
+                        "{}, reconnecting in {}s",
+                        describe(&err),
+                        delay.as_secs()
+                    )),
+                    delay,
+                )
+            }
+        };
+
+        let status = format!(
+            "checked at {}, every {}s",
+            chrono::Local::now().format("%H:%M:%S"),
+            interval.as_secs()
+        );
+        let mut stdout = std::io::stdout().lock();
+        // Closed stdout, e.g. of `watch | head`, ends the watch
+        if write!(stdout, "{}", page.render(&status, error.as_deref(), color))
+            .and_then(|()| stdout.flush())
+            .is_err()
+        {
+            return;
+        }
+        drop(stdout);
+
+        tokio::time::sleep(delay).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn post(post_id: PostId, rank: Option<i64>) -> Post {
+        let moment = chrono::NaiveDate::from_ymd_opt(2023, 1, 15)
+            .unwrap()
+            .and_hms_opt(8, 30, 0)
+            .unwrap();
+        Post {
+            post_id,
+            title: format!("post {post_id}"),
+            author: "pg".to_owned(),
+            url: format!("https://news.ycombinator.com/item?id={post_id}"),
+            link: None,
+            publication_moment: moment,
+            last_snapshot_moment: moment,
+            rank,
+            listing: None,
+            score: Some(post_id * 10),
+        }
+    }
+
+    #[test]
+    fn test_changes() {
+        let mut page = FrontPage::default();
+        page.update(vec![post(1, Some(1)), post(2, Some(2)), post(3, Some(3))]);
+        assert_eq!(page.changes, vec![Change::Same; 3]);
+
+        page.update(vec![
+            post(3, Some(1)),
+            post(4, Some(2)),
+            post(1, Some(3)),
+            post(5, None),
+        ]);
+        assert_eq!(
+            page.changes,
+            vec![Change::Up(2), Change::New, Change::Down(2), Change::New]
+        );
+        assert_eq!(page.left, 1);
+
+        page.update(vec![
+            post(3, Some(1)),
+            post(4, Some(2)),
+            post(1, Some(3)),
+            post(5, None),
+        ]);
+        assert_eq!(page.changes, vec![Change::Same; 4]);
+        assert_eq!(page.left, 0);
+    }
+
+    #[test]
+    fn test_render() {
+        let mut page = FrontPage::default();
+        page.update(vec![post(1, Some(1)), post(2, Some(2))]);
+        page.update(vec![post(2, Some(1)), post(3, Some(2))]);
+
+        assert_eq!(
+            page.render("refreshed", None, false),
+            "Hacker News front page, snapshot 2023-01-15 08:30 UTC, refreshed\n\
+             left the front page since the last refresh: 1\n\
+             \n\
+             rank  change  score  author  title\n\
+             1     ▲1      20     pg      post 2\n\
+             2     new     30     pg      post 3\n"
+        );
+
+        let screen = page.render(
+            "refreshed",
+            Some(&describe(
+                &tonic::Status::unavailable("connection refused").into(),
+            )),
+            true,
+        );
+        assert!(screen.starts_with(CLEAR_SCREEN));
+        assert!(screen.contains(&format!(
+            "{RED}error: server is unavailable: connection refused{RESET}\n"
+        )));
+        assert!(screen.contains(&format!(
+            "{BOLD_GREEN}2     new     30     pg      post 3{RESET}\n"
+        )));
+    }
+}
@@ -1,7 +1,12 @@
+use std::{env, path::PathBuf};
+
 fn main() -> Result<(), String> {
+    let out_dir = PathBuf::from(env::var("OUT_DIR").map_err(|err| err.to_string())?);
+
     tonic_build::configure()
         .build_client(true)
         .build_server(true)
+        .file_descriptor_set_path(out_dir.join("hackernews_proxy_descriptor.bin"))
         .compile(&["proto/hackernews_proxy.proto"], &["proto"])
         .map_err(|err| format!("Failed to compile proto: {err}!"))
 }
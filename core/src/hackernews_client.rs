@@ -0,0 +1,519 @@
+use std::{future::Future, time::Duration};
+
+use futures::{stream::BoxStream, StreamExt};
+use tonic::{
+    codegen::InterceptedService,
+    metadata::{Ascii, MetadataValue},
+    service::Interceptor,
+    transport::{Channel, ClientTlsConfig, Endpoint},
+    Code, Request, Response, Status,
+};
+
+pub use crate::retry::RetryPolicy;
+use crate::{
+    hackernews_core::{
+        CrawlTarget, CrawlerStatus, DateTime, DigestFormat, DomainPostRequest, DomainStats, Post,
+        PostId, PostSnapshot, PostStats, SnapshotDiff, TrendingPost, UserOrder, UserPostRequest,
+        UserStats,
+    },
+    hackernews_proxy_proto::{
+        self as proto, admin_service_client::AdminServiceClient,
+        post_service_client::PostServiceClient,
+    },
+};
+
+#[derive(thiserror::Error, Debug)]
+pub enum ClientError {
+    #[error("wrong server address `{address}`: {source}")]
+    Address {
+        address: String,
+        source: tonic::transport::Error,
+    },
+    #[error("wrong tls config: {0}")]
+    Tls(tonic::transport::Error),
+    #[error("api key must be printable ascii")]
+    ApiKey,
+    /// Status is boxed, it's too large for a `Result`
+    #[error("{}: {}", .0.code(), .0.message())]
+    Status(Box<Status>),
+    #[error("wrong response from server: {0:?}")]
+    Response(proto::Error),
+}
+
+impl From<Status> for ClientError {
+    fn from(status: Status) -> Self {
+        Self::Status(Box::new(status))
+    }
+}
+
+impl From<proto::Error> for ClientError {
+    fn from(error: proto::Error) -> Self {
+        Self::Response(error)
+    }
+}
+
+impl ClientError {
+    /// Code of a failed call, `None` if the call wasn't made
+    pub fn code(&self) -> Option<Code> {
+        match self {
+            ClientError::Status(status) => Some(status.code()),
+            _ => None,
+        }
+    }
+
+    /// The server is down or restarting, so the call may succeed later.
+    /// Rate limited calls aren't retried, the limit refills slower than backoff
+    pub fn is_retryable(&self) -> bool {
+        self.code() == Some(Code::Unavailable)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ClientConfig {
+    /// `http://` or `https://` address of the server
+    pub address: String,
+    /// Key for servers with api keys
+    pub api_key: Option<String>,
+    /// Tls with custom certificates, `https://` addresses without it are
+    /// checked against system roots
+    pub tls: Option<ClientTlsConfig>,
+    pub connect_timeout: Duration,
+    /// Deadline of every call, a stream must start within it
+    pub timeout: Option<Duration>,
+    /// Retries of calls, which failed with a retryable error, see
+    /// [`ClientError::is_retryable`]. Streams are retried only till they start
+    pub retry: RetryPolicy,
+}
+
+impl Default for ClientConfig {
+    fn default() -> Self {
+        Self {
+            address: "http://0.0.0.0:7777".to_owned(),
+            api_key: None,
+            tls: None,
+            connect_timeout: Duration::from_secs(5),
+            timeout: Some(Duration::from_secs(30)),
+            retry: RetryPolicy::default(),
+        }
+    }
+}
+
+/// Adds `authorization` metadata with the api key to every call
+#[derive(Debug, Clone)]
+pub struct Authorization(Option<MetadataValue<Ascii>>);
+
+impl Interceptor for Authorization {
+    fn call(&mut self, mut request: Request<()>) -> Result<Request<()>, Status> {
+        if let Some(authorization) = &self.0 {
+            request
+                .metadata_mut()
+                .insert("authorization", authorization.clone());
+        }
+        Ok(request)
+    }
+}
+
+type Service = InterceptedService<Channel, Authorization>;
+
+/// Posts streamed by the server, a broken stream ends with an error
+pub type PostStream = BoxStream<'static, Result<Post, ClientError>>;
+
+/// Client of post & admin services of the server
+///
+/// The channel is connected lazily and reconnects by itself, so a client
+/// outlives restarts of the server. It's cheap to clone
+#[derive(Debug, Clone)]
+pub struct HnClient {
+    posts: PostServiceClient<Service>,
+    admin: AdminServiceClient<Service>,
+    retry: RetryPolicy,
+}
+
+impl HnClient {
+    /// Must be called within a tokio runtime, the channel is driven by it
+    pub fn new(config: ClientConfig) -> Result<Self, ClientError> {
+        let mut endpoint = Endpoint::from_shared(config.address.clone()).map_err(|source| {
+            ClientError::Address {
+                address: config.address,
+                source,
+            }
+        })?;
+        endpoint = endpoint.connect_timeout(config.connect_timeout);
+        if let Some(timeout) = config.timeout {
+            endpoint = endpoint.timeout(timeout);
+        }
+        if let Some(tls) = config.tls {
+            endpoint = endpoint.tls_config(tls).map_err(ClientError::Tls)?;
+        }
+
+        let authorization = Authorization(
+            config
+                .api_key
+                .map(|api_key| format!("Bearer {api_key}").parse())
+                .transpose()
+                .map_err(|_| ClientError::ApiKey)?,
+        );
+        let channel = endpoint.connect_lazy();
+        Ok(Self {
+            posts: PostServiceClient::with_interceptor(channel.clone(), authorization.clone()),
+            admin: AdminServiceClient::with_interceptor(channel, authorization),
+            retry: config.retry,
+        })
+    }
+
+    /// Calls `method` till it succeeds, fails with an error, which is not
+    /// retryable, or retries are over
+    async fn call<C, T, F, Fut>(&self, client: &C, mut method: F) -> Result<T, ClientError>
+    where
+        C: Clone,
+        F: FnMut(C) -> Fut,
+        Fut: Future<Output = Result<Response<T>, Status>>,
+    {
+        let mut retry = 0;
+        loop {
+            match method(client.clone()).await.map_err(ClientError::from) {
+                Ok(response) => return Ok(response.into_inner()),
+                Err(err) if err.is_retryable() && retry < self.retry.max_retries => {
+                    let delay = self.retry.backoff(retry);
+                    tracing::debug!(%err, retry, ?delay, "retrying call");
+                    tokio::time::sleep(delay).await;
+                    retry += 1;
+                }
+                Err(err) => return Err(err),
+            }
+        }
+    }
+
+    async fn posts_stream<F, Fut>(&self, method: F) -> Result<PostStream, ClientError>
+    where
+        F: FnMut(PostServiceClient<Service>) -> Fut,
+        Fut: Future<Output = Result<Response<tonic::Streaming<proto::Post>>, Status>>,
+    {
+        let stream = self.call(&self.posts, method).await?;
+        Ok(Box::pin(stream.map(|post| {
+            Ok(<Result<Post, proto::Error>>::from(post?)?)
+        })))
+    }
+
+    /// Posts of the latest first page snapshot by rank
+    pub async fn top_posts(&self) -> Result<PostStream, ClientError> {
+        self.posts_stream(|mut client| async move {
+            client
+                .get_top_posts(Request::new(proto::TopPostRequest {}))
+                .await
+        })
+        .await
+    }
+
+    pub async fn user_posts(&self, request: UserPostRequest) -> Result<PostStream, ClientError> {
+        let request = proto::UserPostRequest::from(request);
+        self.posts_stream(|mut client| {
+            let request = request.clone();
+            async move { client.get_user_posts(Request::new(request)).await }
+        })
+        .await
+    }
+
+    pub async fn domain_posts(
+        &self,
+        request: DomainPostRequest,
+    ) -> Result<PostStream, ClientError> {
+        let request = proto::DomainPostRequest::from(request);
+        self.posts_stream(|mut client| {
+            let request = request.clone();
+            async move { client.get_domain_posts(Request::new(request)).await }
+        })
+        .await
+    }
+
+    /// Domains ranked by their posts at the first page in `since..until`,
+    /// `until` is now if not set
+    pub async fn top_domains(
+        &self,
+        since: DateTime,
+        until: Option<DateTime>,
+        limit: u32,
+    ) -> Result<Vec<DomainStats>, ClientError> {
+        let request = proto::TopDomainsRequest {
+            since: Some(since.into()),
+            until: until.map(Into::into),
+            limit,
+        };
+        let response = self
+            .call(&self.posts, |mut client| {
+                let request = request.clone();
+                async move { client.get_top_domains(Request::new(request)).await }
+            })
+            .await?;
+        Ok(response
+            .domains
+            .into_iter()
+            .map(Result::from)
+            .collect::<Result<_, _>>()?)
+    }
+
+    /// Posts below the first page, which gain points the fastest
+    pub async fn trending_posts(
+        &self,
+        window_minutes: u32,
+        limit: u32,
+    ) -> Result<Vec<TrendingPost>, ClientError> {
+        let request = proto::TrendingPostsRequest {
+            window_minutes,
+            limit,
+        };
+        let response = self
+            .call(&self.posts, |mut client| {
+                let request = request.clone();
+                async move { client.get_trending_posts(Request::new(request)).await }
+            })
+            .await?;
+        Ok(response
+            .posts
+            .into_iter()
+            .map(Result::from)
+            .collect::<Result<_, _>>()?)
+    }
+
+    /// Changes of the front page between the snapshots at `from` and `to`,
+    /// `to` is now if not set
+    pub async fn diff_snapshots(
+        &self,
+        from: DateTime,
+        to: Option<DateTime>,
+    ) -> Result<SnapshotDiff, ClientError> {
+        let request = proto::DiffSnapshotsRequest {
+            from: Some(from.into()),
+            to: to.map(Into::into),
+        };
+        let diff = self
+            .call(&self.posts, |mut client| {
+                let request = request.clone();
+                async move { client.diff_snapshots(Request::new(request)).await }
+            })
+            .await?;
+        Ok(<Result<_, _>>::from(diff)?)
+    }
+
+    /// Digest of `since..until` rendered in `format`, `until` is now if not set
+    pub async fn digest(
+        &self,
+        since: DateTime,
+        until: Option<DateTime>,
+        limit: u32,
+        format: DigestFormat,
+    ) -> Result<String, ClientError> {
+        let request = proto::DigestRequest {
+            since: Some(since.into()),
+            until: until.map(Into::into),
+            limit,
+            format: proto::DigestFormat::from(format).into(),
+        };
+        let digest = self
+            .call(&self.posts, |mut client| {
+                let request = request.clone();
+                async move { client.get_digest(Request::new(request)).await }
+            })
+            .await?;
+        Ok(digest.content)
+    }
+
+    /// `None` if the post was never crawled
+    pub async fn post_stats(&self, post_id: PostId) -> Result<Option<PostStats>, ClientError> {
+        let stats = self
+            .call(&self.posts, |mut client| async move {
+                client
+                    .get_post_stats(Request::new(proto::PostStatsRequest { post_id }))
+                    .await
+            })
+            .await;
+        match stats {
+            Ok(stats) => Ok(Some(<Result<_, _>>::from(stats)?)),
+            Err(err) if err.code() == Some(Code::NotFound) => Ok(None),
+            Err(err) => Err(err),
+        }
+    }
+
+    /// Samples of the post by time, empty if it was never crawled
+    pub async fn post_history(&self, post_id: PostId) -> Result<Vec<PostSnapshot>, ClientError> {
+        let history = self
+            .call(&self.posts, |mut client| async move {
+                client
+                    .get_post_history(Request::new(proto::PostHistoryRequest { post_id }))
+                    .await
+            })
+            .await?;
+        Ok(history
+            .snapshots
+            .into_iter()
+            .map(Result::from)
+            .collect::<Result<_, _>>()?)
+    }
+
+    /// `None` if there are no crawled posts of the user
+    pub async fn user_stats(&self, user: String) -> Result<Option<UserStats>, ClientError> {
+        let stats = self
+            .call(&self.posts, |mut client| {
+                let user = user.clone();
+                async move {
+                    client
+                        .get_user_stats(Request::new(proto::UserStatsRequest { user }))
+                        .await
+                }
+            })
+            .await;
+        match stats {
+            Ok(stats) => Ok(Some(stats.into())),
+            Err(err) if err.code() == Some(Code::NotFound) => Ok(None),
+            Err(err) => Err(err),
+        }
+    }
+
+    /// Authors of posts published in `since..until` ranked by `order`,
+    /// `until` is now if not set
+    pub async fn top_users(
+        &self,
+        since: DateTime,
+        until: Option<DateTime>,
+        order: UserOrder,
+        limit: u32,
+    ) -> Result<Vec<UserStats>, ClientError> {
+        let request = proto::TopUsersRequest {
+            since: Some(since.into()),
+            until: until.map(Into::into),
+            order_by: proto::UserOrder::from(order).into(),
+            limit,
+        };
+        let response = self
+            .call(&self.posts, |mut client| {
+                let request = request.clone();
+                async move { client.get_top_users(Request::new(request)).await }
+            })
+            .await?;
+        Ok(response.users.into_iter().map(Into::into).collect())
+    }
+
+    /// Queues a crawl, it's not retried, so a crawl is never queued twice
+    pub async fn trigger_crawl(&self, target: CrawlTarget) -> Result<(), ClientError> {
+        self.admin
+            .clone()
+            .trigger_crawl(Request::new(target.into()))
+            .await?;
+        Ok(())
+    }
+
+    pub async fn pause_crawler(&self) -> Result<(), ClientError> {
+        self.call(&self.admin, |mut client| async move {
+            client.pause_crawler(Request::new(proto::Empty {})).await
+        })
+        .await?;
+        Ok(())
+    }
+
+    pub async fn resume_crawler(&self) -> Result<(), ClientError> {
+        self.call(&self.admin, |mut client| async move {
+            client.resume_crawler(Request::new(proto::Empty {})).await
+        })
+        .await?;
+        Ok(())
+    }
+
+    pub async fn crawler_status(&self) -> Result<CrawlerStatus, ClientError> {
+        let status = self
+            .call(&self.admin, |mut client| async move {
+                client
+                    .get_crawler_status(Request::new(proto::Empty {}))
+                    .await
+            })
+            .await?;
+        Ok(<Result<_, _>>::from(status)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn get_client(address: String) -> HnClient {
+        HnClient::new(ClientConfig {
+            address,
+            connect_timeout: Duration::from_millis(500),
+            retry: RetryPolicy {
+                max_retries: 2,
+                base_delay: Duration::from_millis(10),
+                max_delay: Duration::from_millis(10),
+            },
+            ..ClientConfig::default()
+        })
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_config() {
+        assert!(matches!(
+            HnClient::new(ClientConfig {
+                address: "not an address".to_owned(),
+                ..ClientConfig::default()
+            }),
+            Err(ClientError::Address { .. })
+        ));
+        assert!(matches!(
+            HnClient::new(ClientConfig {
+                api_key: Some("key\n".to_owned()),
+                ..ClientConfig::default()
+            }),
+            Err(ClientError::ApiKey)
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_retries() {
+        let client = get_client("http://127.0.0.1:1".to_owned());
+
+        let mut calls = 0;
+        let result = client
+            .call(&client.posts, |_| {
+                calls += 1;
+                async { Err::<Response<()>, _>(Status::unavailable("server is restarting")) }
+            })
+            .await;
+        assert_eq!(result.unwrap_err().code(), Some(Code::Unavailable));
+        assert_eq!(calls, 3);
+
+        let mut calls = 0;
+        let result = client
+            .call(&client.posts, |_| {
+                calls += 1;
+                async { Err::<Response<()>, _>(Status::permission_denied("read only key")) }
+            })
+            .await;
+        assert_eq!(result.unwrap_err().code(), Some(Code::PermissionDenied));
+        assert_eq!(calls, 1);
+
+        let mut calls = 0;
+        let result = client
+            .call(&client.posts, |_| {
+                calls += 1;
+                async { Err::<Response<()>, _>(Status::resource_exhausted("too many calls")) }
+            })
+            .await;
+        assert_eq!(result.unwrap_err().code(), Some(Code::ResourceExhausted));
+        assert_eq!(calls, 1);
+    }
+
+    #[tokio::test]
+    async fn test_unavailable_server() {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = format!("http://{}", listener.local_addr().unwrap());
+        drop(listener);
+
+        let err = match get_client(address).top_posts().await {
+            Ok(_) => panic!("there is no server"),
+            Err(err) => err,
+        };
+        assert!(err.is_retryable());
+        assert!(err
+            .to_string()
+            .starts_with("The service is currently unavailable"));
+    }
+}
@@ -0,0 +1,368 @@
+use std::{num::NonZeroUsize, sync::OnceLock};
+
+use publicsuffix::Psl;
+
+pub use url::Url;
+pub type DateTime = chrono::NaiveDateTime;
+pub type PostId = i64;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "sqlx", derive(sqlx::FromRow))]
+pub struct Post {
+    pub post_id: PostId,
+    pub title: String,
+    pub author: String,
+    pub url: String,
+    pub link: Option<String>,
+    pub publication_moment: DateTime,
+    pub last_snapshot_moment: DateTime,
+    /// Rank at `listing` in the snapshot, it's stored per snapshot only,
+    /// so stored posts don't have it
+    #[cfg_attr(feature = "sqlx", sqlx(default))]
+    pub rank: Option<i64>,
+    /// Crawled listing, which the post was seen at, stored per snapshot only
+    #[cfg_attr(feature = "sqlx", sqlx(default))]
+    pub listing: Option<Listing>,
+    /// Points of the latest snapshot, job posts have none
+    pub score: Option<i64>,
+}
+
+impl Post {
+    /// Registrable domain of the submitted link
+    pub fn domain(&self) -> Option<String> {
+        self.link.as_deref().and_then(link_domain)
+    }
+
+    /// Submitted link, or the HN item page for own posts like Ask HN
+    pub fn href(&self) -> &str {
+        match &self.link {
+            Some(link) if link_domain(link).is_some() => link,
+            _ => &self.url,
+        }
+    }
+}
+
+/// Snapshot of <https://publicsuffix.org/list/public_suffix_list.dat>,
+/// both ICANN and private suffixes like `github.io`
+const PUBLIC_SUFFIX_LIST: &str = include_str!("../data/public_suffix_list.dat");
+
+fn public_suffix_list() -> &'static publicsuffix::List {
+    static LIST: OnceLock<publicsuffix::List> = OnceLock::new();
+    LIST.get_or_init(|| {
+        PUBLIC_SUFFIX_LIST
+            .parse()
+            .expect("bundled public suffix list is valid")
+    })
+}
+
+/// Registrable domain of `host`: its public suffix and one more label,
+/// e.g. `example.co.uk` of `www.blog.example.co.uk`. Hosts without one,
+/// like `localhost`, are kept as they are
+pub fn registrable_domain(host: &str) -> String {
+    let host = host.trim_end_matches('.').to_ascii_lowercase();
+    match public_suffix_list().domain(host.as_bytes()) {
+        Some(domain) => String::from_utf8_lossy(domain.as_bytes()).into_owned(),
+        None => host,
+    }
+}
+
+/// Domain of a submitted link, `None` for HN own posts like Ask HN,
+/// which link to a relative `item?id=` url
+pub fn link_domain(link: &str) -> Option<String> {
+    match Url::parse(link).ok()?.host()? {
+        url::Host::Domain(domain) => Some(registrable_domain(domain)),
+        address => Some(address.to_string()),
+    }
+}
+
+/// Domain of user input: a link, a host or a domain itself
+pub fn parse_domain(input: &str) -> Option<String> {
+    let input = input.trim();
+    if input.contains("://") {
+        return link_domain(input);
+    }
+    let host = input.split('/').next().unwrap_or_default();
+    (!host.is_empty()).then(|| registrable_domain(host))
+}
+
+#[derive(strum::IntoStaticStr)]
+pub enum UserPostRequest {
+    All { user: String },
+    WasAtFirstPage { user: String },
+}
+impl UserPostRequest {
+    pub fn get_user(&self) -> &str {
+        match self {
+            UserPostRequest::All { user } => user,
+            UserPostRequest::WasAtFirstPage { user } => user,
+        }
+    }
+}
+
+pub struct DomainPostRequest {
+    /// Registrable domain, see [`registrable_domain`]
+    pub domain: String,
+    pub was_at_first_page: bool,
+}
+
+/// First page appearances of posts of one domain
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "sqlx", derive(sqlx::FromRow))]
+pub struct DomainStats {
+    pub domain: String,
+    /// Posts, which were at the first page
+    pub posts: i64,
+    /// First page snapshots with these posts
+    pub appearances: i64,
+    pub last_seen: DateTime,
+}
+
+/// Time one post spent at the first page
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "sqlx", derive(sqlx::FromRow))]
+pub struct PostStats {
+    pub post_id: PostId,
+    /// First page snapshot, where the post was first seen
+    pub first_seen: Option<DateTime>,
+    pub last_seen: Option<DateTime>,
+    /// Gaps between first page snapshots over an hour count as an hour
+    pub front_page_minutes: f64,
+    /// First page snapshots with the post
+    pub appearances: i64,
+    /// Best rank at the front page listing, `None` if never ranked
+    pub best_rank: Option<i64>,
+}
+
+/// Sample of a post in one crawl snapshot
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "sqlx", derive(sqlx::FromRow))]
+pub struct PostSnapshot {
+    pub snapshot_moment: DateTime,
+    /// Listing of `rank`, `None` for samples without rank
+    pub listing: Option<Listing>,
+    pub rank: Option<i64>,
+    pub score: Option<i64>,
+    pub was_at_first_page: bool,
+}
+
+/// Post rising below the first page, with its rank and score of the
+/// latest snapshot
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "sqlx", derive(sqlx::FromRow))]
+pub struct TrendingPost {
+    #[cfg_attr(feature = "sqlx", sqlx(flatten))]
+    pub post: Post,
+    /// Points gained per hour, `None` without points
+    pub points_per_hour: Option<f64>,
+    /// Ranks climbed per hour at the front page listing, `None` if unranked
+    pub rank_velocity: Option<f64>,
+}
+
+/// Post, which entered, left or moved within the front page listing
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "sqlx", derive(sqlx::FromRow))]
+pub struct SnapshotChange {
+    #[cfg_attr(feature = "sqlx", sqlx(flatten))]
+    pub post: Post,
+    /// `None` if the post entered
+    pub old_rank: Option<i64>,
+    /// `None` if the post left
+    pub new_rank: Option<i64>,
+}
+
+/// Changes of the front page listing between two crawl snapshots
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SnapshotDiff {
+    pub from: DateTime,
+    pub to: DateTime,
+    /// Entered and moved posts by new rank, then left ones by old rank
+    pub changes: Vec<SnapshotChange>,
+}
+
+/// Post of a digest with its stats in the digest window
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "sqlx", derive(sqlx::FromRow))]
+pub struct DigestPost {
+    #[cfg_attr(feature = "sqlx", sqlx(flatten))]
+    pub post: Post,
+    /// Best rank at the listing in the window
+    pub best_rank: i64,
+    /// Minutes at the first page in the window
+    pub front_page_minutes: f64,
+}
+
+/// The best posts of one listing
+#[derive(Debug, Clone, PartialEq)]
+pub struct DigestSection {
+    pub listing: Listing,
+    pub posts: Vec<DigestPost>,
+}
+
+/// The best posts of each crawled listing in `since..until`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Digest {
+    pub since: DateTime,
+    pub until: DateTime,
+    pub sections: Vec<DigestSection>,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, strum::Display, strum::EnumString)]
+pub enum DigestFormat {
+    #[default]
+    #[strum(serialize = "md")]
+    Markdown,
+    #[strum(serialize = "html")]
+    Html,
+}
+
+/// Aggregates of posts of one author
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "sqlx", derive(sqlx::FromRow))]
+pub struct UserStats {
+    pub user: String,
+    /// Crawled posts
+    pub posts: i64,
+    /// Posts, which were at the first page
+    pub first_page_posts: i64,
+    /// Best rank at the front page listing, `None` if never ranked
+    pub best_rank: Option<i64>,
+    /// Minutes posts spent at the first page
+    pub front_page_minutes: f64,
+    /// Average of the latest points of posts, `None` without points
+    pub average_score: Option<f64>,
+}
+
+/// Order of the users leaderboard, the best ones go first
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, strum::Display, strum::EnumString)]
+#[strum(serialize_all = "kebab-case")]
+pub enum UserOrder {
+    Posts,
+    FirstPagePosts,
+    BestRank,
+    #[default]
+    FrontPageMinutes,
+    AverageScore,
+}
+
+/// HN listing, which is crawled page by page
+#[derive(
+    Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, strum::Display, strum::EnumString,
+)]
+#[cfg_attr(feature = "sqlx", derive(sqlx::Type))]
+#[strum(serialize_all = "lowercase")]
+#[cfg_attr(feature = "sqlx", sqlx(rename_all = "lowercase"))]
+pub enum Listing {
+    /// Front page, only its first page counts as "first page" of HN
+    #[default]
+    Top,
+    New,
+    Ask,
+    Show,
+    Jobs,
+}
+
+impl Listing {
+    pub fn path(&self) -> &'static str {
+        match self {
+            Listing::Top => "news",
+            Listing::New => "newest",
+            Listing::Ask => "ask",
+            Listing::Show => "show",
+            Listing::Jobs => "jobs",
+        }
+    }
+}
+
+/// Pages of one listing visited by one crawl
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CrawlTarget {
+    pub listing: Listing,
+    pub first_page: NonZeroUsize,
+    /// Inclusive
+    pub last_page: NonZeroUsize,
+}
+
+impl CrawlTarget {
+    pub const DEFAULT_LAST_PAGE: usize = 10;
+
+    /// `None` if the page range is empty or starts from zero page
+    pub fn new(listing: Listing, first_page: usize, last_page: usize) -> Option<Self> {
+        let first_page = NonZeroUsize::new(first_page)?;
+        let last_page = NonZeroUsize::new(last_page)?;
+        (first_page <= last_page).then_some(Self {
+            listing,
+            first_page,
+            last_page,
+        })
+    }
+
+    /// Posts of this page are at the first page of HN
+    pub fn is_first_page(&self, page: usize) -> bool {
+        self.listing == Listing::Top && page == 1
+    }
+}
+
+impl Default for CrawlTarget {
+    fn default() -> Self {
+        Self::new(Listing::Top, 1, Self::DEFAULT_LAST_PAGE).unwrap()
+    }
+}
+
+/// In-flight crawl
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CrawlProgress {
+    pub target: CrawlTarget,
+    /// Page of the latest crawled post
+    pub page: Option<usize>,
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct CrawlerStatus {
+    pub paused: bool,
+    pub crawl: Option<CrawlProgress>,
+    pub last_error: Option<String>,
+    pub last_snapshot: Option<DateTime>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_link_domain() {
+        for (link, domain) in [
+            ("https://example.com/post", Some("example.com")),
+            ("https://www.Example.com./", Some("example.com")),
+            ("http://blog.example.co.uk/a?b=c", Some("example.co.uk")),
+            (
+                "https://someone.github.io/project",
+                Some("someone.github.io"),
+            ),
+            ("https://www.example.com.ar/", Some("example.com.ar")),
+            ("https://foo.bar.pvt.k12.ma.us/", Some("bar.pvt.k12.ma.us")),
+            ("https://localhost:8080/", Some("localhost")),
+            ("http://127.0.0.1/", Some("127.0.0.1")),
+            ("item?id=34379766", None),
+            ("", None),
+        ] {
+            assert_eq!(link_domain(link).as_deref(), domain, "{link}");
+        }
+    }
+
+    #[test]
+    fn test_parse_domain() {
+        assert_eq!(
+            parse_domain("www.example.com").as_deref(),
+            Some("example.com")
+        );
+        assert_eq!(
+            parse_domain(" https://news.example.com/item ").as_deref(),
+            Some("example.com")
+        );
+        assert_eq!(
+            parse_domain("example.com/path").as_deref(),
+            Some("example.com")
+        );
+        assert_eq!(parse_domain("  "), None);
+    }
+}
@@ -0,0 +1,434 @@
+use crate::hackernews_core::{self, DateTime};
+
+tonic::include_proto!("hackernews_proxy");
+
+/// Encoded proto descriptors, used by grpc server reflection
+pub const FILE_DESCRIPTOR_SET: &[u8] =
+    tonic::include_file_descriptor_set!("hackernews_proxy_descriptor");
+
+impl TryFrom<Timestamp> for DateTime {
+    type Error = Error;
+
+    fn try_from(value: Timestamp) -> Result<Self, Self::Error> {
+        chrono::DateTime::from_timestamp(value.timestmap, 0)
+            .map(|moment| moment.naive_utc())
+            .ok_or(Error::WrongTimestamp(value.timestmap))
+    }
+}
+impl From<DateTime> for Timestamp {
+    fn from(value: DateTime) -> Self {
+        Timestamp {
+            timestmap: value.and_utc().timestamp(),
+        }
+    }
+}
+impl From<StringWrapper> for String {
+    fn from(value: StringWrapper) -> Self {
+        value.str
+    }
+}
+impl From<String> for StringWrapper {
+    fn from(value: String) -> Self {
+        StringWrapper { str: value }
+    }
+}
+
+#[derive(Debug)]
+pub enum Error {
+    LostLink,
+    WrongUrl(url::ParseError),
+    LostSnapshotTime,
+    LostPublicationTime,
+    WrongListing(i32),
+    WrongPageRange { first: u32, last: u32 },
+    LostCrawlTarget,
+    LostLastSeen,
+    LostPost,
+    WrongTimestamp(i64),
+}
+
+impl From<hackernews_core::Post> for Post {
+    fn from(value: hackernews_core::Post) -> Post {
+        let domain = value.domain();
+        Post {
+            post_id: value.post_id,
+            title: value.title,
+            author: value.author,
+            link: value.link.map(Into::into),
+            url: value.url,
+            publication_moment: Some(value.publication_moment.into()),
+            last_snapshot_moment: Some(value.last_snapshot_moment.into()),
+            domain: domain.map(Into::into),
+            score: value.score.unwrap_or_default(),
+            rank: match value.listing {
+                Some(hackernews_core::Listing::Top) => value.rank.unwrap_or_default(),
+                _ => 0,
+            },
+        }
+    }
+}
+impl From<Post> for Result<hackernews_core::Post, Error> {
+    fn from(value: Post) -> Result<hackernews_core::Post, Error> {
+        Ok(hackernews_core::Post {
+            post_id: value.post_id,
+            title: value.title,
+            author: value.author,
+            url: value.url,
+            link: value.link.map(Into::into),
+            publication_moment: value
+                .publication_moment
+                .ok_or(Error::LostPublicationTime)?
+                .try_into()?,
+            last_snapshot_moment: value
+                .last_snapshot_moment
+                .ok_or(Error::LostSnapshotTime)?
+                .try_into()?,
+            rank: (value.rank != 0).then_some(value.rank),
+            listing: (value.rank != 0).then_some(hackernews_core::Listing::Top),
+            score: (value.score != 0).then_some(value.score),
+        })
+    }
+}
+impl From<hackernews_core::UserPostRequest> for UserPostRequest {
+    fn from(value: hackernews_core::UserPostRequest) -> Self {
+        match value {
+            hackernews_core::UserPostRequest::All { user } => Self {
+                user,
+                filter: Some(user_post_request::Filter::All(Empty {})),
+            },
+            hackernews_core::UserPostRequest::WasAtFirstPage { user } => Self {
+                user,
+                filter: Some(user_post_request::Filter::WasAtFirstPage(Empty {})),
+            },
+        }
+    }
+}
+
+impl From<UserPostRequest> for Option<hackernews_core::UserPostRequest> {
+    fn from(value: UserPostRequest) -> Self {
+        match value {
+            UserPostRequest {
+                user: _,
+                filter: None,
+            } => None,
+            UserPostRequest {
+                user,
+                filter: Some(user_post_request::Filter::All(_)),
+            } => Some(hackernews_core::UserPostRequest::All { user }),
+            UserPostRequest {
+                user,
+                filter: Some(user_post_request::Filter::WasAtFirstPage(_)),
+            } => Some(hackernews_core::UserPostRequest::WasAtFirstPage { user }),
+        }
+    }
+}
+
+impl From<hackernews_core::DomainPostRequest> for DomainPostRequest {
+    fn from(value: hackernews_core::DomainPostRequest) -> Self {
+        Self {
+            domain: value.domain,
+            was_at_first_page: value.was_at_first_page,
+        }
+    }
+}
+
+/// `None` if there is no domain in the request
+impl From<DomainPostRequest> for Option<hackernews_core::DomainPostRequest> {
+    fn from(value: DomainPostRequest) -> Self {
+        Some(hackernews_core::DomainPostRequest {
+            domain: hackernews_core::parse_domain(&value.domain)?,
+            was_at_first_page: value.was_at_first_page,
+        })
+    }
+}
+
+impl From<hackernews_core::DomainStats> for DomainStats {
+    fn from(value: hackernews_core::DomainStats) -> Self {
+        Self {
+            domain: value.domain,
+            posts: value.posts as u64,
+            appearances: value.appearances as u64,
+            last_seen: Some(value.last_seen.into()),
+        }
+    }
+}
+impl From<DomainStats> for Result<hackernews_core::DomainStats, Error> {
+    fn from(value: DomainStats) -> Result<hackernews_core::DomainStats, Error> {
+        Ok(hackernews_core::DomainStats {
+            domain: value.domain,
+            posts: value.posts as i64,
+            appearances: value.appearances as i64,
+            last_seen: value.last_seen.ok_or(Error::LostLastSeen)?.try_into()?,
+        })
+    }
+}
+
+impl From<hackernews_core::TrendingPost> for TrendingPost {
+    fn from(value: hackernews_core::TrendingPost) -> Self {
+        Self {
+            rank: value.post.rank.unwrap_or_default() as u32,
+            post: Some(value.post.into()),
+            points_per_hour: value.points_per_hour.unwrap_or_default(),
+            rank_velocity: value.rank_velocity.unwrap_or_default(),
+        }
+    }
+}
+impl From<TrendingPost> for Result<hackernews_core::TrendingPost, Error> {
+    fn from(value: TrendingPost) -> Result<hackernews_core::TrendingPost, Error> {
+        let post: Result<hackernews_core::Post, Error> = value.post.ok_or(Error::LostPost)?.into();
+        Ok(hackernews_core::TrendingPost {
+            post: hackernews_core::Post {
+                rank: (value.rank != 0).then_some(value.rank.into()),
+                ..post?
+            },
+            points_per_hour: (value.points_per_hour != 0.0).then_some(value.points_per_hour),
+            rank_velocity: (value.rank_velocity != 0.0).then_some(value.rank_velocity),
+        })
+    }
+}
+
+impl From<hackernews_core::SnapshotChange> for SnapshotChange {
+    fn from(value: hackernews_core::SnapshotChange) -> Self {
+        Self {
+            post: Some(value.post.into()),
+            old_rank: value.old_rank.unwrap_or_default() as u32,
+            new_rank: value.new_rank.unwrap_or_default() as u32,
+        }
+    }
+}
+impl From<SnapshotChange> for Result<hackernews_core::SnapshotChange, Error> {
+    fn from(value: SnapshotChange) -> Result<hackernews_core::SnapshotChange, Error> {
+        Ok(hackernews_core::SnapshotChange {
+            post: Result::from(value.post.ok_or(Error::LostPost)?)?,
+            old_rank: (value.old_rank != 0).then_some(value.old_rank.into()),
+            new_rank: (value.new_rank != 0).then_some(value.new_rank.into()),
+        })
+    }
+}
+
+impl From<hackernews_core::SnapshotDiff> for SnapshotDiff {
+    fn from(value: hackernews_core::SnapshotDiff) -> Self {
+        Self {
+            from: Some(value.from.into()),
+            to: Some(value.to.into()),
+            changes: value.changes.into_iter().map(Into::into).collect(),
+        }
+    }
+}
+impl From<SnapshotDiff> for Result<hackernews_core::SnapshotDiff, Error> {
+    fn from(value: SnapshotDiff) -> Result<hackernews_core::SnapshotDiff, Error> {
+        Ok(hackernews_core::SnapshotDiff {
+            from: value.from.ok_or(Error::LostSnapshotTime)?.try_into()?,
+            to: value.to.ok_or(Error::LostSnapshotTime)?.try_into()?,
+            changes: value
+                .changes
+                .into_iter()
+                .map(Result::from)
+                .collect::<Result<_, _>>()?,
+        })
+    }
+}
+
+impl From<hackernews_core::PostSnapshot> for PostSnapshot {
+    fn from(value: hackernews_core::PostSnapshot) -> Self {
+        Self {
+            snapshot_moment: Some(value.snapshot_moment.into()),
+            listing: value.listing.map(Listing::from).unwrap_or_default().into(),
+            rank: value.rank.unwrap_or_default() as u32,
+            score: value.score.unwrap_or_default(),
+            was_at_first_page: value.was_at_first_page,
+        }
+    }
+}
+impl From<PostSnapshot> for Result<hackernews_core::PostSnapshot, Error> {
+    fn from(value: PostSnapshot) -> Result<hackernews_core::PostSnapshot, Error> {
+        let listing = Listing::from_i32(value.listing).ok_or(Error::WrongListing(value.listing))?;
+        Ok(hackernews_core::PostSnapshot {
+            snapshot_moment: value
+                .snapshot_moment
+                .ok_or(Error::LostSnapshotTime)?
+                .try_into()?,
+            listing: (value.rank != 0).then_some(listing.into()),
+            rank: (value.rank != 0).then_some(value.rank.into()),
+            score: (value.score != 0).then_some(value.score),
+            was_at_first_page: value.was_at_first_page,
+        })
+    }
+}
+
+impl From<hackernews_core::PostStats> for PostStats {
+    fn from(value: hackernews_core::PostStats) -> Self {
+        Self {
+            post_id: value.post_id,
+            first_seen: value.first_seen.map(Into::into),
+            last_seen: value.last_seen.map(Into::into),
+            front_page_minutes: value.front_page_minutes,
+            appearances: value.appearances as u64,
+            best_rank: value.best_rank.unwrap_or_default() as u32,
+        }
+    }
+}
+impl From<PostStats> for Result<hackernews_core::PostStats, Error> {
+    fn from(value: PostStats) -> Result<hackernews_core::PostStats, Error> {
+        Ok(hackernews_core::PostStats {
+            post_id: value.post_id,
+            first_seen: value.first_seen.map(TryInto::try_into).transpose()?,
+            last_seen: value.last_seen.map(TryInto::try_into).transpose()?,
+            front_page_minutes: value.front_page_minutes,
+            appearances: value.appearances as i64,
+            best_rank: (value.best_rank != 0).then_some(value.best_rank.into()),
+        })
+    }
+}
+
+impl From<hackernews_core::UserStats> for UserStats {
+    fn from(value: hackernews_core::UserStats) -> Self {
+        Self {
+            user: value.user,
+            posts: value.posts as u64,
+            first_page_posts: value.first_page_posts as u64,
+            best_rank: value.best_rank.unwrap_or_default() as u32,
+            front_page_minutes: value.front_page_minutes,
+            average_score: value.average_score.unwrap_or_default(),
+        }
+    }
+}
+impl From<UserStats> for hackernews_core::UserStats {
+    fn from(value: UserStats) -> Self {
+        Self {
+            user: value.user,
+            posts: value.posts as i64,
+            first_page_posts: value.first_page_posts as i64,
+            best_rank: (value.best_rank != 0).then_some(value.best_rank.into()),
+            front_page_minutes: value.front_page_minutes,
+            average_score: (value.average_score != 0.0).then_some(value.average_score),
+        }
+    }
+}
+
+impl From<hackernews_core::UserOrder> for UserOrder {
+    fn from(value: hackernews_core::UserOrder) -> Self {
+        match value {
+            hackernews_core::UserOrder::Posts => UserOrder::Posts,
+            hackernews_core::UserOrder::FirstPagePosts => UserOrder::FirstPagePosts,
+            hackernews_core::UserOrder::BestRank => UserOrder::BestRank,
+            hackernews_core::UserOrder::FrontPageMinutes => UserOrder::FrontPageMinutes,
+            hackernews_core::UserOrder::AverageScore => UserOrder::AverageScore,
+        }
+    }
+}
+impl From<UserOrder> for hackernews_core::UserOrder {
+    fn from(value: UserOrder) -> Self {
+        match value {
+            UserOrder::Posts => hackernews_core::UserOrder::Posts,
+            UserOrder::FirstPagePosts => hackernews_core::UserOrder::FirstPagePosts,
+            UserOrder::BestRank => hackernews_core::UserOrder::BestRank,
+            UserOrder::FrontPageMinutes => hackernews_core::UserOrder::FrontPageMinutes,
+            UserOrder::AverageScore => hackernews_core::UserOrder::AverageScore,
+        }
+    }
+}
+
+impl From<hackernews_core::DigestFormat> for DigestFormat {
+    fn from(value: hackernews_core::DigestFormat) -> Self {
+        match value {
+            hackernews_core::DigestFormat::Markdown => DigestFormat::Markdown,
+            hackernews_core::DigestFormat::Html => DigestFormat::Html,
+        }
+    }
+}
+impl From<DigestFormat> for hackernews_core::DigestFormat {
+    fn from(value: DigestFormat) -> Self {
+        match value {
+            DigestFormat::Markdown => hackernews_core::DigestFormat::Markdown,
+            DigestFormat::Html => hackernews_core::DigestFormat::Html,
+        }
+    }
+}
+
+impl From<hackernews_core::Listing> for Listing {
+    fn from(value: hackernews_core::Listing) -> Self {
+        match value {
+            hackernews_core::Listing::Top => Listing::Top,
+            hackernews_core::Listing::New => Listing::New,
+            hackernews_core::Listing::Ask => Listing::Ask,
+            hackernews_core::Listing::Show => Listing::Show,
+            hackernews_core::Listing::Jobs => Listing::Jobs,
+        }
+    }
+}
+impl From<Listing> for hackernews_core::Listing {
+    fn from(value: Listing) -> Self {
+        match value {
+            Listing::Top => hackernews_core::Listing::Top,
+            Listing::New => hackernews_core::Listing::New,
+            Listing::Ask => hackernews_core::Listing::Ask,
+            Listing::Show => hackernews_core::Listing::Show,
+            Listing::Jobs => hackernews_core::Listing::Jobs,
+        }
+    }
+}
+
+impl From<hackernews_core::CrawlTarget> for CrawlTarget {
+    fn from(value: hackernews_core::CrawlTarget) -> Self {
+        CrawlTarget {
+            listing: Listing::from(value.listing).into(),
+            pages: Some(PageRange {
+                first: value.first_page.get() as u32,
+                last: value.last_page.get() as u32,
+            }),
+        }
+    }
+}
+impl From<CrawlTarget> for Result<hackernews_core::CrawlTarget, Error> {
+    fn from(value: CrawlTarget) -> Result<hackernews_core::CrawlTarget, Error> {
+        let listing = Listing::from_i32(value.listing)
+            .ok_or(Error::WrongListing(value.listing))?
+            .into();
+        let PageRange { first, last } = value.pages.unwrap_or(PageRange {
+            first: 1,
+            last: hackernews_core::CrawlTarget::DEFAULT_LAST_PAGE as u32,
+        });
+
+        hackernews_core::CrawlTarget::new(listing, first as usize, last as usize)
+            .ok_or(Error::WrongPageRange { first, last })
+    }
+}
+
+impl From<hackernews_core::CrawlerStatus> for CrawlerStatus {
+    fn from(value: hackernews_core::CrawlerStatus) -> Self {
+        let state = match (value.paused, &value.crawl) {
+            (true, _) => crawler_status::State::Paused,
+            (false, Some(_)) => crawler_status::State::Crawling,
+            (false, None) => crawler_status::State::Idle,
+        };
+
+        CrawlerStatus {
+            state: state.into(),
+            crawl: value.crawl.map(|crawl| CrawlProgress {
+                target: Some(crawl.target.into()),
+                page: crawl.page.unwrap_or_default() as u32,
+            }),
+            last_error: value.last_error.map(Into::into),
+            last_snapshot: value.last_snapshot.map(Into::into),
+        }
+    }
+}
+impl From<CrawlerStatus> for Result<hackernews_core::CrawlerStatus, Error> {
+    fn from(value: CrawlerStatus) -> Result<hackernews_core::CrawlerStatus, Error> {
+        let crawl = match value.crawl {
+            Some(crawl) => Some(hackernews_core::CrawlProgress {
+                target: Result::from(crawl.target.ok_or(Error::LostCrawlTarget)?)?,
+                page: (crawl.page != 0).then_some(crawl.page as usize),
+            }),
+            None => None,
+        };
+
+        Ok(hackernews_core::CrawlerStatus {
+            paused: value.state == crawler_status::State::Paused as i32,
+            crawl,
+            last_error: value.last_error.map(Into::into),
+            last_snapshot: value.last_snapshot.map(TryInto::try_into).transpose()?,
+        })
+    }
+}
@@ -0,0 +1,14 @@
+/// Core struct of crate
+pub mod hackernews_core;
+pub use hackernews_core as core;
+
+/// Module with protobuf and protobuf casts
+pub mod hackernews_proxy_proto;
+pub use hackernews_proxy_proto as proto;
+
+/// Module with async grpc client of the api
+pub mod hackernews_client;
+pub use hackernews_client as client;
+
+/// Module with backoff of retried calls, shared by the client and the crawler
+pub mod retry;
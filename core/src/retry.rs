@@ -0,0 +1,76 @@
+use std::time::Duration;
+
+use rand::Rng;
+
+/// Retries of failed calls with exponential backoff
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    pub max_retries: u32,
+    pub base_delay: Duration,
+    pub max_delay: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_retries: 3,
+            base_delay: Duration::from_millis(500),
+            max_delay: Duration::from_secs(5),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Exponential backoff with "equal jitter": half of the delay is fixed,
+    /// the other half is random, so parallel retries don't come in a burst
+    pub fn backoff(&self, retry: u32) -> Duration {
+        let delay = self
+            .base_delay
+            .saturating_mul(2u32.saturating_pow(retry))
+            .min(self.max_delay);
+        let half = delay / 2;
+
+        half + rand::thread_rng().gen_range(Duration::ZERO..=half)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_backoff() {
+        let policy = RetryPolicy {
+            max_retries: 10,
+            base_delay: Duration::from_millis(100),
+            max_delay: Duration::from_secs(1),
+        };
+
+        for _ in 0..100 {
+            let delay = policy.backoff(0);
+            assert!((Duration::from_millis(50)..=Duration::from_millis(100)).contains(&delay));
+
+            let delay = policy.backoff(2);
+            assert!((Duration::from_millis(200)..=Duration::from_millis(400)).contains(&delay));
+
+            let delay = policy.backoff(9);
+            assert!((Duration::from_millis(500)..=Duration::from_secs(1)).contains(&delay));
+        }
+    }
+
+    #[test]
+    fn test_max_delay() {
+        let policy = RetryPolicy {
+            max_retries: 5,
+            base_delay: Duration::from_millis(100),
+            max_delay: Duration::from_millis(300),
+        };
+        for (retry, max) in [(0, 100), (1, 200), (2, 300), (10, 300), (u32::MAX, 300)] {
+            let delay = policy.backoff(retry);
+            assert!(
+                delay >= Duration::from_millis(max / 2) && delay <= Duration::from_millis(max),
+                "{retry}: {delay:?}"
+            );
+        }
+    }
+}
@@ -0,0 +1,25 @@
+ALTER TABLE "posts" ADD COLUMN "domain" VARCHAR;
+
+CREATE INDEX "posts_domain" ON "posts" ("domain");
+CREATE INDEX "first_page_posts_snapshot_moment" ON "first_page_posts" ("snapshot_moment");
+
+-- View is recreated to pick up the new column, its trigger is dropped with it
+DROP VIEW "posts_view";
+
+CREATE VIEW "posts_view" AS
+SELECT "posts".*, "fpp"."snapshot_moment" IS NOT NULL AS "was_at_first_page"
+FROM "posts"
+         LEFT JOIN "first_page_posts" AS "fpp" ON "posts"."post_id" = "fpp"."post_id";
+
+CREATE TRIGGER "posts_view"
+    INSTEAD OF INSERT
+    ON "posts_view"
+BEGIN
+    INSERT INTO "posts" ("post_id", "title", "author", "url", "link", "domain", "publication_moment", "last_snapshot_moment")
+    VALUES ("new"."post_id", "new"."title", "new"."author", "new"."url", "new"."link", "new"."domain", "new"."publication_moment", "new"."last_snapshot_moment")
+    ON CONFLICT DO UPDATE SET "last_snapshot_moment" = "new"."last_snapshot_moment", "domain" = "new"."domain";
+
+    INSERT INTO "first_page_posts" ("post_id", "snapshot_moment")
+    SELECT "new"."post_id", "new"."last_snapshot_moment"
+    WHERE "new"."was_at_first_page" IS TRUE;
+END;
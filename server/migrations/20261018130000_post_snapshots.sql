@@ -0,0 +1,59 @@
+ALTER TABLE "posts" ADD COLUMN "score" INT;
+
+-- Rank at the front page listing and score of a post in each snapshot
+CREATE TABLE "post_snapshots"
+(
+    "post_id"         INT       NOT NULL,
+    "snapshot_moment" TIMESTAMP NOT NULL,
+    "rank"            INT,
+    "score"           INT,
+    UNIQUE ("post_id", "snapshot_moment"),
+    FOREIGN KEY ("post_id") REFERENCES "posts" ("post_id")
+);
+
+CREATE INDEX "post_snapshots_snapshot_moment" ON "post_snapshots" ("snapshot_moment");
+CREATE INDEX "posts_author" ON "posts" ("author");
+CREATE INDEX "posts_publication_moment" ON "posts" ("publication_moment");
+
+-- Minutes a post spent at the first page after each first page snapshot:
+-- till the next first page snapshot, zero for the latest one. Gaps over an
+-- hour, e.g. outages of the crawler, count as an hour
+CREATE VIEW "first_page_dwell" AS
+WITH "snapshots" AS (
+    SELECT "snapshot_moment",
+           LEAD("snapshot_moment") OVER (ORDER BY "snapshot_moment") AS "next_moment"
+    FROM (SELECT DISTINCT "snapshot_moment" FROM "first_page_posts")
+)
+SELECT "fpp"."post_id",
+       "fpp"."snapshot_moment",
+       COALESCE(MIN((julianday("snapshots"."next_moment") - julianday("fpp"."snapshot_moment")) * 24 * 60, 60), 0) AS "minutes"
+FROM "first_page_posts" AS "fpp"
+         INNER JOIN "snapshots" ON "snapshots"."snapshot_moment" = "fpp"."snapshot_moment";
+
+DROP VIEW "posts_view";
+
+-- `rank` is only written through the view, it's stored in "post_snapshots"
+CREATE VIEW "posts_view" AS
+SELECT "posts".*, NULL AS "rank", "fpp"."snapshot_moment" IS NOT NULL AS "was_at_first_page"
+FROM "posts"
+         LEFT JOIN "first_page_posts" AS "fpp" ON "posts"."post_id" = "fpp"."post_id";
+
+CREATE TRIGGER "posts_view"
+    INSTEAD OF INSERT
+    ON "posts_view"
+BEGIN
+    INSERT INTO "posts" ("post_id", "title", "author", "url", "link", "domain", "score", "publication_moment", "last_snapshot_moment")
+    VALUES ("new"."post_id", "new"."title", "new"."author", "new"."url", "new"."link", "new"."domain", "new"."score", "new"."publication_moment", "new"."last_snapshot_moment")
+    ON CONFLICT DO UPDATE SET "last_snapshot_moment" = "new"."last_snapshot_moment",
+                              "domain" = "new"."domain",
+                              "score" = COALESCE("new"."score", "score");
+
+    INSERT INTO "first_page_posts" ("post_id", "snapshot_moment")
+    SELECT "new"."post_id", "new"."last_snapshot_moment"
+    WHERE "new"."was_at_first_page" IS TRUE;
+
+    INSERT INTO "post_snapshots" ("post_id", "snapshot_moment", "rank", "score")
+    SELECT "new"."post_id", "new"."last_snapshot_moment", "new"."rank", "new"."score"
+    WHERE "new"."rank" IS NOT NULL OR "new"."score" IS NOT NULL
+    ON CONFLICT DO NOTHING;
+END;
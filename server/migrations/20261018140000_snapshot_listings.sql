@@ -0,0 +1,34 @@
+-- Listing of the crawl, which took the sample. Only samples of the front page
+-- listing had ranks before, listing of other samples is unknown
+ALTER TABLE "post_snapshots" ADD COLUMN "listing" TEXT;
+UPDATE "post_snapshots" SET "listing" = 'top' WHERE "rank" IS NOT NULL;
+
+CREATE INDEX "post_snapshots_listing_snapshot_moment" ON "post_snapshots" ("listing", "snapshot_moment");
+
+DROP VIEW "posts_view";
+
+-- `rank` and `listing` are only written through the view, they're stored in "post_snapshots"
+CREATE VIEW "posts_view" AS
+SELECT "posts".*, NULL AS "rank", NULL AS "listing", "fpp"."snapshot_moment" IS NOT NULL AS "was_at_first_page"
+FROM "posts"
+         LEFT JOIN "first_page_posts" AS "fpp" ON "posts"."post_id" = "fpp"."post_id";
+
+CREATE TRIGGER "posts_view"
+    INSTEAD OF INSERT
+    ON "posts_view"
+BEGIN
+    INSERT INTO "posts" ("post_id", "title", "author", "url", "link", "domain", "score", "publication_moment", "last_snapshot_moment")
+    VALUES ("new"."post_id", "new"."title", "new"."author", "new"."url", "new"."link", "new"."domain", "new"."score", "new"."publication_moment", "new"."last_snapshot_moment")
+    ON CONFLICT DO UPDATE SET "last_snapshot_moment" = "new"."last_snapshot_moment",
+                              "domain" = "new"."domain",
+                              "score" = COALESCE("new"."score", "score");
+
+    INSERT INTO "first_page_posts" ("post_id", "snapshot_moment")
+    SELECT "new"."post_id", "new"."last_snapshot_moment"
+    WHERE "new"."was_at_first_page" IS TRUE;
+
+    INSERT INTO "post_snapshots" ("post_id", "snapshot_moment", "listing", "rank", "score")
+    SELECT "new"."post_id", "new"."last_snapshot_moment", "new"."listing", "new"."rank", "new"."score"
+    WHERE "new"."rank" IS NOT NULL OR "new"."score" IS NOT NULL
+    ON CONFLICT DO NOTHING;
+END;
@@ -0,0 +1,3 @@
+-- Domains were derived from a short list of multi-label suffixes,
+-- they are filled in again with the public suffix list on server start
+UPDATE "posts" SET "domain" = NULL;
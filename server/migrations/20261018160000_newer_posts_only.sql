@@ -0,0 +1,24 @@
+-- Imported dumps may be older than the stored posts: samples of older
+-- snapshots are still stored, but a post keeps its latest moment and score
+DROP TRIGGER "posts_view";
+
+CREATE TRIGGER "posts_view"
+    INSTEAD OF INSERT
+    ON "posts_view"
+BEGIN
+    INSERT INTO "posts" ("post_id", "title", "author", "url", "link", "domain", "score", "publication_moment", "last_snapshot_moment")
+    VALUES ("new"."post_id", "new"."title", "new"."author", "new"."url", "new"."link", "new"."domain", "new"."score", "new"."publication_moment", "new"."last_snapshot_moment")
+    ON CONFLICT DO UPDATE SET "last_snapshot_moment" = "new"."last_snapshot_moment",
+                              "domain" = "new"."domain",
+                              "score" = COALESCE("new"."score", "score")
+    WHERE "new"."last_snapshot_moment" >= "last_snapshot_moment";
+
+    INSERT INTO "first_page_posts" ("post_id", "snapshot_moment")
+    SELECT "new"."post_id", "new"."last_snapshot_moment"
+    WHERE "new"."was_at_first_page" IS TRUE;
+
+    INSERT INTO "post_snapshots" ("post_id", "snapshot_moment", "listing", "rank", "score")
+    SELECT "new"."post_id", "new"."last_snapshot_moment", "new"."listing", "new"."rank", "new"."score"
+    WHERE "new"."rank" IS NOT NULL OR "new"."score" IS NOT NULL
+    ON CONFLICT DO NOTHING;
+END;
@@ -0,0 +1,171 @@
+use std::sync::Arc;
+
+use tokio::sync::watch;
+use tonic::{Request, Response, Status};
+
+use crate::crawler_control::{CrawlerControl, TriggerError};
+use hackernews_crawler::{
+    core::{self, DateTime},
+    proto,
+};
+
+pub struct Server {
+    pub crawler: Arc<CrawlerControl>,
+    pub latest_snapshot: watch::Receiver<Option<DateTime>>,
+}
+
+#[tonic::async_trait]
+impl proto::admin_service_server::AdminService for Server {
+    async fn trigger_crawl(
+        &self,
+        request: Request<proto::CrawlTarget>,
+    ) -> Result<Response<proto::Empty>, Status> {
+        let target = Result::<core::CrawlTarget, _>::from(request.into_inner())
+            .map_err(|err| Status::invalid_argument(format!("wrong crawl target: {err:?}")))?;
+
+        match self.crawler.trigger(target) {
+            Ok(()) => Ok(Response::new(proto::Empty {})),
+            Err(err @ TriggerError::Paused) => Err(Status::failed_precondition(err.to_string())),
+            Err(err @ TriggerError::QueueFull(_)) => {
+                Err(Status::resource_exhausted(err.to_string()))
+            }
+        }
+    }
+
+    async fn pause_crawler(
+        &self,
+        _request: Request<proto::Empty>,
+    ) -> Result<Response<proto::Empty>, Status> {
+        self.crawler.pause();
+        Ok(Response::new(proto::Empty {}))
+    }
+
+    async fn resume_crawler(
+        &self,
+        _request: Request<proto::Empty>,
+    ) -> Result<Response<proto::Empty>, Status> {
+        self.crawler.resume();
+        Ok(Response::new(proto::Empty {}))
+    }
+
+    async fn get_crawler_status(
+        &self,
+        _request: Request<proto::Empty>,
+    ) -> Result<Response<proto::CrawlerStatus>, Status> {
+        Ok(Response::new(
+            core::CrawlerStatus {
+                last_snapshot: *self.latest_snapshot.borrow(),
+                ..self.crawler.status()
+            }
+            .into(),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use proto::admin_service_server::AdminService;
+    use tonic::Code;
+
+    use super::*;
+
+    fn get_server() -> Server {
+        Server {
+            crawler: Arc::new(CrawlerControl::new(1)),
+            latest_snapshot: watch::channel(None).1,
+        }
+    }
+
+    async fn get_status(server: &Server) -> proto::CrawlerStatus {
+        server
+            .get_crawler_status(Request::new(proto::Empty {}))
+            .await
+            .unwrap()
+            .into_inner()
+    }
+
+    #[tokio::test]
+    async fn test_pause_resume() {
+        let server = get_server();
+        assert_eq!(
+            get_status(&server).await.state(),
+            proto::crawler_status::State::Idle
+        );
+
+        server
+            .pause_crawler(Request::new(proto::Empty {}))
+            .await
+            .unwrap();
+        assert_eq!(
+            get_status(&server).await.state(),
+            proto::crawler_status::State::Paused
+        );
+        let err = server
+            .trigger_crawl(Request::new(proto::CrawlTarget::default()))
+            .await
+            .unwrap_err();
+        assert_eq!(err.code(), Code::FailedPrecondition);
+
+        server
+            .resume_crawler(Request::new(proto::Empty {}))
+            .await
+            .unwrap();
+        server
+            .trigger_crawl(Request::new(proto::CrawlTarget::default()))
+            .await
+            .unwrap();
+        assert_eq!(
+            server.crawler.queue().await.recv().await,
+            Some(core::CrawlTarget::default())
+        );
+    }
+
+    #[tokio::test]
+    async fn test_trigger_wrong_target() {
+        let server = get_server();
+
+        for target in [
+            proto::CrawlTarget {
+                listing: 42,
+                pages: None,
+            },
+            proto::CrawlTarget {
+                listing: proto::Listing::Ask.into(),
+                pages: Some(proto::PageRange { first: 3, last: 2 }),
+            },
+            proto::CrawlTarget {
+                listing: proto::Listing::Ask.into(),
+                pages: Some(proto::PageRange { first: 0, last: 2 }),
+            },
+        ] {
+            let err = server
+                .trigger_crawl(Request::new(target))
+                .await
+                .unwrap_err();
+            assert_eq!(err.code(), Code::InvalidArgument);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_status() {
+        let server = get_server();
+        let target = core::CrawlTarget::new(core::Listing::Show, 2, 4).unwrap();
+        server.crawler.start_crawl(target.clone());
+        server.crawler.set_page(3);
+
+        let status = get_status(&server).await;
+        assert_eq!(status.state(), proto::crawler_status::State::Crawling);
+        assert_eq!(
+            Result::<core::CrawlerStatus, _>::from(status).unwrap(),
+            core::CrawlerStatus {
+                paused: false,
+                crawl: Some(core::CrawlProgress {
+                    target,
+                    page: Some(3)
+                }),
+                last_error: None,
+                last_snapshot: None,
+            }
+        );
+    }
+}
@@ -0,0 +1,1248 @@
+use std::fmt::Debug;
+use std::sync::Arc;
+use std::time::Instant;
+
+use futures::{Stream, StreamExt};
+use prometheus::{HistogramOpts, HistogramVec, IntCounterVec, Opts, Registry};
+use tokio::sync::mpsc::UnboundedSender;
+use tokio_stream::wrappers::UnboundedReceiverStream;
+use tonic::{Code, Status};
+use tracing::Instrument;
+
+use crate::digest;
+use crate::posts_storage::{
+    DiffSnapshots, GetCurrentTopPosts, GetDigest, GetDomainPosts, GetPostHistory, GetPostStats,
+    GetTopDomains, GetTopUsers, GetTrendingPosts, GetUserPosts, GetUserStats,
+};
+use hackernews_crawler::{hackernews_core, hackernews_proxy_proto as proto};
+
+#[derive(Debug, Clone)]
+pub struct ApiMetrics {
+    pub requests: IntCounterVec,
+    pub duration: HistogramVec,
+}
+
+impl Default for ApiMetrics {
+    fn default() -> Self {
+        Self {
+            requests: IntCounterVec::new(
+                Opts::new("grpc_requests_total", "Handled grpc requests"),
+                &["method", "code"],
+            )
+            .unwrap(),
+            duration: HistogramVec::new(
+                HistogramOpts::new(
+                    "grpc_request_duration_seconds",
+                    "Duration of grpc request till the end of the response stream",
+                ),
+                &["method"],
+            )
+            .unwrap(),
+        }
+    }
+}
+
+impl ApiMetrics {
+    pub fn register(&self, registry: &Registry) -> Result<(), prometheus::Error> {
+        registry.register(Box::new(self.requests.clone()))?;
+        registry.register(Box::new(self.duration.clone()))
+    }
+
+    fn observe(&self, method: &str, started: Instant, code: Code) {
+        self.requests
+            .with_label_values(&[method, &format!("{code:?}")])
+            .inc();
+        self.duration
+            .with_label_values(&[method])
+            .observe(started.elapsed().as_secs_f64());
+    }
+}
+
+/// Window of `GetTopDomains` without `since`
+const TOP_DOMAINS_WINDOW: chrono::Duration = chrono::Duration::days(7);
+const TOP_DOMAINS_DEFAULT_LIMIT: u32 = 10;
+const TOP_DOMAINS_MAX_LIMIT: u32 = 100;
+
+/// Window of score snapshots of `GetTrendingPosts` without `window_minutes`
+const TRENDING_DEFAULT_WINDOW_MINUTES: u32 = 60;
+const TRENDING_DEFAULT_LIMIT: u32 = 10;
+const TRENDING_MAX_LIMIT: u32 = 100;
+
+/// Window of `GetDigest` without `since`
+const DIGEST_WINDOW: chrono::Duration = chrono::Duration::hours(24);
+const DIGEST_DEFAULT_LIMIT: u32 = 10;
+const DIGEST_MAX_LIMIT: u32 = 100;
+
+/// Publication window of `GetTopUsers` without `since`
+const TOP_USERS_WINDOW: chrono::Duration = chrono::Duration::days(30);
+const TOP_USERS_DEFAULT_LIMIT: u32 = 10;
+const TOP_USERS_MAX_LIMIT: u32 = 100;
+
+pub struct Server<
+    S: GetCurrentTopPosts
+        + GetUserPosts
+        + GetDomainPosts
+        + GetTopDomains
+        + GetTrendingPosts
+        + DiffSnapshots
+        + GetDigest
+        + GetPostStats
+        + GetPostHistory
+        + GetUserStats
+        + GetTopUsers,
+> {
+    pub posts_storage: Arc<S>,
+    pub metrics: ApiMetrics,
+}
+
+/// Moment of a client timestamp, which may be out of range
+#[allow(clippy::result_large_err)]
+fn moment(timestamp: proto::Timestamp) -> Result<hackernews_core::DateTime, Status> {
+    let seconds = timestamp.timestmap;
+    hackernews_core::DateTime::try_from(timestamp)
+        .map_err(|_| Status::invalid_argument(format!("Wrong timestamp {seconds}")))
+}
+
+/// `since` and `until` of a request, they default to `window` till now
+#[allow(clippy::result_large_err)]
+fn time_range(
+    since: Option<proto::Timestamp>,
+    until: Option<proto::Timestamp>,
+    window: chrono::Duration,
+) -> Result<(hackernews_core::DateTime, hackernews_core::DateTime), Status> {
+    let until = match until {
+        Some(until) => moment(until)?,
+        None => chrono::Local::now().naive_utc(),
+    };
+    let since = match since {
+        Some(since) => moment(since)?,
+        None => until
+            .checked_sub_signed(window)
+            .ok_or_else(|| Status::invalid_argument("`until` is too early"))?,
+    };
+    Ok((since, until))
+}
+
+/// Forward posts to the client, returns the code the stream ended with
+#[allow(clippy::result_large_err)]
+async fn handle_posts_stream<E>(
+    stream: impl Stream<Item = Result<hackernews_core::Post, E>>,
+    sender: UnboundedSender<Result<proto::Post, Status>>,
+) -> Code
+where
+    E: Debug,
+{
+    let mut code = Code::Ok;
+    stream
+        .map(|result_with_post| match result_with_post {
+            Ok(post) => Ok(proto::Post::from(post)),
+            Err(err) => {
+                code = Code::Internal;
+                Err(Status::internal(format!(
+                    "error while deserliaze post: {err:?}" // TODO Hide from user
+                )))
+            }
+        })
+        .for_each(|result_with_post| async {
+            if let Err(err) = sender.send(result_with_post) {
+                tracing::error!("internal error while send err-response to get_top_posts: {err:?}");
+            }
+        })
+        .await;
+    code
+}
+
+#[tonic::async_trait]
+impl<
+        S: GetCurrentTopPosts
+            + GetUserPosts
+            + GetDomainPosts
+            + GetTopDomains
+            + GetTrendingPosts
+            + DiffSnapshots
+            + GetDigest
+            + GetPostStats
+            + GetPostHistory
+            + GetUserStats
+            + GetTopUsers,
+    > proto::post_service_server::PostService for Server<S>
+where
+    S: 'static + Send + Sync,
+    <S as GetUserPosts>::Error: ToString + Debug, // TODO: Mapping to Status
+    <S as GetCurrentTopPosts>::Error: ToString + Debug, // TODO: Mappinc to Status
+    <S as GetDomainPosts>::Error: ToString + Debug,
+    <S as GetTopDomains>::Error: ToString + Debug,
+    <S as GetTrendingPosts>::Error: ToString + Debug,
+    <S as DiffSnapshots>::Error: ToString + Debug,
+    <S as GetDigest>::Error: ToString + Debug,
+    <S as GetPostStats>::Error: ToString + Debug,
+    <S as GetPostHistory>::Error: ToString + Debug,
+    <S as GetUserStats>::Error: ToString + Debug,
+    <S as GetTopUsers>::Error: ToString + Debug,
+{
+    type GetTopPostsStream = UnboundedReceiverStream<Result<proto::Post, Status>>;
+    type GetUserPostsStream = UnboundedReceiverStream<Result<proto::Post, Status>>;
+    type GetDomainPostsStream = UnboundedReceiverStream<Result<proto::Post, Status>>;
+
+    async fn get_top_posts(
+        &self,
+        _request: tonic::Request<proto::TopPostRequest>,
+    ) -> Result<tonic::Response<Self::GetTopPostsStream>, Status> {
+        let started = Instant::now();
+        let (sender, receiver) = tokio::sync::mpsc::unbounded_channel();
+
+        let posts_storage = self.posts_storage.clone();
+        let metrics = self.metrics.clone();
+        // We cannot return the original stream, because it has a link
+        // to the sqlx pool but cannot own it. I did not find offhand
+        // a way to "cheat" fetch call inside sqlx, so I created a bidirectional
+        // channel, it would have been more time, most likely would have made it easier
+        let _task = tokio::task::spawn(
+            async move {
+                let stream = match posts_storage.get_current_top_posts().await {
+                    Ok(stream) => stream,
+                    Err(err) => {
+                        if let Err(err) = sender.send(Err(Status::internal(err.to_string()))) {
+                            tracing::error!(
+                                "internal error while send err-response to get_top_posts: {err:?}"
+                            );
+                        }
+                        metrics.observe("GetTopPosts", started, Code::Internal);
+                        return;
+                    }
+                };
+
+                let code = handle_posts_stream(stream, sender).await;
+                metrics.observe("GetTopPosts", started, code);
+            }
+            .in_current_span(),
+        );
+
+        // A more correct way is to return a wrapper over this stream to
+        // also store and stop the tokio task not through an error when
+        // the receiver is killed, however, let's leave this as a potential
+        // improvement
+        Ok(tonic::Response::new(UnboundedReceiverStream::new(receiver)))
+    }
+
+    async fn get_user_posts(
+        &self,
+        request: tonic::Request<proto::UserPostRequest>,
+    ) -> Result<tonic::Response<Self::GetUserPostsStream>, tonic::Status> {
+        let started = Instant::now();
+        let request = match Option::<hackernews_core::UserPostRequest>::from(request.into_inner()) {
+            Some(request) => request,
+            None => {
+                self.metrics
+                    .observe("GetUserPosts", started, Code::InvalidArgument);
+                return Err(Status::invalid_argument("Please provide request detail"));
+            }
+        };
+
+        let (sender, receiver) = tokio::sync::mpsc::unbounded_channel();
+
+        let posts_storage = self.posts_storage.clone();
+        let metrics = self.metrics.clone();
+        // We cannot return the original stream, because it has a link
+        // to the sqlx pool but cannot own it. I did not find offhand
+        // a way to "cheat" fetch inside sqlx, so I created a bidirectional
+        // channel, it would have been more time, most likely would have made it easier
+        let _task = tokio::task::spawn(
+            async move {
+                let stream = match posts_storage.get_user_posts(request).await {
+                    Ok(stream) => stream,
+                    Err(err) => {
+                        if let Err(err) = sender.send(Err(Status::internal(err.to_string()))) {
+                            tracing::error!(
+                                "internal error while send err-response to get_user_posts: {err:?}"
+                            );
+                        }
+                        metrics.observe("GetUserPosts", started, Code::Internal);
+                        return;
+                    }
+                };
+
+                let code = handle_posts_stream(stream, sender).await;
+                metrics.observe("GetUserPosts", started, code);
+            }
+            .in_current_span(),
+        );
+
+        // A more correct way is to return a wrapper over this stream to
+        // also store and stop the tokio task not through an error when
+        // the receiver is killed, however, let's leave this as a potential
+        // improvement
+        Ok(tonic::Response::new(UnboundedReceiverStream::new(receiver)))
+    }
+
+    async fn get_domain_posts(
+        &self,
+        request: tonic::Request<proto::DomainPostRequest>,
+    ) -> Result<tonic::Response<Self::GetDomainPostsStream>, Status> {
+        let started = Instant::now();
+        let request = match Option::<hackernews_core::DomainPostRequest>::from(request.into_inner())
+        {
+            Some(request) => request,
+            None => {
+                self.metrics
+                    .observe("GetDomainPosts", started, Code::InvalidArgument);
+                return Err(Status::invalid_argument("Please provide a domain"));
+            }
+        };
+
+        let (sender, receiver) = tokio::sync::mpsc::unbounded_channel();
+
+        let posts_storage = self.posts_storage.clone();
+        let metrics = self.metrics.clone();
+        let _task = tokio::task::spawn(
+            async move {
+                let stream = match posts_storage.get_domain_posts(request).await {
+                    Ok(stream) => stream,
+                    Err(err) => {
+                        if let Err(err) = sender.send(Err(Status::internal(err.to_string()))) {
+                            tracing::error!(
+                                "internal error while send err-response to get_domain_posts: {err:?}"
+                            );
+                        }
+                        metrics.observe("GetDomainPosts", started, Code::Internal);
+                        return;
+                    }
+                };
+
+                let code = handle_posts_stream(stream, sender).await;
+                metrics.observe("GetDomainPosts", started, code);
+            }
+            .in_current_span(),
+        );
+
+        Ok(tonic::Response::new(UnboundedReceiverStream::new(receiver)))
+    }
+
+    async fn get_top_domains(
+        &self,
+        request: tonic::Request<proto::TopDomainsRequest>,
+    ) -> Result<tonic::Response<proto::TopDomainsResponse>, Status> {
+        let started = Instant::now();
+        let request = request.into_inner();
+        let limit = match request.limit {
+            0 => TOP_DOMAINS_DEFAULT_LIMIT,
+            limit => limit.min(TOP_DOMAINS_MAX_LIMIT),
+        };
+
+        let result = match time_range(request.since, request.until, TOP_DOMAINS_WINDOW) {
+            Ok((since, until)) => self
+                .posts_storage
+                .get_top_domains(since, until, limit)
+                .await
+                .map(|domains| proto::TopDomainsResponse {
+                    domains: domains.into_iter().map(Into::into).collect(),
+                })
+                .map_err(|err| Status::internal(err.to_string())),
+            Err(status) => Err(status),
+        };
+        self.metrics.observe(
+            "GetTopDomains",
+            started,
+            result.as_ref().map_or_else(Status::code, |_| Code::Ok),
+        );
+
+        result.map(tonic::Response::new)
+    }
+
+    async fn get_trending_posts(
+        &self,
+        request: tonic::Request<proto::TrendingPostsRequest>,
+    ) -> Result<tonic::Response<proto::TrendingPostsResponse>, Status> {
+        let started = Instant::now();
+        let request = request.into_inner();
+        let window_minutes = match request.window_minutes {
+            0 => TRENDING_DEFAULT_WINDOW_MINUTES,
+            window_minutes => window_minutes,
+        };
+        let limit = match request.limit {
+            0 => TRENDING_DEFAULT_LIMIT,
+            limit => limit.min(TRENDING_MAX_LIMIT),
+        };
+        let until = chrono::Local::now().naive_utc();
+        let since = until - chrono::Duration::minutes(window_minutes.into());
+
+        let result = self
+            .posts_storage
+            .get_trending_posts(since, until, limit)
+            .await
+            .map(|posts| proto::TrendingPostsResponse {
+                posts: posts.into_iter().map(Into::into).collect(),
+            })
+            .map_err(|err| Status::internal(err.to_string()));
+        self.metrics.observe(
+            "GetTrendingPosts",
+            started,
+            result.as_ref().map_or_else(Status::code, |_| Code::Ok),
+        );
+
+        result.map(tonic::Response::new)
+    }
+
+    async fn diff_snapshots(
+        &self,
+        request: tonic::Request<proto::DiffSnapshotsRequest>,
+    ) -> Result<tonic::Response<proto::SnapshotDiff>, Status> {
+        let started = Instant::now();
+        let request = request.into_inner();
+        let to = match request.to {
+            Some(to) => moment(to),
+            None => Ok(chrono::Local::now().naive_utc()),
+        };
+        let result = match (request.from.map(moment), to) {
+            (Some(Ok(from)), Ok(to)) => match self.posts_storage.diff_snapshots(from, to).await {
+                Ok(Some(diff)) => Ok(proto::SnapshotDiff::from(diff)),
+                Ok(None) => Err(Status::not_found("No crawl snapshot before `from`")),
+                Err(err) => Err(Status::internal(err.to_string())),
+            },
+            (None, _) => Err(Status::invalid_argument("Please provide `from`")),
+            (Some(Err(status)), _) | (_, Err(status)) => Err(status),
+        };
+        self.metrics.observe(
+            "DiffSnapshots",
+            started,
+            result.as_ref().map_or_else(Status::code, |_| Code::Ok),
+        );
+
+        result.map(tonic::Response::new)
+    }
+
+    async fn get_digest(
+        &self,
+        request: tonic::Request<proto::DigestRequest>,
+    ) -> Result<tonic::Response<proto::RenderedDigest>, Status> {
+        let started = Instant::now();
+        let request = request.into_inner();
+        let limit = match request.limit {
+            0 => DIGEST_DEFAULT_LIMIT,
+            limit => limit.min(DIGEST_MAX_LIMIT),
+        };
+
+        let range = time_range(request.since, request.until, DIGEST_WINDOW);
+        let result = match (proto::DigestFormat::from_i32(request.format), range) {
+            (Some(format), Ok((since, until))) => {
+                match self.posts_storage.get_digest(since, until, limit).await {
+                    Ok(digest) => digest::render(&digest, format.into())
+                        .map(|content| proto::RenderedDigest { content })
+                        .map_err(|err| Status::internal(err.to_string())),
+                    Err(err) => Err(Status::internal(err.to_string())),
+                }
+            }
+            (None, _) => Err(Status::invalid_argument(format!(
+                "Unknown format {}",
+                request.format
+            ))),
+            (_, Err(status)) => Err(status),
+        };
+        self.metrics.observe(
+            "GetDigest",
+            started,
+            result.as_ref().map_or_else(Status::code, |_| Code::Ok),
+        );
+
+        result.map(tonic::Response::new)
+    }
+
+    async fn get_post_stats(
+        &self,
+        request: tonic::Request<proto::PostStatsRequest>,
+    ) -> Result<tonic::Response<proto::PostStats>, Status> {
+        let started = Instant::now();
+        let result = match self
+            .posts_storage
+            .get_post_stats(request.into_inner().post_id)
+            .await
+        {
+            Ok(Some(stats)) => Ok(proto::PostStats::from(stats)),
+            Ok(None) => Err(Status::not_found("The post was never crawled")),
+            Err(err) => Err(Status::internal(err.to_string())),
+        };
+        self.metrics.observe(
+            "GetPostStats",
+            started,
+            result.as_ref().map_or_else(Status::code, |_| Code::Ok),
+        );
+
+        result.map(tonic::Response::new)
+    }
+
+    async fn get_post_history(
+        &self,
+        request: tonic::Request<proto::PostHistoryRequest>,
+    ) -> Result<tonic::Response<proto::PostHistory>, Status> {
+        let started = Instant::now();
+        let result = self
+            .posts_storage
+            .get_post_history(request.into_inner().post_id)
+            .await
+            .map(|snapshots| proto::PostHistory {
+                snapshots: snapshots.into_iter().map(Into::into).collect(),
+            })
+            .map_err(|err| Status::internal(err.to_string()));
+        self.metrics.observe(
+            "GetPostHistory",
+            started,
+            result.as_ref().map_or_else(Status::code, |_| Code::Ok),
+        );
+
+        result.map(tonic::Response::new)
+    }
+
+    async fn get_user_stats(
+        &self,
+        request: tonic::Request<proto::UserStatsRequest>,
+    ) -> Result<tonic::Response<proto::UserStats>, Status> {
+        let started = Instant::now();
+        let user = request.into_inner().user;
+        let result = if user.is_empty() {
+            Err(Status::invalid_argument("Please provide a user"))
+        } else {
+            match self.posts_storage.get_user_stats(user).await {
+                Ok(Some(stats)) => Ok(proto::UserStats::from(stats)),
+                Ok(None) => Err(Status::not_found("No crawled posts of the user")),
+                Err(err) => Err(Status::internal(err.to_string())),
+            }
+        };
+        self.metrics.observe(
+            "GetUserStats",
+            started,
+            result.as_ref().map_or_else(Status::code, |_| Code::Ok),
+        );
+
+        result.map(tonic::Response::new)
+    }
+
+    async fn get_top_users(
+        &self,
+        request: tonic::Request<proto::TopUsersRequest>,
+    ) -> Result<tonic::Response<proto::TopUsersResponse>, Status> {
+        let started = Instant::now();
+        let request = request.into_inner();
+        let limit = match request.limit {
+            0 => TOP_USERS_DEFAULT_LIMIT,
+            limit => limit.min(TOP_USERS_MAX_LIMIT),
+        };
+
+        let range = time_range(request.since, request.until, TOP_USERS_WINDOW);
+        let result = match (proto::UserOrder::from_i32(request.order_by), range) {
+            (Some(order), Ok((since, until))) => self
+                .posts_storage
+                .get_top_users(since, until, order.into(), limit)
+                .await
+                .map(|users| proto::TopUsersResponse {
+                    users: users.into_iter().map(Into::into).collect(),
+                })
+                .map_err(|err| Status::internal(err.to_string())),
+            (None, _) => Err(Status::invalid_argument(format!(
+                "Unknown order {}",
+                request.order_by
+            ))),
+            (_, Err(status)) => Err(status),
+        };
+        self.metrics.observe(
+            "GetTopUsers",
+            started,
+            result.as_ref().map_or_else(Status::code, |_| Code::Ok),
+        );
+
+        result.map(tonic::Response::new)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::sync::Mutex;
+
+    use futures::stream::BoxStream;
+    use tonic::codegen::Service;
+
+    use hackernews_crawler::hackernews_core::{Post, UserPostRequest};
+    use hackernews_crawler::proto::post_service_server::PostService;
+
+    use super::*;
+
+    #[derive(Debug, Default)]
+    struct StorageMock {
+        pub users_posts: HashMap<String, Post>,
+        pub top_posts: Vec<Vec<Post>>,
+        pub domain_posts: HashMap<String, Vec<Post>>,
+        pub domains: Vec<hackernews_core::DomainStats>,
+        pub trending_posts: Vec<hackernews_core::TrendingPost>,
+        /// Moments of crawl snapshots, a diff needs one at or before `from`
+        pub snapshots: Vec<hackernews_core::DateTime>,
+        pub post_stats: HashMap<hackernews_core::PostId, hackernews_core::PostStats>,
+        pub post_history: HashMap<hackernews_core::PostId, Vec<hackernews_core::PostSnapshot>>,
+        pub user_stats: HashMap<String, hackernews_core::UserStats>,
+        /// `since`, `until` and `limit` of window queries, as they are called
+        pub windows: Mutex<Vec<(hackernews_core::DateTime, hackernews_core::DateTime, u32)>>,
+        pub user_orders: Mutex<Vec<hackernews_core::UserOrder>>,
+    }
+
+    impl StorageMock {
+        // TODO Migrate to RAII
+        fn assert_ready(&self) {
+            assert!(self.users_posts.is_empty());
+            assert!(self.top_posts.is_empty());
+        }
+
+        fn record_window(
+            &self,
+            since: hackernews_core::DateTime,
+            until: hackernews_core::DateTime,
+            limit: u32,
+        ) {
+            self.windows.lock().unwrap().push((since, until, limit));
+        }
+
+        fn last_window(&self) -> (hackernews_core::DateTime, hackernews_core::DateTime, u32) {
+            *self
+                .windows
+                .lock()
+                .unwrap()
+                .last()
+                .expect("no window query")
+        }
+    }
+
+    #[async_trait::async_trait]
+    impl GetUserPosts for StorageMock {
+        type Error = sqlx::Error;
+
+        async fn get_user_posts<'l>(
+            &'l self,
+            _filter: UserPostRequest,
+        ) -> Result<BoxStream<'l, Result<Post, Self::Error>>, Self::Error> {
+            todo!("validate the correctness of the request and return user posts")
+        }
+    }
+
+    #[async_trait::async_trait]
+    impl GetDomainPosts for StorageMock {
+        type Error = sqlx::Error;
+
+        async fn get_domain_posts<'l>(
+            &'l self,
+            request: hackernews_core::DomainPostRequest,
+        ) -> Result<BoxStream<'l, Result<Post, Self::Error>>, Self::Error> {
+            let posts = self
+                .domain_posts
+                .get(&request.domain)
+                .cloned()
+                .unwrap_or_default();
+            Ok(futures::stream::iter(posts.into_iter().map(Ok)).boxed())
+        }
+    }
+
+    #[async_trait::async_trait]
+    impl GetTopDomains for StorageMock {
+        type Error = sqlx::Error;
+
+        async fn get_top_domains(
+            &self,
+            since: hackernews_core::DateTime,
+            until: hackernews_core::DateTime,
+            limit: u32,
+        ) -> Result<Vec<hackernews_core::DomainStats>, Self::Error> {
+            self.record_window(since, until, limit);
+            Ok(self.domains.iter().take(limit as usize).cloned().collect())
+        }
+    }
+
+    #[async_trait::async_trait]
+    impl GetTrendingPosts for StorageMock {
+        type Error = sqlx::Error;
+
+        async fn get_trending_posts(
+            &self,
+            since: hackernews_core::DateTime,
+            until: hackernews_core::DateTime,
+            limit: u32,
+        ) -> Result<Vec<hackernews_core::TrendingPost>, Self::Error> {
+            self.record_window(since, until, limit);
+            Ok(self
+                .trending_posts
+                .iter()
+                .take(limit as usize)
+                .cloned()
+                .collect())
+        }
+    }
+
+    #[async_trait::async_trait]
+    impl DiffSnapshots for StorageMock {
+        type Error = sqlx::Error;
+
+        async fn diff_snapshots(
+            &self,
+            from: hackernews_core::DateTime,
+            to: hackernews_core::DateTime,
+        ) -> Result<Option<hackernews_core::SnapshotDiff>, Self::Error> {
+            Ok(self
+                .snapshots
+                .iter()
+                .any(|snapshot| *snapshot <= from)
+                .then(|| hackernews_core::SnapshotDiff {
+                    from,
+                    to,
+                    changes: Vec::new(),
+                }))
+        }
+    }
+
+    #[async_trait::async_trait]
+    impl GetDigest for StorageMock {
+        type Error = sqlx::Error;
+
+        async fn get_digest(
+            &self,
+            since: hackernews_core::DateTime,
+            until: hackernews_core::DateTime,
+            limit: u32,
+        ) -> Result<hackernews_core::Digest, Self::Error> {
+            self.record_window(since, until, limit);
+            Ok(hackernews_core::Digest {
+                since,
+                until,
+                sections: Vec::new(),
+            })
+        }
+    }
+
+    #[async_trait::async_trait]
+    impl GetPostStats for StorageMock {
+        type Error = sqlx::Error;
+
+        async fn get_post_stats(
+            &self,
+            post_id: hackernews_core::PostId,
+        ) -> Result<Option<hackernews_core::PostStats>, Self::Error> {
+            Ok(self.post_stats.get(&post_id).cloned())
+        }
+    }
+
+    #[async_trait::async_trait]
+    impl GetPostHistory for StorageMock {
+        type Error = sqlx::Error;
+
+        async fn get_post_history(
+            &self,
+            post_id: hackernews_core::PostId,
+        ) -> Result<Vec<hackernews_core::PostSnapshot>, Self::Error> {
+            Ok(self.post_history.get(&post_id).cloned().unwrap_or_default())
+        }
+    }
+
+    #[async_trait::async_trait]
+    impl GetUserStats for StorageMock {
+        type Error = sqlx::Error;
+
+        async fn get_user_stats(
+            &self,
+            user: String,
+        ) -> Result<Option<hackernews_core::UserStats>, Self::Error> {
+            Ok(self.user_stats.get(&user).cloned())
+        }
+    }
+
+    #[async_trait::async_trait]
+    impl GetTopUsers for StorageMock {
+        type Error = sqlx::Error;
+
+        async fn get_top_users(
+            &self,
+            since: hackernews_core::DateTime,
+            until: hackernews_core::DateTime,
+            order: hackernews_core::UserOrder,
+            limit: u32,
+        ) -> Result<Vec<hackernews_core::UserStats>, Self::Error> {
+            self.record_window(since, until, limit);
+            self.user_orders.lock().unwrap().push(order);
+            let mut users = self.user_stats.values().cloned().collect::<Vec<_>>();
+            users.sort_by(|a, b| a.user.cmp(&b.user));
+            users.truncate(limit as usize);
+            Ok(users)
+        }
+    }
+
+    #[async_trait::async_trait]
+    impl GetCurrentTopPosts for StorageMock {
+        type Error = sqlx::Error;
+
+        async fn get_current_top_posts<'l>(
+            &'l self,
+        ) -> Result<BoxStream<'l, Result<Post, Self::Error>>, Self::Error> {
+            todo!("validate the correctness of the request and return current top posts")
+        }
+    }
+
+    #[test]
+    fn test_get_top_posts() {
+        let mock = Arc::new(StorageMock::default());
+        use hackernews_crawler::proto::post_service_server::PostServiceServer;
+        drop(
+            PostServiceServer::new(Server {
+                posts_storage: mock.clone(),
+                metrics: ApiMetrics::default(),
+            })
+            .call(tonic::codegen::http::Request::<_>::new(
+                "TODO, Mock Request".to_owned(),
+            )),
+        );
+        mock.assert_ready();
+    }
+
+    #[test]
+    fn test_get_user_posts() {
+        let mock = Arc::new(StorageMock::default());
+        use hackernews_crawler::proto::post_service_server::PostServiceServer;
+        drop(
+            PostServiceServer::new(Server {
+                posts_storage: mock.clone(),
+                metrics: ApiMetrics::default(),
+            })
+            .call(tonic::codegen::http::Request::<_>::new(
+                "TODO, Mock Request".to_owned(),
+            )),
+        );
+        mock.assert_ready();
+    }
+
+    #[test]
+    fn test_time_range() {
+        let timestamp = |timestmap| Some(proto::Timestamp { timestmap });
+        let day = chrono::Duration::days(1);
+
+        let (since, until) = time_range(None, timestamp(86_400 * 2), day).unwrap();
+        assert_eq!(since.and_utc().timestamp(), 86_400);
+        assert_eq!(until.and_utc().timestamp(), 86_400 * 2);
+
+        let (since, until) = time_range(timestamp(10), None, day).unwrap();
+        assert_eq!(since.and_utc().timestamp(), 10);
+        assert!(until > since);
+
+        for (since, until) in [
+            (None, timestamp(i64::MAX)),
+            (timestamp(i64::MIN), None),
+            // The default window would go before the earliest moment
+            (None, Some(hackernews_core::DateTime::MIN.into())),
+        ] {
+            assert_eq!(
+                time_range(since, until, day).unwrap_err().code(),
+                Code::InvalidArgument
+            );
+        }
+    }
+
+    fn get_server(mock: StorageMock) -> Server<StorageMock> {
+        Server {
+            posts_storage: Arc::new(mock),
+            metrics: ApiMetrics::default(),
+        }
+    }
+
+    fn day(day: u32) -> hackernews_core::DateTime {
+        chrono::NaiveDate::from_ymd_opt(2023, 1, day)
+            .unwrap()
+            .and_hms_opt(12, 0, 0)
+            .unwrap()
+    }
+
+    fn get_post(post_id: hackernews_core::PostId) -> Post {
+        Post {
+            post_id,
+            title: format!("Post {post_id}"),
+            author: "someone".to_owned(),
+            url: format!("item?id={post_id}"),
+            link: Some(format!("https://example.com/{post_id}")),
+            publication_moment: day(1),
+            last_snapshot_moment: day(2),
+            rank: None,
+            listing: None,
+            score: Some(10),
+        }
+    }
+
+    fn get_user_stats(user: &str) -> hackernews_core::UserStats {
+        hackernews_core::UserStats {
+            user: user.to_owned(),
+            posts: 2,
+            first_page_posts: 1,
+            best_rank: Some(3),
+            front_page_minutes: 90.0,
+            average_score: Some(42.0),
+        }
+    }
+
+    /// Out of range of `DateTime`
+    const WRONG_TIMESTAMP: proto::Timestamp = proto::Timestamp {
+        timestmap: i64::MAX,
+    };
+
+    #[tokio::test]
+    async fn test_get_domain_posts() {
+        let server = get_server(StorageMock {
+            domain_posts: HashMap::from([(
+                "example.com".to_owned(),
+                vec![get_post(1), get_post(2)],
+            )]),
+            ..Default::default()
+        });
+        let domain_posts = |domain: &str| {
+            server.get_domain_posts(tonic::Request::new(proto::DomainPostRequest {
+                domain: domain.to_owned(),
+                was_at_first_page: false,
+            }))
+        };
+
+        // Links and hosts are requested by their registrable domain
+        let posts = domain_posts("https://www.example.com/a")
+            .await
+            .unwrap()
+            .into_inner()
+            .map(|post| post.unwrap().post_id)
+            .collect::<Vec<_>>()
+            .await;
+        assert_eq!(posts, vec![1, 2]);
+
+        assert_eq!(
+            domain_posts(" ").await.unwrap_err().code(),
+            Code::InvalidArgument
+        );
+    }
+
+    #[tokio::test]
+    async fn test_get_top_domains() {
+        let domain = |domain: &str| hackernews_core::DomainStats {
+            domain: domain.to_owned(),
+            posts: 1,
+            appearances: 2,
+            last_seen: day(2),
+        };
+        let server = get_server(StorageMock {
+            domains: vec![domain("example.com"), domain("other.org")],
+            ..Default::default()
+        });
+        let top_domains = |since, until, limit| {
+            server.get_top_domains(tonic::Request::new(proto::TopDomainsRequest {
+                since,
+                until,
+                limit,
+            }))
+        };
+
+        let response = top_domains(Some(day(1).into()), Some(day(3).into()), 1)
+            .await
+            .unwrap()
+            .into_inner();
+        assert_eq!(response.domains.len(), 1);
+        assert_eq!(response.domains[0].domain, "example.com");
+        assert_eq!(server.posts_storage.last_window(), (day(1), day(3), 1));
+
+        top_domains(None, Some(day(8).into()), 0).await.unwrap();
+        assert_eq!(
+            server.posts_storage.last_window(),
+            (day(1), day(8), TOP_DOMAINS_DEFAULT_LIMIT)
+        );
+        top_domains(None, None, 1000).await.unwrap();
+        let (since, until, limit) = server.posts_storage.last_window();
+        assert_eq!(until - since, TOP_DOMAINS_WINDOW);
+        assert_eq!(limit, TOP_DOMAINS_MAX_LIMIT);
+
+        assert_eq!(
+            top_domains(None, Some(WRONG_TIMESTAMP), 0)
+                .await
+                .unwrap_err()
+                .code(),
+            Code::InvalidArgument
+        );
+    }
+
+    #[tokio::test]
+    async fn test_get_trending_posts() {
+        let server = get_server(StorageMock {
+            trending_posts: (1..=3)
+                .map(|post_id| hackernews_core::TrendingPost {
+                    post: get_post(post_id),
+                    points_per_hour: Some(5.0),
+                    rank_velocity: None,
+                })
+                .collect(),
+            ..Default::default()
+        });
+        let trending_posts = |window_minutes, limit| {
+            server.get_trending_posts(tonic::Request::new(proto::TrendingPostsRequest {
+                window_minutes,
+                limit,
+            }))
+        };
+
+        let response = trending_posts(30, 2).await.unwrap().into_inner();
+        assert_eq!(
+            response
+                .posts
+                .iter()
+                .map(|trending| trending.post.as_ref().unwrap().post_id)
+                .collect::<Vec<_>>(),
+            vec![1, 2]
+        );
+        let (since, until, limit) = server.posts_storage.last_window();
+        assert_eq!(until - since, chrono::Duration::minutes(30));
+        assert_eq!(limit, 2);
+
+        trending_posts(0, 0).await.unwrap();
+        let (since, until, limit) = server.posts_storage.last_window();
+        assert_eq!(
+            until - since,
+            chrono::Duration::minutes(TRENDING_DEFAULT_WINDOW_MINUTES.into())
+        );
+        assert_eq!(limit, TRENDING_DEFAULT_LIMIT);
+
+        trending_posts(0, 1000).await.unwrap();
+        assert_eq!(server.posts_storage.last_window().2, TRENDING_MAX_LIMIT);
+    }
+
+    #[tokio::test]
+    async fn test_diff_snapshots() {
+        let server = get_server(StorageMock {
+            snapshots: vec![day(2), day(3)],
+            ..Default::default()
+        });
+        let diff = |from, to| {
+            server.diff_snapshots(tonic::Request::new(proto::DiffSnapshotsRequest {
+                from,
+                to,
+            }))
+        };
+
+        let response = diff(Some(day(2).into()), Some(day(3).into()))
+            .await
+            .unwrap()
+            .into_inner();
+        assert_eq!(response.from, Some(day(2).into()));
+        assert_eq!(response.to, Some(day(3).into()));
+        // `to` defaults to now
+        let response = diff(Some(day(3).into()), None).await.unwrap().into_inner();
+        assert!(response.to.unwrap().timestmap > day(3).and_utc().timestamp());
+
+        for (from, to, code) in [
+            (None, Some(day(3).into()), Code::InvalidArgument),
+            (Some(WRONG_TIMESTAMP), None, Code::InvalidArgument),
+            (
+                Some(day(2).into()),
+                Some(WRONG_TIMESTAMP),
+                Code::InvalidArgument,
+            ),
+            // No crawl snapshot before `from`
+            (Some(day(1).into()), None, Code::NotFound),
+        ] {
+            assert_eq!(diff(from, to).await.unwrap_err().code(), code);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_get_digest() {
+        let server = get_server(StorageMock::default());
+        let digest = |since, until, limit, format| {
+            server.get_digest(tonic::Request::new(proto::DigestRequest {
+                since,
+                until,
+                limit,
+                format,
+            }))
+        };
+
+        let response = digest(
+            Some(day(1).into()),
+            Some(day(2).into()),
+            5,
+            proto::DigestFormat::Html.into(),
+        )
+        .await
+        .unwrap()
+        .into_inner();
+        assert!(!response.content.is_empty());
+        assert_eq!(server.posts_storage.last_window(), (day(1), day(2), 5));
+
+        digest(
+            None,
+            Some(day(3).into()),
+            0,
+            proto::DigestFormat::Markdown.into(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(
+            server.posts_storage.last_window(),
+            (day(2), day(3), DIGEST_DEFAULT_LIMIT)
+        );
+        digest(None, None, 1000, proto::DigestFormat::Markdown.into())
+            .await
+            .unwrap();
+        let (since, until, limit) = server.posts_storage.last_window();
+        assert_eq!(until - since, DIGEST_WINDOW);
+        assert_eq!(limit, DIGEST_MAX_LIMIT);
+
+        for (until, format) in [(None, 100), (Some(WRONG_TIMESTAMP), 0)] {
+            assert_eq!(
+                digest(None, until, 0, format).await.unwrap_err().code(),
+                Code::InvalidArgument
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_get_post_stats() {
+        let server = get_server(StorageMock {
+            post_stats: HashMap::from([(
+                1,
+                hackernews_core::PostStats {
+                    post_id: 1,
+                    first_seen: Some(day(1)),
+                    last_seen: Some(day(2)),
+                    front_page_minutes: 60.0,
+                    appearances: 2,
+                    best_rank: Some(4),
+                },
+            )]),
+            ..Default::default()
+        });
+        let post_stats = |post_id| {
+            server.get_post_stats(tonic::Request::new(proto::PostStatsRequest { post_id }))
+        };
+
+        let stats = post_stats(1).await.unwrap().into_inner();
+        assert_eq!(stats.appearances, 2);
+        assert_eq!(stats.best_rank, 4);
+        assert_eq!(stats.first_seen, Some(day(1).into()));
+
+        assert_eq!(post_stats(2).await.unwrap_err().code(), Code::NotFound);
+    }
+
+    #[tokio::test]
+    async fn test_get_post_history() {
+        let server = get_server(StorageMock {
+            post_history: HashMap::from([(
+                1,
+                vec![hackernews_core::PostSnapshot {
+                    snapshot_moment: day(1),
+                    listing: Some(hackernews_core::Listing::Top),
+                    rank: Some(3),
+                    score: Some(10),
+                    was_at_first_page: true,
+                }],
+            )]),
+            ..Default::default()
+        });
+        let post_history = |post_id| {
+            server.get_post_history(tonic::Request::new(proto::PostHistoryRequest { post_id }))
+        };
+
+        let history = post_history(1).await.unwrap().into_inner();
+        assert_eq!(history.snapshots.len(), 1);
+        assert_eq!(history.snapshots[0].rank, 3);
+        // Posts, which were never crawled, have no history
+        assert!(post_history(2)
+            .await
+            .unwrap()
+            .into_inner()
+            .snapshots
+            .is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_get_user_stats() {
+        let server = get_server(StorageMock {
+            user_stats: HashMap::from([("someone".to_owned(), get_user_stats("someone"))]),
+            ..Default::default()
+        });
+        let user_stats = |user: &str| {
+            server.get_user_stats(tonic::Request::new(proto::UserStatsRequest {
+                user: user.to_owned(),
+            }))
+        };
+
+        let stats = user_stats("someone").await.unwrap().into_inner();
+        assert_eq!(stats.posts, 2);
+        assert_eq!(stats.best_rank, 3);
+
+        assert_eq!(
+            user_stats("").await.unwrap_err().code(),
+            Code::InvalidArgument
+        );
+        assert_eq!(
+            user_stats("nobody").await.unwrap_err().code(),
+            Code::NotFound
+        );
+    }
+
+    #[tokio::test]
+    async fn test_get_top_users() {
+        let server = get_server(StorageMock {
+            user_stats: HashMap::from([
+                ("first".to_owned(), get_user_stats("first")),
+                ("second".to_owned(), get_user_stats("second")),
+            ]),
+            ..Default::default()
+        });
+        let top_users = |since, until, order_by, limit| {
+            server.get_top_users(tonic::Request::new(proto::TopUsersRequest {
+                since,
+                until,
+                order_by,
+                limit,
+            }))
+        };
+
+        let response = top_users(
+            Some(day(1).into()),
+            Some(day(5).into()),
+            proto::UserOrder::BestRank.into(),
+            1,
+        )
+        .await
+        .unwrap()
+        .into_inner();
+        assert_eq!(
+            response
+                .users
+                .iter()
+                .map(|user| user.user.as_str())
+                .collect::<Vec<_>>(),
+            vec!["first"]
+        );
+        assert_eq!(server.posts_storage.last_window(), (day(1), day(5), 1));
+
+        top_users(None, Some(day(31).into()), 0, 0).await.unwrap();
+        assert_eq!(
+            server.posts_storage.last_window(),
+            (day(1), day(31), TOP_USERS_DEFAULT_LIMIT)
+        );
+        top_users(None, None, 0, 1000).await.unwrap();
+        let (since, until, limit) = server.posts_storage.last_window();
+        assert_eq!(until - since, TOP_USERS_WINDOW);
+        assert_eq!(limit, TOP_USERS_MAX_LIMIT);
+        assert_eq!(
+            *server.posts_storage.user_orders.lock().unwrap(),
+            vec![
+                hackernews_core::UserOrder::BestRank,
+                hackernews_core::UserOrder::FrontPageMinutes,
+                hackernews_core::UserOrder::FrontPageMinutes,
+            ]
+        );
+
+        for (until, order_by) in [(None, 100), (Some(WRONG_TIMESTAMP), 0)] {
+            assert_eq!(
+                top_users(None, until, order_by, 0)
+                    .await
+                    .unwrap_err()
+                    .code(),
+                Code::InvalidArgument
+            );
+        }
+    }
+}
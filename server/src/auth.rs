@@ -0,0 +1,263 @@
+use std::{collections::HashMap, sync::Arc};
+
+use prometheus::{IntCounterVec, Opts, Registry};
+use serde::Deserialize;
+use tonic::{Request, Status};
+
+use crate::rate_limit::Quota;
+
+/// What an API key may call
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, strum::Display)]
+#[serde(rename_all = "lowercase")]
+#[strum(serialize_all = "lowercase")]
+pub enum Scope {
+    /// `PostService`
+    Read,
+    /// `AdminService`
+    Admin,
+}
+
+/// API key from `[[api_keys]]` of `config.toml`
+#[derive(Debug, Clone, Deserialize)]
+pub struct ApiKey {
+    /// Shown in logs instead of the key itself
+    pub name: String,
+    pub key: String,
+    pub scopes: Vec<Scope>,
+    /// Unlimited if not set
+    #[serde(default)]
+    pub requests_per_minute: Option<u32>,
+}
+
+fn bearer_key(authorization: &str) -> Option<&str> {
+    authorization.strip_prefix("Bearer ").map(str::trim)
+}
+
+#[derive(Debug)]
+struct KeyState {
+    name: String,
+    scopes: Vec<Scope>,
+    quota: Option<std::sync::Mutex<Quota>>,
+}
+
+/// Check of `authorization: Bearer <key>` metadata of grpc calls and
+/// the same header of feeds and `/log-filter`
+///
+/// Without configured keys every call is allowed
+#[derive(Debug)]
+pub struct Auth {
+    keys: HashMap<String, KeyState>,
+    rejected: IntCounterVec,
+}
+
+#[allow(clippy::result_large_err)]
+impl Auth {
+    pub fn new(keys: Vec<ApiKey>) -> Self {
+        Self {
+            keys: keys
+                .into_iter()
+                .map(|key| {
+                    (
+                        key.key,
+                        KeyState {
+                            name: key.name,
+                            scopes: key.scopes,
+                            quota: key
+                                .requests_per_minute
+                                .map(|limit| std::sync::Mutex::new(Quota::new(limit))),
+                        },
+                    )
+                })
+                .collect(),
+            rejected: IntCounterVec::new(
+                Opts::new(
+                    "grpc_auth_rejected_total",
+                    "Grpc calls and http requests rejected by api key check",
+                ),
+                &["code"],
+            )
+            .unwrap(),
+        }
+    }
+
+    /// Name of the configured key of `authorization: Bearer <key>` value
+    pub fn key_name(&self, authorization: &str) -> Option<&str> {
+        self.keys
+            .get(bearer_key(authorization)?)
+            .map(|state| state.name.as_str())
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        !self.keys.is_empty()
+    }
+
+    pub fn register(&self, registry: &Registry) -> Result<(), prometheus::Error> {
+        registry.register(Box::new(self.rejected.clone()))
+    }
+
+    fn authorize(&self, authorization: Option<&str>, scope: Scope) -> Result<(), Status> {
+        if !self.is_enabled() {
+            return Ok(());
+        }
+
+        let key = authorization
+            .and_then(bearer_key)
+            .ok_or_else(|| Status::unauthenticated("api key is required"))?;
+        let state = self
+            .keys
+            .get(key)
+            .ok_or_else(|| Status::unauthenticated("unknown api key"))?;
+
+        if !state.scopes.contains(&scope) {
+            tracing::warn!(key = state.name, %scope, "api key without scope");
+            return Err(Status::permission_denied(format!(
+                "api key has no `{scope}` scope"
+            )));
+        }
+
+        if let Some(quota) = &state.quota {
+            if !quota.lock().expect("quota lock poisoned").try_acquire() {
+                tracing::warn!(key = state.name, "api key quota exceeded");
+                return Err(Status::resource_exhausted("api key quota exceeded"));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Check the call has a key with `scope` and within its quota
+    pub fn check<T>(&self, request: &Request<T>, scope: Scope) -> Result<(), Status> {
+        let authorization = request
+            .metadata()
+            .get("authorization")
+            .and_then(|value| value.to_str().ok());
+        self.check_authorization(authorization, scope)
+    }
+
+    /// Check of `authorization` header of plain http requests
+    pub fn check_authorization(
+        &self,
+        authorization: Option<&str>,
+        scope: Scope,
+    ) -> Result<(), Status> {
+        self.authorize(authorization, scope).inspect_err(|status| {
+            self.rejected
+                .with_label_values(&[&format!("{:?}", status.code())])
+                .inc();
+        })
+    }
+
+    /// Interceptor for every method of one grpc service
+    pub fn interceptor(
+        self: &Arc<Self>,
+        scope: Scope,
+    ) -> impl FnMut(Request<()>) -> Result<Request<()>, Status> + Clone {
+        let auth = self.clone();
+        move |request| auth.check(&request, scope).map(|()| request)
+    }
+}
+
+impl Default for Auth {
+    fn default() -> Self {
+        Self::new(Vec::new())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use tonic::Code;
+
+    use super::*;
+
+    fn get_auth() -> Auth {
+        Auth::new(vec![
+            ApiKey {
+                name: "reader".to_owned(),
+                key: "read-key".to_owned(),
+                scopes: vec![Scope::Read],
+                requests_per_minute: Some(2),
+            },
+            ApiKey {
+                name: "operator".to_owned(),
+                key: "admin-key".to_owned(),
+                scopes: vec![Scope::Read, Scope::Admin],
+                requests_per_minute: None,
+            },
+        ])
+    }
+
+    fn get_request(authorization: Option<&str>) -> Request<()> {
+        let mut request = Request::new(());
+        if let Some(authorization) = authorization {
+            request
+                .metadata_mut()
+                .insert("authorization", authorization.parse().unwrap());
+        }
+        request
+    }
+
+    fn check(auth: &Auth, authorization: Option<&str>, scope: Scope) -> Result<(), Code> {
+        auth.check(&get_request(authorization), scope)
+            .map_err(|status| status.code())
+    }
+
+    #[test]
+    fn test_disabled() {
+        assert_eq!(check(&Auth::default(), None, Scope::Admin), Ok(()));
+    }
+
+    #[test]
+    fn test_keys_and_scopes() {
+        let auth = get_auth();
+
+        assert_eq!(check(&auth, None, Scope::Read), Err(Code::Unauthenticated));
+        assert_eq!(
+            check(&auth, Some("read-key"), Scope::Read),
+            Err(Code::Unauthenticated)
+        );
+        assert_eq!(
+            check(&auth, Some("Bearer wrong-key"), Scope::Read),
+            Err(Code::Unauthenticated)
+        );
+        assert_eq!(check(&auth, Some("Bearer read-key"), Scope::Read), Ok(()));
+        assert_eq!(
+            check(&auth, Some("Bearer read-key"), Scope::Admin),
+            Err(Code::PermissionDenied)
+        );
+        assert_eq!(check(&auth, Some("Bearer admin-key"), Scope::Admin), Ok(()));
+        assert_eq!(auth.key_name("Bearer admin-key"), Some("operator"));
+        assert_eq!(auth.key_name("Bearer wrong-key"), None);
+        assert_eq!(
+            auth.rejected.with_label_values(&["Unauthenticated"]).get(),
+            3
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_quota() {
+        let auth = get_auth();
+        let read = || check(&auth, Some("Bearer read-key"), Scope::Read);
+
+        assert_eq!(read(), Ok(()));
+        assert_eq!(read(), Ok(()));
+        assert_eq!(read(), Err(Code::ResourceExhausted));
+
+        // 2 requests per minute are refilled by one per 30 seconds
+        tokio::time::advance(Duration::from_secs(30)).await;
+        assert_eq!(read(), Ok(()));
+        assert_eq!(read(), Err(Code::ResourceExhausted));
+
+        // Unused quota doesn't pile up over the limit
+        tokio::time::advance(Duration::from_secs(3600)).await;
+        assert_eq!(read(), Ok(()));
+        assert_eq!(read(), Ok(()));
+        assert_eq!(read(), Err(Code::ResourceExhausted));
+
+        // Other keys have their own quota
+        for _ in 0..10 {
+            assert_eq!(check(&auth, Some("Bearer admin-key"), Scope::Read), Ok(()));
+        }
+    }
+}
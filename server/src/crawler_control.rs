@@ -0,0 +1,182 @@
+use tokio::sync::{mpsc, watch, Mutex, MutexGuard};
+
+use hackernews_crawler::core::{CrawlProgress, CrawlTarget, CrawlerStatus};
+
+#[derive(thiserror::Error, Debug, PartialEq, Eq)]
+pub enum TriggerError {
+    #[error("crawler is paused, resume it first")]
+    Paused,
+    #[error("{0} crawls are already queued")]
+    QueueFull(usize),
+}
+
+/// State of the crawler shared with the admin api: pause flag, queue of
+/// triggered crawls and progress of the in-flight crawl
+#[derive(Debug)]
+pub struct CrawlerControl {
+    status: watch::Sender<CrawlerStatus>,
+    triggers: mpsc::Sender<CrawlTarget>,
+    queue: Mutex<mpsc::Receiver<CrawlTarget>>,
+    capacity: usize,
+}
+
+impl CrawlerControl {
+    /// `capacity` is the max number of triggered crawls waiting for start
+    pub fn new(capacity: usize) -> Self {
+        let (triggers, queue) = mpsc::channel(capacity);
+        Self {
+            status: watch::channel(CrawlerStatus::default()).0,
+            triggers,
+            queue: Mutex::new(queue),
+            capacity,
+        }
+    }
+
+    pub fn status(&self) -> CrawlerStatus {
+        self.status.borrow().clone()
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.status.borrow().paused
+    }
+
+    pub fn trigger(&self, target: CrawlTarget) -> Result<(), TriggerError> {
+        if self.is_paused() {
+            return Err(TriggerError::Paused);
+        }
+
+        self.triggers
+            .try_send(target)
+            .map_err(|_| TriggerError::QueueFull(self.capacity))?;
+        tracing::info!("crawl is triggered");
+        Ok(())
+    }
+
+    pub fn pause(&self) {
+        self.set_paused(true);
+    }
+
+    pub fn resume(&self) {
+        self.set_paused(false);
+    }
+
+    fn set_paused(&self, paused: bool) {
+        let changed = self
+            .status
+            .send_if_modified(|status| std::mem::replace(&mut status.paused, paused) != paused);
+        if changed {
+            tracing::info!(paused, "crawler pause changed");
+        }
+    }
+
+    /// Resolves when the pause flag becomes `paused`
+    pub async fn wait_paused(&self, paused: bool) {
+        let mut status = self.status.subscribe();
+        while status.borrow_and_update().paused != paused {
+            if status.changed().await.is_err() {
+                return;
+            }
+        }
+    }
+
+    /// Triggered crawls, only the crawler loop takes them
+    pub async fn queue(&self) -> MutexGuard<'_, mpsc::Receiver<CrawlTarget>> {
+        self.queue.lock().await
+    }
+
+    pub fn start_crawl(&self, target: CrawlTarget) {
+        self.status.send_modify(|status| {
+            status.crawl = Some(CrawlProgress { target, page: None });
+        });
+    }
+
+    pub fn set_page(&self, page: usize) {
+        self.status
+            .send_if_modified(|status| match &mut status.crawl {
+                Some(crawl) if crawl.page != Some(page) => {
+                    crawl.page = Some(page);
+                    true
+                }
+                _ => false,
+            });
+    }
+
+    pub fn set_error(&self, error: String) {
+        self.status
+            .send_modify(|status| status.last_error = Some(error));
+    }
+
+    pub fn finish_crawl(&self) {
+        self.status.send_modify(|status| status.crawl = None);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use hackernews_crawler::core::Listing;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_trigger() {
+        let control = CrawlerControl::new(1);
+        let target = CrawlTarget::new(Listing::Ask, 2, 3).unwrap();
+
+        control.trigger(target.clone()).unwrap();
+        assert_eq!(
+            control.trigger(CrawlTarget::default()),
+            Err(TriggerError::QueueFull(1))
+        );
+        assert_eq!(control.queue().await.recv().await, Some(target));
+
+        control.pause();
+        assert_eq!(
+            control.trigger(CrawlTarget::default()),
+            Err(TriggerError::Paused)
+        );
+        control.resume();
+        control.trigger(CrawlTarget::default()).unwrap();
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_wait_paused() {
+        let control = CrawlerControl::new(1);
+
+        assert!(
+            tokio::time::timeout(Duration::from_secs(1), control.wait_paused(true))
+                .await
+                .is_err()
+        );
+
+        tokio::join!(control.wait_paused(true), async { control.pause() });
+        assert!(control.is_paused());
+        control.wait_paused(true).await;
+
+        tokio::join!(control.wait_paused(false), async { control.resume() });
+        assert!(!control.is_paused());
+    }
+
+    #[test]
+    fn test_progress() {
+        let control = CrawlerControl::new(1);
+        let target = CrawlTarget::default();
+
+        control.start_crawl(target.clone());
+        control.set_page(3);
+        assert_eq!(
+            control.status().crawl,
+            Some(CrawlProgress {
+                target,
+                page: Some(3)
+            })
+        );
+
+        control.set_error("database is locked".to_owned());
+        control.finish_crawl();
+        let status = control.status();
+        assert_eq!(status.crawl, None);
+        assert_eq!(status.last_error.as_deref(), Some("database is locked"));
+    }
+}
@@ -0,0 +1,130 @@
+use askama::Template;
+
+use hackernews_crawler::core::{Digest, DigestFormat, Listing};
+
+#[derive(Template)]
+#[template(path = "digest.md")]
+struct MarkdownDigest<'d> {
+    digest: &'d Digest,
+}
+
+#[derive(Template)]
+#[template(path = "digest.html")]
+struct HtmlDigest<'d> {
+    digest: &'d Digest,
+}
+
+fn heading(listing: &Listing) -> &'static str {
+    match listing {
+        Listing::Top => "Front page",
+        Listing::New => "New",
+        Listing::Ask => "Ask HN",
+        Listing::Show => "Show HN",
+        Listing::Jobs => "Jobs",
+    }
+}
+
+impl MarkdownDigest<'_> {
+    fn heading(&self, listing: &Listing) -> &'static str {
+        heading(listing)
+    }
+}
+
+impl HtmlDigest<'_> {
+    fn heading(&self, listing: &Listing) -> &'static str {
+        heading(listing)
+    }
+}
+
+pub fn render(digest: &Digest, format: DigestFormat) -> askama::Result<String> {
+    match format {
+        DigestFormat::Markdown => MarkdownDigest { digest }.render(),
+        DigestFormat::Html => HtmlDigest { digest }.render(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use hackernews_crawler::core::{DigestPost, DigestSection, Post};
+
+    use super::*;
+
+    fn get_digest() -> Digest {
+        let moment = |hour| {
+            chrono::NaiveDate::from_ymd_opt(2023, 1, 1)
+                .unwrap()
+                .and_hms_opt(hour, 0, 0)
+                .unwrap()
+        };
+        let post = |post_id, title: &str, link: &str, best_rank, front_page_minutes| DigestPost {
+            post: Post {
+                post_id,
+                title: title.to_owned(),
+                author: "<pg>".to_owned(),
+                url: format!("https://news.ycombinator.com/item?id={post_id}"),
+                link: Some(link.to_owned()),
+                publication_moment: moment(0),
+                last_snapshot_moment: moment(1),
+                rank: None,
+                listing: None,
+                score: None,
+            },
+            best_rank,
+            front_page_minutes,
+        };
+
+        Digest {
+            since: moment(0),
+            until: moment(12),
+            sections: vec![
+                DigestSection {
+                    listing: Listing::Top,
+                    posts: vec![post(
+                        1,
+                        "Rust &amp; SQLite",
+                        "https://example.com/",
+                        1,
+                        90.4,
+                    )],
+                },
+                DigestSection {
+                    listing: Listing::Ask,
+                    posts: vec![post(2, "Ask HN: Why?", "item?id=2", 3, 0.0)],
+                },
+            ],
+        }
+    }
+
+    #[test]
+    fn test_markdown() {
+        let markdown = render(&get_digest(), DigestFormat::Markdown).unwrap();
+
+        assert!(markdown.contains("2023-01-01 00:00 - 2023-01-01 12:00 UTC"));
+        assert!(markdown.contains("## Front page\n\n1. [Rust &amp; SQLite](https://example.com/) by <pg>, best rank 1, 90 min at the first page\n"));
+        // Own posts link to HN
+        assert!(markdown.contains("## Ask HN\n\n1. [Ask HN: Why?](https://news.ycombinator.com/item?id=2) by <pg>, best rank 3\n"));
+    }
+
+    #[test]
+    fn test_html() {
+        let html = render(&get_digest(), DigestFormat::Html).unwrap();
+
+        assert!(html.contains(r#"<li><a href="https://example.com/">Rust &amp; SQLite</a> by &lt;pg&gt;, best rank 1, 90 min at the first page</li>"#));
+        assert!(html.contains("<h2>Ask HN</h2>"));
+    }
+
+    #[test]
+    fn test_empty() {
+        let digest = Digest {
+            sections: Vec::new(),
+            ..get_digest()
+        };
+
+        assert!(render(&digest, DigestFormat::Markdown)
+            .unwrap()
+            .contains("No crawled posts."));
+        assert!(render(&digest, DigestFormat::Html)
+            .unwrap()
+            .contains("<p>No crawled posts.</p>"));
+    }
+}
@@ -0,0 +1,634 @@
+use std::{
+    fs::File,
+    io::{BufRead, Write},
+    path::Path,
+    sync::Arc,
+};
+
+use arrow_array::{
+    Array, ArrayRef, BooleanArray, Int64Array, RecordBatch, StringArray, TimestampNanosecondArray,
+};
+use arrow_schema::{DataType, Field, Schema, SchemaRef, TimeUnit};
+use futures::StreamExt;
+use parquet::{
+    arrow::{arrow_reader::ParquetRecordBatchReaderBuilder, ArrowWriter},
+    basic::Compression,
+    file::properties::WriterProperties,
+};
+use serde::{Deserialize, Serialize};
+
+use hackernews_crawler::core::{DateTime, Listing, Post, PostId};
+
+use crate::posts_storage::sqlite::{self, SqlitePool};
+
+/// Rows of one parquet record batch
+const BATCH_ROWS: usize = 8192;
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, strum::Display, strum::EnumString)]
+#[strum(serialize_all = "lowercase")]
+pub enum DumpFormat {
+    /// One JSON object per line
+    #[default]
+    Ndjson,
+    /// Columns of parquet, compressed with snappy
+    Parquet,
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("database error: {0}")]
+    Database(#[from] sqlx::Error),
+    #[error("failed to migrate database: {0}")]
+    Migrate(#[from] sqlx::migrate::MigrateError),
+    #[error("io error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("wrong record at line {line}: {source}")]
+    Json {
+        line: usize,
+        source: serde_json::Error,
+    },
+    #[error("parquet error: {0}")]
+    Parquet(#[from] parquet::errors::ParquetError),
+    #[error("arrow error: {0}")]
+    Arrow(#[from] arrow_schema::ArrowError),
+    #[error("wrong parquet column `{0}`, dumps of `export` are expected")]
+    Column(&'static str),
+    #[error("moment `{0}` doesn't fit parquet nanoseconds")]
+    Moment(DateTime),
+}
+
+/// Post with one of its samples, a row of `posts_view`
+#[derive(Debug, Clone, PartialEq, Eq, sqlx::FromRow, Serialize, Deserialize)]
+pub struct Record {
+    pub post_id: PostId,
+    pub title: String,
+    pub author: String,
+    pub url: String,
+    pub link: Option<String>,
+    /// Domain of `link`, for analysis only, imports take it from the link
+    pub domain: Option<String>,
+    #[serde(with = "moment")]
+    pub publication_moment: DateTime,
+    #[serde(with = "moment")]
+    pub snapshot_moment: DateTime,
+    pub was_at_first_page: bool,
+    #[serde(with = "listing")]
+    pub listing: Option<Listing>,
+    pub rank: Option<i64>,
+    pub score: Option<i64>,
+}
+
+impl From<Record> for Post {
+    fn from(record: Record) -> Self {
+        Post {
+            post_id: record.post_id,
+            title: record.title,
+            author: record.author,
+            url: record.url,
+            link: record.link,
+            publication_moment: record.publication_moment,
+            last_snapshot_moment: record.snapshot_moment,
+            rank: record.rank,
+            listing: record.listing,
+            score: record.score,
+        }
+    }
+}
+
+/// Moments are UTC with the fraction of stored ones, e.g. `2023-01-15T08:30:00.25Z`
+mod moment {
+    use serde::{de::Error, Deserialize, Deserializer, Serializer};
+
+    use hackernews_crawler::core::DateTime;
+
+    const FORMAT: &str = "%Y-%m-%dT%H:%M:%S%.fZ";
+
+    pub fn serialize<S: Serializer>(moment: &DateTime, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(&moment.format(FORMAT))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<DateTime, D::Error> {
+        let moment = String::deserialize(deserializer)?;
+        DateTime::parse_from_str(&moment, FORMAT).map_err(D::Error::custom)
+    }
+}
+
+mod listing {
+    use serde::{de::Error, Deserialize, Deserializer, Serializer};
+
+    use hackernews_crawler::core::Listing;
+
+    pub fn serialize<S: Serializer>(
+        listing: &Option<Listing>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        match listing {
+            Some(listing) => serializer.collect_str(listing),
+            None => serializer.serialize_none(),
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Option<Listing>, D::Error> {
+        <Option<String>>::deserialize(deserializer)?
+            .map(|listing| listing.parse().map_err(D::Error::custom))
+            .transpose()
+    }
+}
+
+/// Date like `2023-01-15` or UTC moment like `2023-01-15T08:30:00Z`
+pub fn parse_moment(moment: &str) -> Result<DateTime, String> {
+    DateTime::parse_from_str(moment, "%Y-%m-%dT%H:%M:%S%.fZ")
+        .or_else(|_| {
+            chrono::NaiveDate::parse_from_str(moment, "%Y-%m-%d")
+                .map(|date| date.and_time(chrono::NaiveTime::MIN))
+        })
+        .map_err(|_| {
+            format!("expected e.g. `2023-01-15` or `2023-01-15T08:30:00Z`, not `{moment}`")
+        })
+}
+
+fn schema() -> SchemaRef {
+    let moment = DataType::Timestamp(TimeUnit::Nanosecond, Some("UTC".into()));
+    Arc::new(Schema::new(vec![
+        Field::new("post_id", DataType::Int64, false),
+        Field::new("title", DataType::Utf8, false),
+        Field::new("author", DataType::Utf8, false),
+        Field::new("url", DataType::Utf8, false),
+        Field::new("link", DataType::Utf8, true),
+        Field::new("domain", DataType::Utf8, true),
+        Field::new("publication_moment", moment.clone(), false),
+        Field::new("snapshot_moment", moment, false),
+        Field::new("was_at_first_page", DataType::Boolean, false),
+        Field::new("listing", DataType::Utf8, true),
+        Field::new("rank", DataType::Int64, true),
+        Field::new("score", DataType::Int64, true),
+    ]))
+}
+
+fn nanos(moment: DateTime) -> Result<i64, Error> {
+    moment
+        .and_utc()
+        .timestamp_nanos_opt()
+        .ok_or(Error::Moment(moment))
+}
+
+fn batch(records: &[Record]) -> Result<RecordBatch, Error> {
+    let moments = |moment: fn(&Record) -> DateTime| -> Result<ArrayRef, Error> {
+        let nanos = records
+            .iter()
+            .map(|record| nanos(moment(record)))
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(Arc::new(
+            TimestampNanosecondArray::from(nanos).with_timezone("UTC"),
+        ))
+    };
+    let columns: Vec<ArrayRef> = vec![
+        Arc::new(Int64Array::from_iter_values(
+            records.iter().map(|record| record.post_id),
+        )),
+        Arc::new(StringArray::from_iter_values(
+            records.iter().map(|record| &record.title),
+        )),
+        Arc::new(StringArray::from_iter_values(
+            records.iter().map(|record| &record.author),
+        )),
+        Arc::new(StringArray::from_iter_values(
+            records.iter().map(|record| &record.url),
+        )),
+        Arc::new(StringArray::from_iter(
+            records.iter().map(|record| record.link.as_deref()),
+        )),
+        Arc::new(StringArray::from_iter(
+            records.iter().map(|record| record.domain.as_deref()),
+        )),
+        moments(|record| record.publication_moment)?,
+        moments(|record| record.snapshot_moment)?,
+        Arc::new(BooleanArray::from_iter(
+            records.iter().map(|record| Some(record.was_at_first_page)),
+        )),
+        Arc::new(StringArray::from_iter(
+            records
+                .iter()
+                .map(|record| record.listing.map(|listing| listing.to_string())),
+        )),
+        Arc::new(Int64Array::from_iter(
+            records.iter().map(|record| record.rank),
+        )),
+        Arc::new(Int64Array::from_iter(
+            records.iter().map(|record| record.score),
+        )),
+    ];
+    Ok(RecordBatch::try_new(schema(), columns)?)
+}
+
+fn column<'b, A: Array + 'static>(
+    batch: &'b RecordBatch,
+    name: &'static str,
+) -> Result<&'b A, Error> {
+    batch
+        .column_by_name(name)
+        .and_then(|column| column.as_any().downcast_ref())
+        .ok_or(Error::Column(name))
+}
+
+/// Column, which must not have nulls
+fn required<'b, A: Array + 'static>(
+    batch: &'b RecordBatch,
+    name: &'static str,
+) -> Result<&'b A, Error> {
+    let array = column::<A>(batch, name)?;
+    if array.null_count() > 0 {
+        return Err(Error::Column(name));
+    }
+    Ok(array)
+}
+
+fn text(array: &StringArray, row: usize) -> Option<String> {
+    (!array.is_null(row)).then(|| array.value(row).to_owned())
+}
+
+fn number(array: &Int64Array, row: usize) -> Option<i64> {
+    (!array.is_null(row)).then(|| array.value(row))
+}
+
+fn records(batch: &RecordBatch) -> Result<Vec<Record>, Error> {
+    let post_ids = required::<Int64Array>(batch, "post_id")?;
+    let titles = required::<StringArray>(batch, "title")?;
+    let authors = required::<StringArray>(batch, "author")?;
+    let urls = required::<StringArray>(batch, "url")?;
+    let links = column::<StringArray>(batch, "link")?;
+    let domains = column::<StringArray>(batch, "domain")?;
+    let publication_moments = required::<TimestampNanosecondArray>(batch, "publication_moment")?;
+    let snapshot_moments = required::<TimestampNanosecondArray>(batch, "snapshot_moment")?;
+    let first_page = required::<BooleanArray>(batch, "was_at_first_page")?;
+    let listings = column::<StringArray>(batch, "listing")?;
+    let ranks = column::<Int64Array>(batch, "rank")?;
+    let scores = column::<Int64Array>(batch, "score")?;
+
+    (0..batch.num_rows())
+        .map(|row| {
+            Ok(Record {
+                post_id: post_ids.value(row),
+                title: titles.value(row).to_owned(),
+                author: authors.value(row).to_owned(),
+                url: urls.value(row).to_owned(),
+                link: text(links, row),
+                domain: text(domains, row),
+                publication_moment: publication_moments
+                    .value_as_datetime(row)
+                    .ok_or(Error::Column("publication_moment"))?,
+                snapshot_moment: snapshot_moments
+                    .value_as_datetime(row)
+                    .ok_or(Error::Column("snapshot_moment"))?,
+                was_at_first_page: first_page.value(row),
+                listing: text(listings, row)
+                    .map(|listing| listing.parse())
+                    .transpose()
+                    .map_err(|_| Error::Column("listing"))?,
+                rank: number(ranks, row),
+                score: number(scores, row),
+            })
+        })
+        .collect()
+}
+
+/// Writes records of `since..until` in `format`, the number of records if it's done
+pub async fn export(
+    pool: &SqlitePool,
+    since: Option<DateTime>,
+    until: Option<DateTime>,
+    format: DumpFormat,
+    mut writer: impl Write + Send,
+) -> Result<usize, Error> {
+    let mut records = sqlite::export_records(pool, since, until);
+    let mut exported = 0;
+
+    match format {
+        DumpFormat::Ndjson => {
+            while let Some(record) = records.next().await {
+                serde_json::to_writer(&mut writer, &record?).map_err(std::io::Error::from)?;
+                writeln!(writer)?;
+                exported += 1;
+            }
+            writer.flush()?;
+        }
+        DumpFormat::Parquet => {
+            let properties = WriterProperties::builder()
+                .set_compression(Compression::SNAPPY)
+                .build();
+            let mut parquet = ArrowWriter::try_new(writer, schema(), Some(properties))?;
+            let mut chunks = records.ready_chunks(BATCH_ROWS);
+            while let Some(chunk) = chunks.next().await {
+                let chunk = chunk.into_iter().collect::<Result<Vec<_>, _>>()?;
+                parquet.write(&batch(&chunk)?)?;
+                exported += chunk.len();
+            }
+            parquet.close()?;
+        }
+    }
+    Ok(exported)
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct ImportStats {
+    pub imported: usize,
+    /// Records, which were stored already
+    pub skipped: usize,
+}
+
+impl ImportStats {
+    fn add(&mut self, imported: bool) {
+        if imported {
+            self.imported += 1;
+        } else {
+            self.skipped += 1;
+        }
+    }
+}
+
+/// Imports a dump of [`export`] in one transaction, nothing is stored if
+/// it fails. Records go in the order of the dump, which is the order of
+/// snapshots, so the latest snapshot of a post stays the latest
+pub async fn import(
+    pool: &SqlitePool,
+    format: DumpFormat,
+    path: &Path,
+) -> Result<ImportStats, Error> {
+    let file = File::open(path)?;
+    let mut transaction = pool.begin().await?;
+    let mut stats = ImportStats::default();
+
+    match format {
+        DumpFormat::Ndjson => {
+            for (line, text) in std::io::BufReader::new(file).lines().enumerate() {
+                let text = text?;
+                if text.trim().is_empty() {
+                    continue;
+                }
+                let record = serde_json::from_str(&text).map_err(|source| Error::Json {
+                    line: line + 1,
+                    source,
+                })?;
+                stats.add(sqlite::import_record(&mut transaction, record).await?);
+            }
+        }
+        DumpFormat::Parquet => {
+            for batch in ParquetRecordBatchReaderBuilder::try_new(file)?.build()? {
+                for record in records(&batch?)? {
+                    stats.add(sqlite::import_record(&mut transaction, record).await?);
+                }
+            }
+        }
+    }
+
+    transaction.commit().await?;
+    Ok(stats)
+}
+
+#[cfg(test)]
+mod tests {
+    use hackernews_crawler::core::Listing;
+
+    use crate::posts_storage::InsertPost;
+
+    use super::*;
+
+    fn moment(minute: u32) -> DateTime {
+        chrono::NaiveDate::from_ymd_opt(2023, 1, 15)
+            .unwrap()
+            .and_hms_nano_opt(8, minute, 0, 250_000_000)
+            .unwrap()
+    }
+
+    fn post(post_id: PostId, minute: u32, rank: Option<i64>, score: Option<i64>) -> Post {
+        Post {
+            post_id,
+            title: format!("post {post_id}"),
+            author: "pg".to_owned(),
+            url: format!("https://news.ycombinator.com/item?id={post_id}"),
+            link: (post_id == 1).then(|| "https://www.example.com/".to_owned()),
+            publication_moment: moment(0),
+            last_snapshot_moment: moment(minute),
+            rank,
+            listing: rank.map(|_| Listing::Top),
+            score,
+        }
+    }
+
+    async fn get_storage() -> SqlitePool {
+        let path = std::env::temp_dir().join(format!(
+            "hackernews-crawler-dump-{}.db",
+            rand::random::<u64>()
+        ));
+        let storage = sqlite::connect(&format!("sqlite:{}?mode=rwc", path.display()))
+            .await
+            .unwrap();
+        sqlx::migrate!().run(&storage).await.unwrap();
+        storage
+    }
+
+    async fn get_crawled_storage() -> SqlitePool {
+        let storage = get_storage().await;
+        for (post, is_first_page) in [
+            (post(1, 10, Some(1), Some(50)), true),
+            (post(2, 10, Some(2), Some(10)), true),
+            (post(1, 20, Some(3), Some(70)), true),
+            (post(2, 20, None, Some(12)), false),
+            // Crawled before samples were stored
+            (post(3, 20, None, None), false),
+        ] {
+            storage.insert_post(post, is_first_page).await.unwrap();
+        }
+        storage
+    }
+
+    async fn export_to_vec(storage: &SqlitePool, format: DumpFormat) -> Vec<u8> {
+        let mut dump = Vec::new();
+        export(storage, None, None, format, &mut dump)
+            .await
+            .unwrap();
+        dump
+    }
+
+    async fn dump_file(dump: &[u8]) -> std::path::PathBuf {
+        let path =
+            std::env::temp_dir().join(format!("hackernews-crawler-dump-{}", rand::random::<u64>()));
+        tokio::fs::write(&path, dump).await.unwrap();
+        path
+    }
+
+    #[tokio::test]
+    async fn test_export_ndjson() {
+        let storage = get_crawled_storage().await;
+        let dump = String::from_utf8(export_to_vec(&storage, DumpFormat::Ndjson).await).unwrap();
+        let lines = dump.lines().collect::<Vec<_>>();
+
+        assert_eq!(lines.len(), 5);
+        assert_eq!(
+            lines[0],
+            "{\"post_id\":1,\"title\":\"post 1\",\"author\":\"pg\",\
+             \"url\":\"https://news.ycombinator.com/item?id=1\",\"link\":\"https://www.example.com/\",\
+             \"domain\":\"example.com\",\"publication_moment\":\"2023-01-15T08:00:00.250Z\",\
+             \"snapshot_moment\":\"2023-01-15T08:10:00.250Z\",\"was_at_first_page\":true,\
+             \"listing\":\"top\",\"rank\":1,\"score\":50}"
+        );
+        let records = lines
+            .iter()
+            .map(|line| serde_json::from_str::<Record>(line).unwrap())
+            .map(|record| (record.post_id, record.snapshot_moment, record.rank))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            records,
+            vec![
+                (1, moment(10), Some(1)),
+                (2, moment(10), Some(2)),
+                (1, moment(20), Some(3)),
+                (2, moment(20), None),
+                (3, moment(20), None),
+            ]
+        );
+
+        let mut dump = Vec::new();
+        let exported = export(
+            &storage,
+            Some(moment(15)),
+            Some(moment(30)),
+            DumpFormat::Ndjson,
+            &mut dump,
+        )
+        .await
+        .unwrap();
+        assert_eq!(exported, 3);
+    }
+
+    #[tokio::test]
+    async fn test_import() {
+        let storage = get_crawled_storage().await;
+
+        for format in [DumpFormat::Ndjson, DumpFormat::Parquet] {
+            let dump = export_to_vec(&storage, format).await;
+            let path = dump_file(&dump).await;
+
+            let fresh = get_storage().await;
+            let stats = import(&fresh, format, &path).await.unwrap();
+            assert_eq!(
+                stats,
+                ImportStats {
+                    imported: 5,
+                    skipped: 0
+                },
+                "{format}"
+            );
+            assert_eq!(export_to_vec(&fresh, format).await, dump, "{format}");
+
+            // Repeated import changes nothing
+            let stats = import(&fresh, format, &path).await.unwrap();
+            assert_eq!(
+                stats,
+                ImportStats {
+                    imported: 0,
+                    skipped: 5
+                },
+                "{format}"
+            );
+            assert_eq!(export_to_vec(&fresh, format).await, dump, "{format}");
+
+            let last_snapshot_moments: Vec<(PostId, DateTime)> = sqlx::query_as(
+                r#"SELECT "post_id", "last_snapshot_moment" FROM "posts" ORDER BY "post_id""#,
+            )
+            .fetch_all(&fresh)
+            .await
+            .unwrap();
+            assert_eq!(
+                last_snapshot_moments,
+                vec![(1, moment(20)), (2, moment(20)), (3, moment(20))]
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_import_older_dump() {
+        let storage = get_crawled_storage().await;
+        let mut dump = Vec::new();
+        export(
+            &storage,
+            None,
+            Some(moment(15)),
+            DumpFormat::Ndjson,
+            &mut dump,
+        )
+        .await
+        .unwrap();
+        let path = dump_file(&dump).await;
+
+        let newer = get_storage().await;
+        for (post, is_first_page) in [
+            (post(1, 30, Some(2), Some(90)), true),
+            (post(2, 30, None, Some(15)), false),
+        ] {
+            newer.insert_post(post, is_first_page).await.unwrap();
+        }
+        let stats = import(&newer, DumpFormat::Ndjson, &path).await.unwrap();
+        assert_eq!(
+            stats,
+            ImportStats {
+                imported: 2,
+                skipped: 0
+            }
+        );
+
+        // Samples of the older snapshot are added, posts stay at the newer one
+        let posts: Vec<(PostId, DateTime, Option<i64>)> = sqlx::query_as(
+            r#"SELECT "post_id", "last_snapshot_moment", "score" FROM "posts" ORDER BY "post_id""#,
+        )
+        .fetch_all(&newer)
+        .await
+        .unwrap();
+        assert_eq!(
+            posts,
+            vec![(1, moment(30), Some(90)), (2, moment(30), Some(15))]
+        );
+        let samples: Vec<(PostId, DateTime)> = sqlx::query_as(
+            r#"SELECT "post_id", "snapshot_moment" FROM "post_snapshots" ORDER BY "post_id", "snapshot_moment""#,
+        )
+        .fetch_all(&newer)
+        .await
+        .unwrap();
+        assert_eq!(
+            samples,
+            vec![
+                (1, moment(10)),
+                (1, moment(30)),
+                (2, moment(10)),
+                (2, moment(30))
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_wrong_dump() {
+        let storage = get_storage().await;
+        let path = dump_file(b"{\"post_id\":1}\n").await;
+
+        match import(&storage, DumpFormat::Ndjson, &path).await {
+            Err(Error::Json { line: 1, .. }) => {}
+            result => panic!("unexpected result: {result:?}"),
+        }
+        assert!(import(&storage, DumpFormat::Parquet, &path).await.is_err());
+    }
+
+    #[test]
+    fn test_parse_moment() {
+        assert_eq!(
+            parse_moment("2023-01-15"),
+            Ok(chrono::NaiveDate::from_ymd_opt(2023, 1, 15)
+                .unwrap()
+                .and_hms_opt(0, 0, 0)
+                .unwrap())
+        );
+        assert_eq!(parse_moment("2023-01-15T08:10:00.250Z"), Ok(moment(10)));
+        assert!(parse_moment("yesterday").is_err());
+    }
+}
@@ -0,0 +1,283 @@
+use askama::Template;
+
+use hackernews_crawler::core::{DateTime, Post};
+
+/// Posts of a feed, readers get entries newest first anyway
+pub const FEED_POSTS: usize = 50;
+
+const HACKERNEWS_URL: &str = "https://news.ycombinator.com/";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, strum::Display, strum::EnumString)]
+#[strum(serialize_all = "lowercase")]
+pub enum FeedFormat {
+    Atom,
+    Rss,
+}
+
+impl FeedFormat {
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            FeedFormat::Atom => "application/atom+xml; charset=utf-8",
+            FeedFormat::Rss => "application/rss+xml; charset=utf-8",
+        }
+    }
+}
+
+/// Feed served over http, see [`FeedRequest::parse_path`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FeedRequest {
+    /// Posts of the latest first page snapshot
+    Top,
+    /// The latest posts, which reached the first page
+    FirstPage,
+    /// Posts of one author, optionally only the ones, which were at the first page
+    User {
+        user: String,
+        was_at_first_page: bool,
+    },
+}
+
+impl FeedRequest {
+    /// Feed and its format of `/feeds/...` path: `top`, `first-page`,
+    /// `users/{user}` or `users/{user}/first-page` with `.atom` or `.rss`
+    pub fn parse_path(path: &str) -> Option<(Self, FeedFormat)> {
+        let (name, format) = path.strip_prefix("/feeds/")?.rsplit_once('.')?;
+        let format = format.parse().ok()?;
+
+        let request = match name {
+            "top" => FeedRequest::Top,
+            "first-page" => FeedRequest::FirstPage,
+            name => {
+                let user = name.strip_prefix("users/")?;
+                let (user, was_at_first_page) = match user.strip_suffix("/first-page") {
+                    Some(user) => (user, true),
+                    None => (user, false),
+                };
+                if user.is_empty() || user.contains('/') {
+                    return None;
+                }
+                FeedRequest::User {
+                    user: user.to_owned(),
+                    was_at_first_page,
+                }
+            }
+        };
+        Some((request, format))
+    }
+
+    fn path(&self) -> String {
+        match self {
+            FeedRequest::Top => "top".to_owned(),
+            FeedRequest::FirstPage => "first-page".to_owned(),
+            FeedRequest::User {
+                user,
+                was_at_first_page: false,
+            } => format!("users/{user}"),
+            FeedRequest::User {
+                user,
+                was_at_first_page: true,
+            } => format!("users/{user}/first-page"),
+        }
+    }
+
+    fn title(&self) -> String {
+        match self {
+            FeedRequest::Top => "Hacker News: front page".to_owned(),
+            FeedRequest::FirstPage => "Hacker News: reached the first page".to_owned(),
+            FeedRequest::User {
+                user,
+                was_at_first_page: false,
+            } => format!("Hacker News: posts of {user}"),
+            FeedRequest::User {
+                user,
+                was_at_first_page: true,
+            } => format!("Hacker News: posts of {user} at the first page"),
+        }
+    }
+
+    /// HN page with the same posts
+    fn link(&self) -> String {
+        match self {
+            FeedRequest::Top | FeedRequest::FirstPage => HACKERNEWS_URL.to_owned(),
+            FeedRequest::User { user, .. } => format!("{HACKERNEWS_URL}submitted?id={user}"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Feed {
+    /// Stable id of the feed itself
+    pub id: String,
+    pub title: String,
+    pub link: String,
+    /// The latest snapshot of the posts, `now` without posts
+    pub updated: DateTime,
+    pub posts: Vec<Post>,
+}
+
+impl Feed {
+    pub fn new(request: &FeedRequest, posts: Vec<Post>, now: DateTime) -> Self {
+        Self {
+            id: format!("tag:hackernews-crawler,2023:feeds/{}", request.path()),
+            title: request.title(),
+            link: request.link(),
+            updated: posts
+                .iter()
+                .map(|post| post.last_snapshot_moment)
+                .max()
+                .unwrap_or(now),
+            posts,
+        }
+    }
+}
+
+/// Id of an entry, it depends on the post only, so readers don't show
+/// a post twice, when its title or link is edited
+fn guid(post: &Post) -> String {
+    format!("tag:news.ycombinator.com,2007:item-{}", post.post_id)
+}
+
+fn comments(post: &Post) -> String {
+    format!("{HACKERNEWS_URL}item?id={}", post.post_id)
+}
+
+#[derive(Template)]
+#[template(path = "atom.xml")]
+struct AtomFeed<'f> {
+    feed: &'f Feed,
+}
+
+#[derive(Template)]
+#[template(path = "rss.xml")]
+struct RssFeed<'f> {
+    feed: &'f Feed,
+}
+
+impl AtomFeed<'_> {
+    fn guid(&self, post: &Post) -> String {
+        guid(post)
+    }
+
+    fn comments(&self, post: &Post) -> String {
+        comments(post)
+    }
+}
+
+impl RssFeed<'_> {
+    fn guid(&self, post: &Post) -> String {
+        guid(post)
+    }
+
+    fn comments(&self, post: &Post) -> String {
+        comments(post)
+    }
+}
+
+pub fn render(feed: &Feed, format: FeedFormat) -> askama::Result<String> {
+    match format {
+        FeedFormat::Atom => AtomFeed { feed }.render(),
+        FeedFormat::Rss => RssFeed { feed }.render(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn moment(hour: u32) -> DateTime {
+        chrono::NaiveDate::from_ymd_opt(2023, 1, 15)
+            .unwrap()
+            .and_hms_opt(hour, 30, 0)
+            .unwrap()
+    }
+
+    fn get_feed() -> Feed {
+        let post = Post {
+            post_id: 34388962,
+            title: "Rust &amp; SQLite".to_owned(),
+            author: "pg".to_owned(),
+            url: "https://news.ycombinator.com/item?id=34388962".to_owned(),
+            link: Some("https://example.com/?a=1&b=2".to_owned()),
+            publication_moment: moment(8),
+            last_snapshot_moment: moment(12),
+            rank: None,
+            listing: None,
+            score: None,
+        };
+        Feed::new(&FeedRequest::Top, vec![post], moment(13))
+    }
+
+    #[test]
+    fn test_parse_path() {
+        assert_eq!(
+            FeedRequest::parse_path("/feeds/top.atom"),
+            Some((FeedRequest::Top, FeedFormat::Atom))
+        );
+        assert_eq!(
+            FeedRequest::parse_path("/feeds/first-page.rss"),
+            Some((FeedRequest::FirstPage, FeedFormat::Rss))
+        );
+        assert_eq!(
+            FeedRequest::parse_path("/feeds/users/pg.atom"),
+            Some((
+                FeedRequest::User {
+                    user: "pg".to_owned(),
+                    was_at_first_page: false
+                },
+                FeedFormat::Atom
+            ))
+        );
+        assert_eq!(
+            FeedRequest::parse_path("/feeds/users/pg/first-page.rss"),
+            Some((
+                FeedRequest::User {
+                    user: "pg".to_owned(),
+                    was_at_first_page: true
+                },
+                FeedFormat::Rss
+            ))
+        );
+        for path in [
+            "/feeds/top",
+            "/feeds/top.json",
+            "/feeds/users/.atom",
+            "/feeds/users/a/b.atom",
+            "/top.atom",
+        ] {
+            assert_eq!(FeedRequest::parse_path(path), None, "{path}");
+        }
+    }
+
+    #[test]
+    fn test_atom() {
+        let atom = render(&get_feed(), FeedFormat::Atom).unwrap();
+
+        assert!(atom.contains("<id>tag:hackernews-crawler,2023:feeds/top</id>"));
+        assert!(atom.contains("<updated>2023-01-15T12:30:00Z</updated>"));
+        assert!(atom.contains("<id>tag:news.ycombinator.com,2007:item-34388962</id>"));
+        assert!(atom.contains(r#"<title type="html">Rust &amp;amp; SQLite</title>"#));
+        assert!(atom.contains(r#"<link rel="alternate" href="https://example.com/?a=1&amp;b=2"/>"#));
+        assert!(atom.contains("<published>2023-01-15T08:30:00Z</published>"));
+    }
+
+    #[test]
+    fn test_rss() {
+        let rss = render(&get_feed(), FeedFormat::Rss).unwrap();
+
+        assert!(rss.contains(
+            r#"<guid isPermaLink="false">tag:news.ycombinator.com,2007:item-34388962</guid>"#
+        ));
+        assert!(rss.contains("<pubDate>Sun, 15 Jan 2023 08:30:00 +0000</pubDate>"));
+        assert!(rss.contains("<dc:creator>pg</dc:creator>"));
+        assert!(rss.contains("<comments>https://news.ycombinator.com/item?id=34388962</comments>"));
+    }
+
+    #[test]
+    fn test_empty() {
+        let feed = Feed::new(&FeedRequest::FirstPage, Vec::new(), moment(13));
+
+        assert_eq!(feed.updated, moment(13));
+        assert!(!render(&feed, FeedFormat::Atom).unwrap().contains("<entry>"));
+        assert!(!render(&feed, FeedFormat::Rss).unwrap().contains("<item>"));
+    }
+}
@@ -0,0 +1,457 @@
+use std::{sync::Mutex, time::Duration};
+
+use prometheus::{Histogram, HistogramOpts, IntCounter, Registry};
+use reqwest::{
+    header::{HeaderMap, RETRY_AFTER},
+    StatusCode, Url,
+};
+use tokio::time::Instant;
+
+pub use hackernews_crawler::retry::RetryPolicy;
+
+#[derive(thiserror::Error, Debug)]
+pub enum FetchError {
+    #[error(transparent)]
+    Http(#[from] reqwest::Error),
+    #[error("unexpected response status {0}")]
+    Status(StatusCode),
+    #[error("circuit breaker is open after {0} consecutive trips")]
+    CircuitOpen(u32),
+}
+
+#[derive(Debug, Default)]
+struct BreakerState {
+    open_until: Option<Instant>,
+    trips: u32,
+}
+
+/// Pauses all fetches when HN tells us to slow down (503, 429 or rate-limit page)
+///
+/// Every consecutive trip doubles the pause. After `max_trips` consecutive
+/// trips without a successful fetch, the breaker stays open and fetches fail
+/// fast until [`CircuitBreaker::reset`]
+#[derive(Debug)]
+pub struct CircuitBreaker {
+    cooldown: Duration,
+    max_trips: u32,
+    state: Mutex<BreakerState>,
+}
+
+impl Default for CircuitBreaker {
+    fn default() -> Self {
+        Self::new(Duration::from_secs(60), 5)
+    }
+}
+
+impl CircuitBreaker {
+    pub fn new(cooldown: Duration, max_trips: u32) -> Self {
+        Self {
+            cooldown,
+            max_trips,
+            state: Mutex::default(),
+        }
+    }
+
+    pub fn trip(&self, retry_after: Option<Duration>) {
+        let mut state = self.state.lock().unwrap();
+        let pause = self
+            .cooldown
+            .saturating_mul(2u32.saturating_pow(state.trips))
+            .max(retry_after.unwrap_or_default());
+        state.trips = state.trips.saturating_add(1);
+        state.open_until = Some(Instant::now() + pause);
+        tracing::warn!(
+            "circuit breaker tripped {trips} time(s), pause crawling for {pause:?}",
+            trips = state.trips
+        );
+    }
+
+    pub fn reset(&self) {
+        *self.state.lock().unwrap() = BreakerState::default();
+    }
+
+    pub fn is_exhausted(&self) -> bool {
+        self.state.lock().unwrap().trips >= self.max_trips
+    }
+
+    /// Wait until the breaker closes or fail if it will not close anymore
+    pub async fn wait(&self) -> Result<(), FetchError> {
+        let open_until = {
+            let state = self.state.lock().unwrap();
+            if state.trips >= self.max_trips {
+                return Err(FetchError::CircuitOpen(state.trips));
+            }
+            state.open_until
+        };
+
+        if let Some(open_until) = open_until {
+            tokio::time::sleep_until(open_until).await;
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct FetchStats {
+    pub requests: IntCounter,
+    pub retries: IntCounter,
+    pub failures: IntCounter,
+    pub breaker_trips: IntCounter,
+    pub fetch_duration: Histogram,
+}
+
+impl Default for FetchStats {
+    fn default() -> Self {
+        Self {
+            requests: IntCounter::new("fetch_requests_total", "HTTP requests to HN").unwrap(),
+            retries: IntCounter::new("fetch_retries_total", "Retried HTTP requests to HN").unwrap(),
+            failures: IntCounter::new(
+                "fetch_failures_total",
+                "HTTP requests to HN failed after all retries",
+            )
+            .unwrap(),
+            breaker_trips: IntCounter::new(
+                "fetch_circuit_breaker_trips_total",
+                "Circuit breaker trips by 503, 429 or rate-limit page",
+            )
+            .unwrap(),
+            fetch_duration: Histogram::with_opts(HistogramOpts::new(
+                "fetch_duration_seconds",
+                "Duration of one HTTP request to HN",
+            ))
+            .unwrap(),
+        }
+    }
+}
+
+impl FetchStats {
+    pub fn register(&self, registry: &Registry) -> Result<(), prometheus::Error> {
+        registry.register(Box::new(self.requests.clone()))?;
+        registry.register(Box::new(self.retries.clone()))?;
+        registry.register(Box::new(self.failures.clone()))?;
+        registry.register(Box::new(self.breaker_trips.clone()))?;
+        registry.register(Box::new(self.fetch_duration.clone()))
+    }
+}
+
+/// HTTP client for HN with pacing between requests, retries with jittered
+/// exponential backoff honoring `Retry-After`, and a [`CircuitBreaker`]
+#[derive(Debug)]
+pub struct Fetcher {
+    client: reqwest::Client,
+    request_delay: Duration,
+    policy: RetryPolicy,
+    breaker: CircuitBreaker,
+    next_request: Mutex<Option<Instant>>,
+    stats: FetchStats,
+}
+
+impl Default for Fetcher {
+    fn default() -> Self {
+        Self::new(
+            Duration::from_millis(1500),
+            RetryPolicy {
+                max_delay: Duration::from_secs(30),
+                ..RetryPolicy::default()
+            },
+            CircuitBreaker::default(),
+        )
+    }
+}
+
+fn parse_retry_after(headers: &HeaderMap) -> Option<Duration> {
+    let value = headers.get(RETRY_AFTER)?.to_str().ok()?.trim();
+
+    match value.parse::<u64>() {
+        Ok(secs) => Some(Duration::from_secs(secs)),
+        Err(_) => chrono::DateTime::parse_from_rfc2822(value)
+            .ok()?
+            .signed_duration_since(chrono::Utc::now())
+            .to_std()
+            .ok(),
+    }
+}
+
+fn is_retryable(status: StatusCode) -> bool {
+    status == StatusCode::TOO_MANY_REQUESTS || status.is_server_error()
+}
+
+impl Fetcher {
+    pub fn new(request_delay: Duration, policy: RetryPolicy, breaker: CircuitBreaker) -> Self {
+        Self {
+            client: reqwest::Client::new(),
+            request_delay,
+            policy,
+            breaker,
+            next_request: Mutex::default(),
+            stats: FetchStats::default(),
+        }
+    }
+
+    pub fn breaker(&self) -> &CircuitBreaker {
+        &self.breaker
+    }
+
+    pub fn stats(&self) -> &FetchStats {
+        &self.stats
+    }
+
+    /// Reserve the next free slot, so the requests are `request_delay` apart
+    async fn pace(&self) {
+        let slot = {
+            let mut next_request = self.next_request.lock().unwrap();
+            let slot = next_request
+                .map(|next| next.max(Instant::now()))
+                .unwrap_or_else(Instant::now);
+            *next_request = Some(slot + self.request_delay);
+            slot
+        };
+
+        tokio::time::sleep_until(slot).await;
+    }
+
+    /// Fetches the url, retrying failures and tripping the breaker on throttling.
+    /// A successful response doesn't reset the breaker: the caller does it once
+    /// the body is known not to be a rate limit page.
+    pub async fn fetch(&self, url: Url) -> Result<reqwest::Response, FetchError> {
+        let mut retry = 0;
+
+        loop {
+            self.breaker.wait().await?;
+            self.pace().await;
+            self.stats.requests.inc();
+
+            let timer = self.stats.fetch_duration.start_timer();
+            let response = self.client.get(url.clone()).send().await;
+            timer.observe_duration();
+
+            let (err, delay) = match response {
+                Ok(response) if response.status().is_success() => return Ok(response),
+                Ok(response) => {
+                    let status = response.status();
+                    let retry_after = parse_retry_after(response.headers());
+
+                    if !is_retryable(status) {
+                        self.stats.failures.inc();
+                        return Err(FetchError::Status(status));
+                    }
+
+                    if matches!(
+                        status,
+                        StatusCode::TOO_MANY_REQUESTS | StatusCode::SERVICE_UNAVAILABLE
+                    ) {
+                        self.stats.breaker_trips.inc();
+                        self.breaker.trip(retry_after);
+                        // The breaker itself will hold us back before the next attempt
+                        (FetchError::Status(status), None)
+                    } else {
+                        (
+                            FetchError::Status(status),
+                            Some(retry_after.unwrap_or_else(|| self.policy.backoff(retry))),
+                        )
+                    }
+                }
+                Err(err) => (err.into(), Some(self.policy.backoff(retry))),
+            };
+
+            if retry >= self.policy.max_retries {
+                self.stats.failures.inc();
+                return Err(err);
+            }
+
+            retry += 1;
+            self.stats.retries.inc();
+            tracing::warn!(%url, %err, retry, ?delay, "failed to fetch");
+
+            if let Some(delay) = delay {
+                tokio::time::sleep(delay).await;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use std::{
+        collections::VecDeque,
+        sync::{Arc, Mutex},
+    };
+
+    use tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::TcpListener,
+    };
+
+    use super::*;
+
+    /// Serve the scripted responses one per connection, the last one repeats forever
+    pub(crate) async fn scripted_server(responses: Vec<String>) -> Url {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let responses = Arc::new(Mutex::new(VecDeque::from(responses)));
+
+        tokio::spawn(async move {
+            while let Ok((mut socket, _)) = listener.accept().await {
+                let response = {
+                    let mut responses = responses.lock().unwrap();
+                    if responses.len() > 1 {
+                        responses.pop_front()
+                    } else {
+                        responses.front().cloned()
+                    }
+                    .expect("at least one scripted response")
+                };
+
+                tokio::spawn(async move {
+                    let mut buf = vec![0; 4096];
+                    let _ = socket.read(&mut buf).await;
+                    let _ = socket.write_all(response.as_bytes()).await;
+                    let _ = socket.shutdown().await;
+                });
+            }
+        });
+
+        format!("http://{addr}/").parse().unwrap()
+    }
+
+    pub(crate) fn http_response(status: &str, headers: &[&str], body: &str) -> String {
+        let mut response = format!("HTTP/1.1 {status}\r\nConnection: close\r\n");
+        for header in headers {
+            response.push_str(header);
+            response.push_str("\r\n");
+        }
+        response.push_str(&format!("Content-Length: {}\r\n\r\n{body}", body.len()));
+        response
+    }
+
+    fn get_fetcher(max_retries: u32) -> Fetcher {
+        Fetcher::new(
+            Duration::ZERO,
+            RetryPolicy {
+                max_retries,
+                base_delay: Duration::from_millis(1),
+                max_delay: Duration::from_millis(10),
+            },
+            CircuitBreaker::new(Duration::from_millis(1), 3),
+        )
+    }
+
+    #[tokio::test]
+    async fn test_retry_until_success() {
+        let url = scripted_server(vec![
+            http_response("500 Internal Server Error", &[], ""),
+            http_response("502 Bad Gateway", &[], ""),
+            http_response("200 OK", &[], "ok"),
+        ])
+        .await;
+
+        let fetcher = get_fetcher(3);
+        let response = fetcher.fetch(url).await.unwrap();
+
+        assert_eq!(response.text().await.unwrap(), "ok");
+        assert_eq!(fetcher.stats().retries.get(), 2);
+        assert_eq!(fetcher.stats().requests.get(), 3);
+        assert_eq!(fetcher.stats().failures.get(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_give_up_after_max_retries() {
+        let url = scripted_server(vec![http_response("500 Internal Server Error", &[], "")]).await;
+
+        let fetcher = get_fetcher(2);
+        assert!(matches!(
+            fetcher.fetch(url).await,
+            Err(FetchError::Status(StatusCode::INTERNAL_SERVER_ERROR))
+        ));
+        assert_eq!(fetcher.stats().requests.get(), 3);
+        assert_eq!(fetcher.stats().failures.get(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_not_retry_client_error() {
+        let url = scripted_server(vec![http_response("404 Not Found", &[], "")]).await;
+
+        let fetcher = get_fetcher(3);
+        assert!(matches!(
+            fetcher.fetch(url).await,
+            Err(FetchError::Status(StatusCode::NOT_FOUND))
+        ));
+        assert_eq!(fetcher.stats().requests.get(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_honor_retry_after() {
+        let url = scripted_server(vec![
+            http_response("429 Too Many Requests", &["Retry-After: 1"], ""),
+            http_response("200 OK", &[], "ok"),
+        ])
+        .await;
+
+        let fetcher = get_fetcher(1);
+        let started = Instant::now();
+        fetcher.fetch(url).await.unwrap();
+
+        assert!(started.elapsed() >= Duration::from_secs(1));
+        assert_eq!(fetcher.stats().breaker_trips.get(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_circuit_breaker_opens_on_service_unavailable() {
+        let url = scripted_server(vec![http_response("503 Service Unavailable", &[], "")]).await;
+
+        let fetcher = get_fetcher(10);
+        assert!(matches!(
+            fetcher.fetch(url).await,
+            Err(FetchError::CircuitOpen(3))
+        ));
+        assert!(fetcher.breaker().is_exhausted());
+        assert_eq!(fetcher.stats().requests.get(), 3);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_circuit_breaker_pause() {
+        let breaker = CircuitBreaker::new(Duration::from_secs(10), 3);
+
+        let started = Instant::now();
+        breaker.wait().await.unwrap();
+        assert_eq!(started.elapsed(), Duration::ZERO);
+
+        breaker.trip(None);
+        breaker.wait().await.unwrap();
+        assert_eq!(started.elapsed(), Duration::from_secs(10));
+
+        breaker.trip(Some(Duration::from_secs(5)));
+        breaker.wait().await.unwrap();
+        assert_eq!(started.elapsed(), Duration::from_secs(30));
+
+        breaker.trip(None);
+        assert!(matches!(
+            breaker.wait().await,
+            Err(FetchError::CircuitOpen(3))
+        ));
+
+        breaker.reset();
+        breaker.wait().await.unwrap();
+    }
+
+    #[test]
+    fn test_parse_retry_after() {
+        let mut headers = HeaderMap::new();
+        assert_eq!(parse_retry_after(&headers), None);
+
+        headers.insert(RETRY_AFTER, "120".parse().unwrap());
+        assert_eq!(parse_retry_after(&headers), Some(Duration::from_secs(120)));
+
+        headers.insert(
+            RETRY_AFTER,
+            "Wed, 21 Oct 2015 07:28:00 GMT".parse().unwrap(),
+        );
+        assert_eq!(parse_retry_after(&headers), None, "date in the past");
+
+        let future = (chrono::Utc::now() + chrono::Duration::seconds(100)).to_rfc2822();
+        headers.insert(RETRY_AFTER, future.parse().unwrap());
+        let delay = parse_retry_after(&headers).unwrap();
+        assert!(delay > Duration::from_secs(90) && delay <= Duration::from_secs(100));
+    }
+}
@@ -0,0 +1,627 @@
+use std::{
+    num::NonZeroUsize,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+};
+
+use anyhow::Result;
+use prometheus::{IntCounter, Registry};
+use reqwest::Url;
+use tracing::{Instrument, Span};
+use voyager::{scraper::Selector, Collector, Crawler, CrawlerConfig, Response, Scraper};
+
+use crate::fetcher::Fetcher;
+use hackernews_crawler::hackernews_core::{CrawlTarget, DateTime, Listing, Post as Entry, PostId};
+
+/// Body of the page HN serves with `200 OK` instead of `429` when we are too fast
+pub(crate) const RATE_LIMIT_MARKER: &str =
+    "Sorry, we're not able to serve your requests this quickly.";
+
+#[derive(Debug)]
+pub enum HackernewsState {
+    Page {
+        snapshot_time: DateTime,
+        page: usize,
+    },
+    Post {
+        snapshot_time: DateTime,
+        post_id: PostId,
+        page: usize,
+        /// Rank at the crawled listing
+        rank: Option<i64>,
+    },
+}
+
+/// Set once a crawl misses pages, e.g. when the circuit breaker gives up,
+/// so its snapshot is partial
+#[derive(Debug, Clone, Default)]
+pub struct CutShort(Arc<AtomicBool>);
+
+impl CutShort {
+    pub fn set(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_set(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ScrapeStats {
+    pub pages: IntCounter,
+    pub items: IntCounter,
+    pub parse_failures: IntCounter,
+}
+
+impl Default for ScrapeStats {
+    fn default() -> Self {
+        Self {
+            pages: IntCounter::new("scrape_pages_total", "Scraped HN listing pages").unwrap(),
+            items: IntCounter::new("scrape_items_total", "Scraped HN item pages").unwrap(),
+            parse_failures: IntCounter::new(
+                "scrape_parse_failures_total",
+                "HN pages with unexpected html",
+            )
+            .unwrap(),
+        }
+    }
+}
+
+impl ScrapeStats {
+    pub fn register(&self, registry: &Registry) -> Result<(), prometheus::Error> {
+        registry.register(Box::new(self.pages.clone()))?;
+        registry.register(Box::new(self.items.clone()))?;
+        registry.register(Box::new(self.parse_failures.clone()))
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct HackernewsScraper {
+    post_selector: Selector,
+    rank_selector: Selector,
+    score_selector: Selector,
+    author_selector: Selector,
+    publication_moment_selector: Selector,
+    title_selector: Selector,
+    max_page: NonZeroUsize,
+    listing: Listing,
+    base_url: Url,
+    fetcher: Arc<Fetcher>,
+    stats: ScrapeStats,
+    cut_short: CutShort,
+}
+
+impl Default for HackernewsScraper {
+    fn default() -> Self {
+        Self {
+            post_selector: Selector::parse("#hnmain tr.athing").unwrap(),
+            rank_selector: Selector::parse("span.rank").unwrap(),
+            score_selector: Selector::parse("span.score").unwrap(),
+            author_selector: Selector::parse("a.hnuser").unwrap(),
+            publication_moment_selector: Selector::parse("span.age").unwrap(),
+            title_selector: Selector::parse("td.title a").unwrap(),
+            max_page: NonZeroUsize::new(CrawlTarget::DEFAULT_LAST_PAGE).unwrap(),
+            listing: Listing::default(),
+            base_url: "https://news.ycombinator.com/".parse().unwrap(),
+            fetcher: Arc::default(),
+            stats: ScrapeStats::default(),
+            cut_short: CutShort::default(),
+        }
+    }
+}
+
+impl HackernewsScraper {
+    pub fn new(base_url: Url, fetcher: Arc<Fetcher>) -> Self {
+        Self {
+            base_url,
+            fetcher,
+            ..Default::default()
+        }
+    }
+
+    pub fn fetcher(&self) -> &Fetcher {
+        &self.fetcher
+    }
+
+    pub fn stats(&self) -> &ScrapeStats {
+        &self.stats
+    }
+
+    /// Collector of one crawl and its flag, which is set if the crawl is cut short
+    pub fn new_collector(
+        &self,
+        snapshot_time: DateTime,
+        target: &CrawlTarget,
+    ) -> (Collector<Self>, CutShort) {
+        let cut_short = CutShort::default();
+        let scraper = Self {
+            max_page: target.last_page,
+            listing: target.listing,
+            cut_short: cut_short.clone(),
+            ..self.clone()
+        };
+        let mut collector = Collector::new(scraper, CrawlerConfig::default());
+
+        FetchingCrawler {
+            crawler: collector.crawler_mut(),
+            base_url: self.base_url.clone(),
+            listing: target.listing,
+            fetcher: self.fetcher.clone(),
+        }
+        .visit_page(target.first_page.get(), snapshot_time);
+
+        (collector, cut_short)
+    }
+}
+
+pub trait HackernewsCrawler {
+    fn visit_page(&mut self, page: usize, snapshot_time: DateTime);
+    fn visit_post(
+        &mut self,
+        post_id: PostId,
+        page: usize,
+        rank: Option<i64>,
+        snapshot_time: DateTime,
+    );
+}
+
+/// [`Crawler`] which performs all requests through [`Fetcher`] instead of
+/// voyager's own client, to get retries and the circuit breaker
+struct FetchingCrawler<'c> {
+    crawler: &'c mut Crawler<HackernewsScraper>,
+    base_url: Url,
+    listing: Listing,
+    fetcher: Arc<Fetcher>,
+}
+
+impl FetchingCrawler<'_> {
+    fn visit(&mut self, path: &str, state: HackernewsState, span: Span) {
+        let url = match self.base_url.join(path) {
+            Ok(url) => url,
+            Err(err) => {
+                tracing::error!(path, %err, "can't build url");
+                return;
+            }
+        };
+        let fetcher = self.fetcher.clone();
+
+        self.crawler.crawl(move |_client| {
+            async move {
+                let response = fetcher.fetch(url).await?;
+                Ok((response, Some(state)))
+            }
+            .instrument(span)
+        });
+    }
+}
+
+impl HackernewsCrawler for FetchingCrawler<'_> {
+    fn visit_page(&mut self, page: usize, snapshot_time: DateTime) {
+        let path = match page {
+            1 => self.listing.path().to_owned(),
+            page => format!("{}?p={page}", self.listing.path()),
+        };
+        self.visit(
+            &path,
+            HackernewsState::Page {
+                page,
+                snapshot_time,
+            },
+            tracing::info_span!("fetch_page", listing = %self.listing, page),
+        );
+    }
+
+    fn visit_post(
+        &mut self,
+        post_id: PostId,
+        page: usize,
+        rank: Option<i64>,
+        snapshot_time: DateTime,
+    ) {
+        self.visit(
+            &format!("item?id={post_id}"),
+            HackernewsState::Post {
+                post_id,
+                page,
+                rank,
+                snapshot_time,
+            },
+            tracing::info_span!("fetch_item", post_id, page),
+        )
+    }
+}
+
+impl HackernewsScraper {
+    // This function is implemented to be able to replace the crawler to trait object
+    fn scrape_internal(
+        &mut self,
+        response: Response<HackernewsState>,
+        crawler: &mut impl HackernewsCrawler,
+    ) -> Result<Option<(usize, Entry)>> {
+        if response.text.contains(RATE_LIMIT_MARKER) {
+            tracing::warn!(url = %response.response_url, "rate limited");
+            self.fetcher.stats().breaker_trips.inc();
+            self.fetcher.breaker().trip(None);
+            // Responses in flight may reset the breaker before the crawl is over
+            if self.fetcher.breaker().is_exhausted() {
+                self.cut_short.set();
+            }
+
+            // Revisit the same url, the fetcher will wait for the breaker
+            match response.state {
+                Some(HackernewsState::Page {
+                    page,
+                    snapshot_time,
+                }) => crawler.visit_page(page, snapshot_time),
+                Some(HackernewsState::Post {
+                    post_id,
+                    page,
+                    rank,
+                    snapshot_time,
+                }) => crawler.visit_post(post_id, page, rank, snapshot_time),
+                None => {}
+            }
+            return Ok(None);
+        }
+        self.fetcher.breaker().reset();
+
+        let html = response.html();
+
+        Ok(match response.state {
+            Some(HackernewsState::Page {
+                page,
+                snapshot_time,
+            }) => {
+                tracing::info!(listing = %self.listing, page, %snapshot_time, "start visit page");
+                self.stats.pages.inc();
+                for el in html.select(&self.post_selector) {
+                    let Some(id) = el.value().attr("id") else {
+                        continue;
+                    };
+                    let rank = el
+                        .select(&self.rank_selector)
+                        .next()
+                        .and_then(|el| el.inner_html().trim().trim_end_matches('.').parse().ok());
+                    tracing::info!(post_id = id, page, rank, "let's visit post");
+                    match id.parse() {
+                        Ok(post_id) => crawler.visit_post(post_id, page, rank, snapshot_time),
+                        Err(err) => {
+                            self.stats.parse_failures.inc();
+                            tracing::error!(post_id = id, page, %err, "ignore post with wrong id");
+                        }
+                    }
+                }
+
+                if page < self.max_page.get() {
+                    tracing::info!(page = page + 1, "let's visit page");
+                    crawler.visit_page(page + 1, snapshot_time);
+                } else {
+                    tracing::info!(page, "scrapping ended");
+                }
+                None
+            }
+            Some(HackernewsState::Post {
+                post_id,
+                page,
+                rank,
+                snapshot_time,
+            }) => {
+                tracing::info!(post_id, page, %snapshot_time, "visited post");
+                self.stats.items.inc();
+                let el_title = match html.select(&self.title_selector).next() {
+                    Some(el_title) => el_title,
+                    None => {
+                        self.stats.parse_failures.inc();
+                        tracing::error!(post_id, "ignore post because can't parse title");
+                        return Ok(None);
+                    }
+                };
+                let author = match html
+                    .select(&self.author_selector)
+                    .map(|el| el.inner_html())
+                    .next()
+                {
+                    Some(author) => author,
+                    None => {
+                        self.stats.parse_failures.inc();
+                        tracing::warn!(post_id, "can't parse author");
+                        "unknown".to_owned()
+                    }
+                };
+
+                let publication_moment = match html
+                    .select(&self.publication_moment_selector)
+                    .map(|el| el.value().attr("title"))
+                    .next()
+                    .flatten()
+                    .and_then(|publication_moment| {
+                        tracing::debug!(post_id, publication_moment, "raw publication moment");
+                        DateTime::parse_from_str(publication_moment.trim(), "%Y-%m-%dT%H:%M:%S")
+                            .ok()
+                    }) {
+                    Some(moment) => moment,
+                    None => {
+                        self.stats.parse_failures.inc();
+                        tracing::error!(
+                            post_id,
+                            "ignore post because can't parse publication moment"
+                        );
+                        return Ok(None);
+                    }
+                };
+
+                // Job posts have no points
+                let score = html
+                    .select(&self.score_selector)
+                    .next()
+                    .and_then(|el| el.inner_html().split_whitespace().next()?.parse().ok());
+
+                Some((
+                    page,
+                    Entry {
+                        post_id,
+                        author,
+                        url: response.response_url.to_string(),
+                        link: el_title.value().attr("href").map(str::to_string),
+                        title: el_title.inner_html(),
+                        publication_moment,
+                        last_snapshot_moment: snapshot_time,
+                        rank,
+                        listing: Some(self.listing),
+                        score,
+                    },
+                ))
+            }
+            None => None,
+        })
+    }
+}
+
+impl Scraper for HackernewsScraper {
+    type Output = (usize, Entry);
+    type State = HackernewsState;
+
+    fn scrape(
+        &mut self,
+        response: Response<Self::State>,
+        crawler: &mut Crawler<Self>,
+    ) -> Result<Option<Self::Output>> {
+        let mut crawler = FetchingCrawler {
+            crawler,
+            base_url: self.base_url.clone(),
+            listing: self.listing,
+            fetcher: self.fetcher.clone(),
+        };
+        self.scrape_internal(response, &mut crawler)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use reqwest::{header::HeaderMap, StatusCode};
+
+    use super::*;
+    struct CrawlerMock {
+        expected_visits: Vec<HackernewsState>,
+    }
+    impl HackernewsCrawler for CrawlerMock {
+        fn visit_page(&mut self, expected_page: usize, expected_snapshot_time: DateTime) {
+            match self.expected_visits.pop().expect("visit not exptected") {
+                HackernewsState::Page {
+                    page,
+                    snapshot_time,
+                } => {
+                    assert_eq!(expected_page, page);
+                    assert_eq!(expected_snapshot_time, snapshot_time);
+                }
+                HackernewsState::Post { .. } => {
+                    panic!("Expected page, not post visit")
+                }
+            }
+        }
+
+        fn visit_post(
+            &mut self,
+            expected_post_id: PostId,
+            expected_page: usize,
+            expected_rank: Option<i64>,
+            expected_snapshot_time: DateTime,
+        ) {
+            match self.expected_visits.pop().expect("visit not expected") {
+                HackernewsState::Page { .. } => panic!("Exptected page, not post"),
+                HackernewsState::Post {
+                    snapshot_time,
+                    post_id,
+                    page,
+                    rank,
+                } => {
+                    assert_eq!(expected_post_id, post_id);
+                    assert_eq!(expected_page, page);
+                    assert_eq!(expected_rank, rank);
+                    assert_eq!(expected_snapshot_time, snapshot_time);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_visit_news_page() {
+        let snapshot_time = chrono::Local::now().naive_utc();
+        // TODO Add parsing of next page
+        let mut mock = CrawlerMock {
+            expected_visits: [
+                34388962, 34388369, 34387409, 34388866, 34388826, 34389037, 34388095, 34388670,
+                34388773, 34388985, 34382212, 34387407, 34384825, 34386309, 34386570, 34386052,
+                34384941, 34385766, 34384719, 34384767, 34386929, 34386443, 34387081, 34386876,
+                34385223, 34387834, 34384681, 34383529, 34376781, 34386017,
+            ]
+            .into_iter()
+            .enumerate()
+            .rev()
+            .map(|(index, post_id)| HackernewsState::Post {
+                snapshot_time,
+                post_id,
+                page: 1,
+                rank: Some(index as i64 + 1),
+            })
+            .collect(),
+        };
+
+        HackernewsScraper {
+            max_page: NonZeroUsize::new(1).unwrap(),
+            ..Default::default()
+        }
+        .scrape_internal(
+            Response {
+                depth: 0,
+                request_url: "https://news.ycombinator.com/".parse().unwrap(),
+                response_url: "https://news.ycombinator.com/".parse().unwrap(),
+                response_status: StatusCode::OK,
+                response_headers: HeaderMap::default(),
+                text: include_str!("../fixtures/first_page.html").to_string(),
+                state: Some(HackernewsState::Page {
+                    page: 1,
+                    snapshot_time,
+                }),
+            },
+            &mut mock,
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn test_visit_post() {
+        let snapshot_time = chrono::Local::now().naive_utc();
+        let mut mock = CrawlerMock {
+            expected_visits: Vec::new(),
+        };
+
+        let (page, post) = HackernewsScraper::default()
+            .scrape_internal(
+                Response {
+                    depth: 0,
+                    request_url: "https://news.ycombinator.com/item?id=34388962"
+                        .parse()
+                        .unwrap(),
+                    response_url: "https://news.ycombinator.com/item?id=34388962"
+                        .parse()
+                        .unwrap(),
+                    response_status: StatusCode::OK,
+                    response_headers: HeaderMap::default(),
+                    // Item page starts with the same rows as the listing
+                    text: include_str!("../fixtures/first_page.html").to_string(),
+                    state: Some(HackernewsState::Post {
+                        snapshot_time,
+                        post_id: 34388962,
+                        page: 1,
+                        rank: Some(1),
+                    }),
+                },
+                &mut mock,
+            )
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(page, 1);
+        assert_eq!(
+            (post.rank, post.listing, post.score),
+            (Some(1), Some(Listing::Top), Some(38))
+        );
+    }
+
+    #[test]
+    fn test_rate_limited_page() {
+        let snapshot_time = chrono::Local::now().naive_utc();
+        let mut mock = CrawlerMock {
+            expected_visits: vec![HackernewsState::Page {
+                page: 3,
+                snapshot_time,
+            }],
+        };
+
+        let mut scraper = HackernewsScraper::default();
+        let output = scraper
+            .scrape_internal(
+                Response {
+                    depth: 0,
+                    request_url: "https://news.ycombinator.com/news?p=3".parse().unwrap(),
+                    response_url: "https://news.ycombinator.com/news?p=3".parse().unwrap(),
+                    response_status: StatusCode::OK,
+                    response_headers: HeaderMap::default(),
+                    text: format!("<html><body>{RATE_LIMIT_MARKER}</body></html>"),
+                    state: Some(HackernewsState::Page {
+                        page: 3,
+                        snapshot_time,
+                    }),
+                },
+                &mut mock,
+            )
+            .unwrap();
+
+        assert!(output.is_none());
+        assert!(mock.expected_visits.is_empty());
+        assert_eq!(scraper.fetcher().stats().breaker_trips.get(), 1);
+    }
+
+    #[test]
+    fn test_cut_short_by_breaker() {
+        let snapshot_time = chrono::Local::now().naive_utc();
+        let url: Url = "https://news.ycombinator.com/item?id=34388962"
+            .parse()
+            .unwrap();
+        let response = |text: String| Response {
+            depth: 0,
+            request_url: url.clone(),
+            response_url: url.clone(),
+            response_status: StatusCode::OK,
+            response_headers: HeaderMap::default(),
+            text,
+            state: Some(HackernewsState::Post {
+                snapshot_time,
+                post_id: 34388962,
+                page: 1,
+                rank: Some(1),
+            }),
+        };
+        let mut mock = CrawlerMock {
+            expected_visits: vec![HackernewsState::Post {
+                snapshot_time,
+                post_id: 34388962,
+                page: 1,
+                rank: Some(1),
+            }],
+        };
+        let (collector, cut_short) = HackernewsScraper {
+            fetcher: Arc::new(Fetcher::new(
+                std::time::Duration::ZERO,
+                Default::default(),
+                crate::fetcher::CircuitBreaker::new(std::time::Duration::ZERO, 1),
+            )),
+            ..Default::default()
+        }
+        .new_collector(snapshot_time, &CrawlTarget::default());
+        let mut scraper = collector.scraper().clone();
+
+        scraper
+            .scrape_internal(
+                response(format!("<html><body>{RATE_LIMIT_MARKER}</body></html>")),
+                &mut mock,
+            )
+            .unwrap();
+        assert!(cut_short.is_set());
+
+        // A response in flight resets the breaker, but the crawl stays cut short
+        scraper
+            .scrape_internal(
+                response(include_str!("../fixtures/first_page.html").to_string()),
+                &mut mock,
+            )
+            .unwrap()
+            .unwrap();
+        assert!(!scraper.fetcher().breaker().is_exhausted());
+        assert!(cut_short.is_set());
+    }
+}
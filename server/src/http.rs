@@ -0,0 +1,566 @@
+use std::{convert::Infallible, future::Future, net::SocketAddr, sync::Arc};
+
+use futures::TryStreamExt;
+use hackernews_crawler::core::{Post, UserPostRequest};
+use hyper::{
+    header::{AUTHORIZATION, CONTENT_TYPE},
+    server::conn::AddrStream,
+    service::{make_service_fn, service_fn},
+    Body, Method, Request, Response, StatusCode,
+};
+use tokio_util::sync::CancellationToken;
+use tonic::{Code, Status};
+
+use crate::{
+    auth::{Auth, Scope},
+    feeds::{self, Feed, FeedRequest, FEED_POSTS},
+    metrics::Metrics,
+    posts_storage::{GetCurrentTopPosts, GetFirstPagePosts, GetLatestUserPosts},
+    rate_limit::RateLimiter,
+    telemetry::{self, LogFilter},
+};
+
+/// Plain HTTP endpoints of operators next to grpc api: prometheus metrics
+/// and `GET`/`PUT /log-filter` with `RUST_LOG`-like directives of the logs,
+/// which needs an api key with `admin` scope
+pub struct Server {
+    pub metrics: Metrics,
+    pub log_filter: LogFilter,
+    pub auth: Arc<Auth>,
+}
+
+/// Atom/rss feeds of posts at `GET /feeds/...` on a listener of their own.
+/// They need an api key with `read` scope and share per-peer rate limits
+/// with grpc api
+pub struct FeedsServer<S> {
+    pub posts_storage: Arc<S>,
+    pub auth: Arc<Auth>,
+    pub rate_limiter: Arc<RateLimiter>,
+}
+
+fn text_response(status: StatusCode, body: impl Into<Body>) -> Response<Body> {
+    Response::builder()
+        .status(status)
+        .header(CONTENT_TYPE, "text/plain; charset=utf-8")
+        .body(body.into())
+        .expect("static response parts are valid")
+}
+
+/// Response of a request rejected by api key check or rate limits
+fn rejected_response(status: Status) -> Response<Body> {
+    let code = match status.code() {
+        Code::Unauthenticated => StatusCode::UNAUTHORIZED,
+        Code::PermissionDenied => StatusCode::FORBIDDEN,
+        Code::ResourceExhausted => StatusCode::TOO_MANY_REQUESTS,
+        _ => StatusCode::INTERNAL_SERVER_ERROR,
+    };
+    text_response(code, status.message().to_owned())
+}
+
+fn authorization<B>(request: &Request<B>) -> Option<&str> {
+    request
+        .headers()
+        .get(AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+}
+
+/// Serve `handle` of every request with the address of its peer
+async fn serve<H, F>(
+    addr: SocketAddr,
+    shutdown: CancellationToken,
+    handle: H,
+) -> Result<(), hyper::Error>
+where
+    H: Fn(Request<Body>, SocketAddr) -> F + Clone + Send + Sync + 'static,
+    F: Future<Output = Response<Body>> + Send + 'static,
+{
+    let make_service = make_service_fn(move |conn: &AddrStream| {
+        let remote_addr = conn.remote_addr();
+        let handle = handle.clone();
+        async move {
+            Ok::<_, Infallible>(service_fn(move |request| {
+                let response = handle(request, remote_addr);
+                async move { Ok::<_, Infallible>(response.await) }
+            }))
+        }
+    });
+
+    hyper::Server::try_bind(&addr)?
+        .serve(make_service)
+        .with_graceful_shutdown(shutdown.cancelled_owned())
+        .await
+}
+
+impl Server {
+    pub async fn handle(&self, request: Request<Body>) -> Response<Body> {
+        match (request.method(), request.uri().path()) {
+            (&Method::GET, "/metrics") => match self.metrics.encode() {
+                Ok(metrics) => text_response(StatusCode::OK, metrics),
+                Err(err) => {
+                    tracing::error!("failed to encode metrics: {err}");
+                    text_response(StatusCode::INTERNAL_SERVER_ERROR, err.to_string())
+                }
+            },
+            (_, "/log-filter") => {
+                if let Err(status) = self
+                    .auth
+                    .check_authorization(authorization(&request), Scope::Admin)
+                {
+                    return rejected_response(status);
+                }
+
+                match *request.method() {
+                    Method::GET => match self.log_filter.get() {
+                        Ok(filter) => text_response(StatusCode::OK, filter),
+                        Err(err) => {
+                            text_response(StatusCode::INTERNAL_SERVER_ERROR, err.to_string())
+                        }
+                    },
+                    Method::PUT => self.set_log_filter(request).await,
+                    _ => text_response(StatusCode::METHOD_NOT_ALLOWED, "method not allowed"),
+                }
+            }
+            _ => text_response(StatusCode::NOT_FOUND, "not found"),
+        }
+    }
+
+    async fn set_log_filter(&self, request: Request<Body>) -> Response<Body> {
+        let body = match hyper::body::to_bytes(request.into_body()).await {
+            Ok(body) => body,
+            Err(err) => return text_response(StatusCode::BAD_REQUEST, err.to_string()),
+        };
+        let directives = match std::str::from_utf8(&body) {
+            Ok(directives) => directives.trim(),
+            Err(err) => return text_response(StatusCode::BAD_REQUEST, err.to_string()),
+        };
+
+        match self.log_filter.set(directives) {
+            Ok(()) => text_response(StatusCode::OK, directives.to_owned()),
+            Err(err @ telemetry::Error::Filter(_)) => {
+                text_response(StatusCode::BAD_REQUEST, err.to_string())
+            }
+            Err(err) => text_response(StatusCode::INTERNAL_SERVER_ERROR, err.to_string()),
+        }
+    }
+
+    pub async fn serve(
+        self: Arc<Self>,
+        addr: SocketAddr,
+        shutdown: CancellationToken,
+    ) -> Result<(), hyper::Error> {
+        serve(addr, shutdown, move |request, _| {
+            let server = self.clone();
+            async move { server.handle(request).await }
+        })
+        .await
+    }
+}
+
+impl<S> FeedsServer<S>
+where
+    S: GetCurrentTopPosts + GetLatestUserPosts + GetFirstPagePosts + Send + Sync + 'static,
+    <S as GetCurrentTopPosts>::Error: ToString,
+    <S as GetLatestUserPosts>::Error: ToString,
+    <S as GetFirstPagePosts>::Error: ToString,
+{
+    pub async fn handle(&self, request: Request<Body>, remote_addr: SocketAddr) -> Response<Body> {
+        // Held till the feed is rendered
+        let _permit = match self.rate_limiter.admit_http(&request, remote_addr) {
+            Ok(permit) => permit,
+            Err(status) => return rejected_response(status),
+        };
+        if let Err(status) = self
+            .auth
+            .check_authorization(authorization(&request), Scope::Read)
+        {
+            return rejected_response(status);
+        }
+
+        match (request.method(), request.uri().path()) {
+            (&Method::GET, path) if path.starts_with("/feeds/") => {
+                match FeedRequest::parse_path(path) {
+                    Some((feed, format)) => self.feed(feed, format).await,
+                    None => text_response(StatusCode::NOT_FOUND, "not found"),
+                }
+            }
+            _ => text_response(StatusCode::NOT_FOUND, "not found"),
+        }
+    }
+
+    async fn feed(&self, request: FeedRequest, format: feeds::FeedFormat) -> Response<Body> {
+        let posts = match self.feed_posts(&request).await {
+            Ok(posts) => posts,
+            Err(err) => {
+                tracing::error!(?request, "failed to get posts of feed: {err}");
+                return text_response(StatusCode::INTERNAL_SERVER_ERROR, err);
+            }
+        };
+
+        let feed = Feed::new(&request, posts, chrono::Utc::now().naive_utc());
+        match feeds::render(&feed, format) {
+            Ok(body) => Response::builder()
+                .status(StatusCode::OK)
+                .header(CONTENT_TYPE, format.content_type())
+                .body(body.into())
+                .expect("static response parts are valid"),
+            Err(err) => {
+                tracing::error!(?request, "failed to render feed: {err}");
+                text_response(StatusCode::INTERNAL_SERVER_ERROR, err.to_string())
+            }
+        }
+    }
+
+    /// The latest `FEED_POSTS` posts of the feed, newest first
+    async fn feed_posts(&self, request: &FeedRequest) -> Result<Vec<Post>, String> {
+        let mut posts = match request {
+            FeedRequest::Top => {
+                self.posts_storage
+                    .get_current_top_posts()
+                    .await
+                    .map_err(|err| err.to_string())?
+                    .map_err(|err| err.to_string())
+                    .try_collect::<Vec<_>>()
+                    .await?
+            }
+            FeedRequest::FirstPage => {
+                return self
+                    .posts_storage
+                    .get_first_page_posts(FEED_POSTS as u32)
+                    .await
+                    .map_err(|err| err.to_string())
+            }
+            FeedRequest::User {
+                user,
+                was_at_first_page,
+            } => {
+                let user = user.clone();
+                let filter = if *was_at_first_page {
+                    UserPostRequest::WasAtFirstPage { user }
+                } else {
+                    UserPostRequest::All { user }
+                };
+                return self
+                    .posts_storage
+                    .get_latest_user_posts(filter, FEED_POSTS as u32)
+                    .await
+                    .map_err(|err| err.to_string());
+            }
+        };
+
+        posts.sort_by_key(|post| std::cmp::Reverse(post.publication_moment));
+        posts.truncate(FEED_POSTS);
+        Ok(posts)
+    }
+
+    pub async fn serve(
+        self: Arc<Self>,
+        addr: SocketAddr,
+        shutdown: CancellationToken,
+    ) -> Result<(), hyper::Error> {
+        serve(addr, shutdown, move |request, remote_addr| {
+            let server = self.clone();
+            async move { server.handle(request, remote_addr).await }
+        })
+        .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use prometheus::IntCounter;
+    use sqlx::sqlite::{SqlitePool, SqlitePoolOptions};
+    use tracing_subscriber::layer::SubscriberExt;
+
+    use super::*;
+    use crate::{auth::ApiKey, posts_storage::InsertPost, rate_limit::Limits};
+
+    type TestFeedsServer = FeedsServer<SqlitePool>;
+
+    const REMOTE_ADDR: &str = "10.0.0.1:4242";
+
+    /// One connection, so every query sees the same in-memory database
+    async fn get_storage() -> Arc<SqlitePool> {
+        let storage = SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect(":memory:")
+            .await
+            .unwrap();
+        sqlx::migrate!()
+            .run(&mut storage.acquire().await.unwrap())
+            .await
+            .unwrap();
+        Arc::new(storage)
+    }
+
+    fn get_auth() -> Arc<Auth> {
+        Arc::new(Auth::new(vec![
+            ApiKey {
+                name: "reader".to_owned(),
+                key: "read-key".to_owned(),
+                scopes: vec![Scope::Read],
+                requests_per_minute: None,
+            },
+            ApiKey {
+                name: "operator".to_owned(),
+                key: "admin-key".to_owned(),
+                scopes: vec![Scope::Read, Scope::Admin],
+                requests_per_minute: None,
+            },
+        ]))
+    }
+
+    fn get_server(log_filter: LogFilter, auth: Arc<Auth>) -> Server {
+        Server {
+            metrics: Metrics::default(),
+            log_filter,
+            auth,
+        }
+    }
+
+    async fn get_feeds_server(auth: Arc<Auth>, requests_per_minute: u32) -> TestFeedsServer {
+        FeedsServer {
+            posts_storage: get_storage().await,
+            rate_limiter: Arc::new(RateLimiter::new(
+                Limits {
+                    requests_per_minute,
+                    max_concurrent_calls: 1,
+                },
+                auth.clone(),
+            )),
+            auth,
+        }
+    }
+
+    fn request(method: Method, uri: &str, api_key: Option<&str>, body: &str) -> Request<Body> {
+        let mut request = Request::builder().method(method).uri(uri);
+        if let Some(key) = api_key {
+            request = request.header(AUTHORIZATION, format!("Bearer {key}"));
+        }
+        request.body(body.to_owned().into()).unwrap()
+    }
+
+    async fn into_parts(response: Response<Body>) -> (StatusCode, String) {
+        let status = response.status();
+        let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+        (status, String::from_utf8(body.to_vec()).unwrap())
+    }
+
+    async fn get(server: &Server, uri: &str) -> (StatusCode, String) {
+        into_parts(server.handle(request(Method::GET, uri, None, "")).await).await
+    }
+
+    async fn put(server: &Server, uri: &str, body: &str) -> (StatusCode, String) {
+        into_parts(server.handle(request(Method::PUT, uri, None, body)).await).await
+    }
+
+    async fn get_feed(
+        server: &TestFeedsServer,
+        uri: &str,
+        api_key: Option<&str>,
+    ) -> (StatusCode, String) {
+        let request = request(Method::GET, uri, api_key, "");
+        into_parts(server.handle(request, REMOTE_ADDR.parse().unwrap()).await).await
+    }
+
+    #[tokio::test]
+    async fn test_metrics() {
+        let metrics = Metrics::default();
+        let counter = IntCounter::new("test_counter", "test").unwrap();
+        metrics
+            .registry()
+            .register(Box::new(counter.clone()))
+            .unwrap();
+        counter.inc_by(42);
+
+        let server = Server {
+            metrics,
+            log_filter: LogFilter::new("info").unwrap().1,
+            auth: get_auth(),
+        };
+
+        let (status, body) = get(&server, "/metrics").await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(body.contains("test_counter 42"));
+
+        let (status, _) = get(&server, "/unknown").await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_log_filter() {
+        let (layer, log_filter) = LogFilter::new("info").unwrap();
+        let _guard = tracing::subscriber::set_default(tracing_subscriber::registry().with(layer));
+        let server = get_server(log_filter, Arc::default());
+
+        assert_eq!(
+            get(&server, "/log-filter").await,
+            (StatusCode::OK, "info".to_owned())
+        );
+
+        let (status, _) = put(
+            &server,
+            "/log-filter",
+            "server::hackernews_scrapper=debug,info\n",
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(tracing::enabled!(target: "server::hackernews_scrapper", tracing::Level::DEBUG));
+        assert!(!tracing::enabled!(target: "server::api", tracing::Level::DEBUG));
+
+        let (status, _) = put(&server, "/log-filter", "server=[").await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(
+            get(&server, "/log-filter").await,
+            (
+                StatusCode::OK,
+                "server::hackernews_scrapper=debug,info".to_owned()
+            )
+        );
+    }
+
+    #[tokio::test]
+    async fn test_log_filter_needs_admin_key() {
+        let (layer, log_filter) = LogFilter::new("info").unwrap();
+        let _guard = tracing::subscriber::set_default(tracing_subscriber::registry().with(layer));
+        let server = &get_server(log_filter, get_auth());
+        let get_filter = |api_key| async move {
+            into_parts(
+                server
+                    .handle(request(Method::GET, "/log-filter", api_key, ""))
+                    .await,
+            )
+            .await
+            .0
+        };
+
+        assert_eq!(get_filter(None).await, StatusCode::UNAUTHORIZED);
+        assert_eq!(get_filter(Some("read-key")).await, StatusCode::FORBIDDEN);
+        assert_eq!(get_filter(Some("admin-key")).await, StatusCode::OK);
+
+        let (status, _) = put(server, "/log-filter", "debug").await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+        let (status, body) = into_parts(
+            server
+                .handle(request(
+                    Method::PUT,
+                    "/log-filter",
+                    Some("admin-key"),
+                    "debug",
+                ))
+                .await,
+        )
+        .await;
+        assert_eq!((status, body.as_str()), (StatusCode::OK, "debug"));
+    }
+
+    #[tokio::test]
+    async fn test_feeds() {
+        let server = get_feeds_server(Arc::default(), 600).await;
+        let moment = |hour| {
+            chrono::NaiveDate::from_ymd_opt(2023, 1, 15)
+                .unwrap()
+                .and_hms_opt(hour, 0, 0)
+                .unwrap()
+        };
+        for (post_id, author, is_first_page) in
+            [(1, "pg", true), (2, "pg", false), (3, "dang", true)]
+        {
+            let post = Post {
+                post_id,
+                title: format!("post {post_id}"),
+                author: author.to_owned(),
+                url: format!("https://news.ycombinator.com/item?id={post_id}"),
+                link: None,
+                publication_moment: moment(post_id as u32),
+                last_snapshot_moment: moment(12),
+                rank: None,
+                listing: None,
+                score: None,
+            };
+            server
+                .posts_storage
+                .insert_post(post, is_first_page)
+                .await
+                .unwrap();
+        }
+
+        let response = server
+            .handle(
+                request(Method::GET, "/feeds/top.atom", None, ""),
+                REMOTE_ADDR.parse().unwrap(),
+            )
+            .await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(
+            response.headers()[CONTENT_TYPE],
+            "application/atom+xml; charset=utf-8"
+        );
+
+        let (_, atom) = get_feed(&server, "/feeds/top.atom", None).await;
+        let ids = |feed: &str, tag: &str| {
+            feed.match_indices(tag)
+                .map(|(start, _)| {
+                    feed[start + tag.len()..]
+                        .split('<')
+                        .next()
+                        .unwrap()
+                        .to_owned()
+                })
+                .collect::<Vec<_>>()
+        };
+        // Newest first
+        assert_eq!(
+            ids(&atom, "<title type=\"html\">"),
+            vec!["post 3", "post 1"]
+        );
+        assert!(atom.contains("<updated>2023-01-15T12:00:00Z</updated>"));
+
+        let (status, rss) = get_feed(&server, "/feeds/users/pg.rss", None).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(
+            ids(&rss, "<item>\n<guid isPermaLink=\"false\">"),
+            vec![
+                "tag:news.ycombinator.com,2007:item-2",
+                "tag:news.ycombinator.com,2007:item-1"
+            ]
+        );
+
+        let (_, rss) = get_feed(&server, "/feeds/users/pg/first-page.rss", None).await;
+        assert_eq!(
+            ids(&rss, "<title>"),
+            vec!["Hacker News: posts of pg at the first page", "post 1"]
+        );
+
+        let (_, atom) = get_feed(&server, "/feeds/first-page.atom", None).await;
+        assert_eq!(
+            ids(&atom, "<title type=\"html\">"),
+            vec!["post 3", "post 1"]
+        );
+
+        let (status, _) = get_feed(&server, "/feeds/top.json", None).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_feeds_need_read_key_and_are_rate_limited() {
+        let server = get_feeds_server(get_auth(), 3).await;
+
+        let (status, _) = get_feed(&server, "/feeds/top.atom", None).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+        let (status, _) = get_feed(&server, "/feeds/top.atom", Some("wrong-key")).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+        let (status, _) = get_feed(&server, "/feeds/top.atom", None).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+        // Requests without a known key are limited by ip, the reader has its own quota
+        let (status, _) = get_feed(&server, "/feeds/top.atom", None).await;
+        assert_eq!(status, StatusCode::TOO_MANY_REQUESTS);
+        for _ in 0..3 {
+            let (status, _) = get_feed(&server, "/feeds/top.atom", Some("read-key")).await;
+            assert_eq!(status, StatusCode::OK);
+        }
+        let (status, _) = get_feed(&server, "/feeds/top.atom", Some("read-key")).await;
+        assert_eq!(status, StatusCode::TOO_MANY_REQUESTS);
+    }
+}
@@ -0,0 +1,1004 @@
+// You should implement a web crawler for Hacker News. Crawl the first 10 pages of Hacker News and store enough information to be able to respond to the following through some APIs:
+// 1.    List of top posts.
+// 2.    List of posts sent by a user.
+// 3.    List of posts of a user that was on the first page at some point.
+//
+// DO NOT spend too much time on this question (max 4-5 hours). This question is open ended. What we are curious to see is your use of:
+// 1.    Database use
+// 2.    API design
+// 3.    Concurrency
+// 4.    Testing
+// 5.    Containerisation and ease of deployment
+//
+// It is not necessary to have a finished project, but it should be at a state where we can reliably get signals for above and to know that you are experienced in topics that will be needed during the job.
+//
+// For example, there's no need to have significant coverage, but we expect to see sort of mocking during some test.
+//
+// Requirements:
+// 1. Use Rust.
+// 2. Use a relational database.
+/// Module with admin api to control the crawler
+mod admin;
+
+/// Module with external api
+mod api;
+
+/// Module with api keys check of grpc calls
+mod auth;
+
+/// Module with the crawler state shared with admin api
+mod crawler_control;
+
+/// Module with markdown & html rendering of digests
+mod digest;
+
+/// Module with ndjson & parquet dumps of crawled data
+mod dump;
+
+/// Module with atom & rss feeds of posts
+mod feeds;
+
+/// Module with http fetcher for scrapper: pacing, retries & circuit breaker
+mod fetcher;
+
+/// Module with scrapper for hackernews website
+mod hackernews_scrapper;
+
+/// Module with plain http endpoints
+mod http;
+
+/// Module with prometheus metrics
+mod metrics;
+mod posts_storage;
+
+/// Module with grpc health statuses of the app
+mod readiness;
+
+/// Module with wall-clock aligned schedule of crawls
+mod scheduler;
+
+/// Module with per-peer rate limits of grpc server
+mod rate_limit;
+
+/// Module with restart policies & health of app components
+mod supervisor;
+
+/// Module with logs & opentelemetry tracing setup
+mod telemetry;
+
+/// Module with tls & client certificates of grpc server
+mod tls;
+
+use std::{future::Future, net::SocketAddr, path::PathBuf, sync::Arc, time::Duration};
+
+use clap::Parser;
+use confique::Config;
+use futures::StreamExt;
+use posts_storage::{BeginSnapshot, GetLatestSnapshot, InsertPost, Storage};
+use reqwest::Url;
+use tokio::{
+    signal::unix::{signal, SignalKind},
+    sync::watch,
+};
+use tokio_util::sync::CancellationToken;
+use tonic::transport::{NamedService, ServerTlsConfig};
+use tonic_health::{proto::health_server::HealthServer, ServingStatus};
+
+use hackernews_crawler::{
+    core::{CrawlTarget, DateTime},
+    proto::{
+        self, admin_service_server::AdminServiceServer, post_service_server::PostServiceServer,
+    },
+};
+
+use api::ApiMetrics;
+use auth::{ApiKey, Auth, Scope};
+use crawler_control::CrawlerControl;
+use fetcher::{CircuitBreaker, Fetcher, RetryPolicy};
+use metrics::{ComponentHealth, Measured, Metrics, SnapshotAge, StorageMetrics};
+use rate_limit::{Limits, RateLimiter};
+use readiness::ServiceReadiness;
+use scheduler::{CrawlSchedule, Scheduler};
+use supervisor::{supervise, Component, Health, RestartPolicy};
+use telemetry::{LogFilter, LogFormat};
+
+/// Runs the crawler & api without a command
+#[derive(clap::Parser, Debug)]
+struct Args {
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+/// Commands against the database of `DATABASE_URL`
+#[derive(clap::Subcommand, Debug)]
+enum Command {
+    /// Dump posts with their snapshots, ranks and scores
+    Export {
+        #[arg(long, default_value_t = dump::DumpFormat::default())]
+        format: dump::DumpFormat,
+        /// Snapshots from, e.g. `2023-01-15` or `2023-01-15T08:30:00Z`
+        #[arg(long, value_parser = dump::parse_moment)]
+        since: Option<DateTime>,
+        /// Snapshots till, not included
+        #[arg(long, value_parser = dump::parse_moment)]
+        until: Option<DateTime>,
+        /// Stdout without it
+        #[arg(long)]
+        output: Option<PathBuf>,
+    },
+    /// Load a dump of `export`, records, which are stored already, are skipped
+    Import {
+        #[arg(long, default_value_t = dump::DumpFormat::default())]
+        format: dump::DumpFormat,
+        input: PathBuf,
+    },
+}
+
+async fn run_command(command: Command, connect_str: &str) -> Result<(), dump::Error> {
+    match command {
+        Command::Export {
+            format,
+            since,
+            until,
+            output,
+        } => {
+            let storage = posts_storage::sqlite::connect_read_only(connect_str, 1).await?;
+            let exported = match output {
+                Some(path) => {
+                    let file = std::io::BufWriter::new(std::fs::File::create(path)?);
+                    dump::export(&storage, since, until, format, file).await?
+                }
+                None => {
+                    let stdout = std::io::BufWriter::new(std::io::stdout());
+                    dump::export(&storage, since, until, format, stdout).await?
+                }
+            };
+            eprintln!("exported {exported} records");
+        }
+        Command::Import { format, input } => {
+            let storage = posts_storage::sqlite::connect(connect_str).await?;
+            sqlx::migrate!().run(&storage).await?;
+            let stats = dump::import(&storage, format, &input).await?;
+            eprintln!(
+                "imported {} records, {} were stored already",
+                stats.imported, stats.skipped
+            );
+        }
+    }
+    Ok(())
+}
+
+#[derive(Debug, Config)]
+struct Configuration {
+    #[config(env = "GRPC_SERVER_ADDRESS", default = "0.0.0.0:7777")]
+    bind_address: SocketAddr,
+    /// PEM certificate chain of grpc server, it's served over tls when set
+    #[config(env = "TLS_CERT")]
+    tls_cert: Option<PathBuf>,
+    /// PEM private key of `tls_cert`
+    #[config(env = "TLS_KEY")]
+    tls_key: Option<PathBuf>,
+    /// PEM CA of client certificates, they are required when it's set
+    #[config(env = "TLS_CLIENT_CA")]
+    tls_client_ca: Option<PathBuf>,
+    /// Metrics and log filter of operators
+    #[config(env = "HTTP_SERVER_ADDRESS", default = "0.0.0.0:9100")]
+    http_address: SocketAddr,
+    /// Atom/rss feeds of posts
+    #[config(env = "FEEDS_SERVER_ADDRESS", default = "0.0.0.0:9200")]
+    feeds_address: SocketAddr,
+    #[config(env = "DATABASE_URL", default = "sqlite:posts.db")]
+    sqlite_connect_str: String,
+    /// Connections of the read-only pool of api, the crawler has its own pool
+    #[config(env = "API_DB_CONNECTIONS", default = 4)]
+    api_db_connections: u32,
+    /// Calls of one api key or ip, health checks are not limited
+    #[config(env = "PEER_REQUESTS_PER_MINUTE", default = 600)]
+    peer_requests_per_minute: u32,
+    /// Calls in flight of one api key or ip, streams count till their end
+    #[config(env = "PEER_MAX_CONCURRENT_CALLS", default = 8)]
+    peer_max_concurrent_calls: u32,
+    #[config(env = "SCRAPPER_TIMEOUT_MILLIS", default = 1500)]
+    scrapper_timeout_millis: u64,
+    /// Cadence of crawls per listing and pages, e.g. `top:1@1m,top:2-10@10m,new:1@30s`
+    #[config(env = "CRAWL_SCHEDULE", default = "top:1-10@1m")]
+    crawl_schedule: CrawlSchedule,
+    /// How many crawls triggered by admin api can wait for start
+    #[config(env = "CRAWL_QUEUE_CAPACITY", default = 8)]
+    crawl_queue_capacity: usize,
+    #[config(env = "SHUTDOWN_TIMEOUT_SECS", default = 30)]
+    shutdown_timeout_secs: u64,
+    #[config(env = "HACKERNEWS_URL", default = "https://news.ycombinator.com/")]
+    hackernews_url: Url,
+    #[config(env = "FETCH_MAX_RETRIES", default = 3)]
+    fetch_max_retries: u32,
+    #[config(env = "FETCH_RETRY_BASE_MILLIS", default = 500)]
+    fetch_retry_base_millis: u64,
+    #[config(env = "FETCH_RETRY_MAX_MILLIS", default = 30000)]
+    fetch_retry_max_millis: u64,
+    #[config(env = "CIRCUIT_BREAKER_COOLDOWN_SECS", default = 60)]
+    circuit_breaker_cooldown_secs: u64,
+    #[config(env = "CIRCUIT_BREAKER_MAX_TRIPS", default = 5)]
+    circuit_breaker_max_trips: u32,
+    #[config(env = "RESTART_MAX", default = 5)]
+    restart_max: u32,
+    #[config(env = "RESTART_WINDOW_SECS", default = 600)]
+    restart_window_secs: u64,
+    #[config(env = "RESTART_BACKOFF_SECS", default = 5)]
+    restart_backoff_secs: u64,
+    /// OTLP/HTTP traces endpoint, spans are not exported when it's not set
+    #[config(env = "OTLP_ENDPOINT")]
+    otlp_endpoint: Option<Url>,
+    /// Keys of grpc api, feeds and `/log-filter`, they are open when there
+    /// are none. Set only by `[[api_keys]]` tables of `config.toml`
+    #[config(default = [])]
+    api_keys: Vec<ApiKey>,
+    #[config(env = "OTEL_SERVICE_NAME", default = "hackernews-crawler")]
+    otel_service_name: String,
+    /// `text` or `json`
+    #[config(env = "LOG_FORMAT", default = "text")]
+    log_format: LogFormat,
+    /// Initial log filter, it can be changed at runtime by `PUT /log-filter`
+    #[config(env = "RUST_LOG", default = "info")]
+    log_filter: String,
+}
+
+struct App {
+    posts_storage: Arc<Storage>,
+    api_storage: Arc<Storage>,
+    scrapper: hackernews_scrapper::HackernewsScraper,
+    crawler: Arc<CrawlerControl>,
+    bind_address: SocketAddr,
+    tls: Option<ServerTlsConfig>,
+    http_address: SocketAddr,
+    feeds_address: SocketAddr,
+    crawl_schedule: CrawlSchedule,
+    shutdown_timeout: Duration,
+    shutdown: CancellationToken,
+    restart_policy: RestartPolicy,
+    health: Arc<Health>,
+    latest_snapshot: watch::Sender<Option<DateTime>>,
+    metrics: Metrics,
+    storage_metrics: StorageMetrics,
+    api_metrics: ApiMetrics,
+    auth: Arc<Auth>,
+    rate_limiter: Arc<RateLimiter>,
+    log_filter: LogFilter,
+}
+
+#[derive(thiserror::Error, Debug)]
+enum Error {
+    #[error(transparent)]
+    Database(#[from] sqlx::Error),
+    #[error(transparent)]
+    Transport(#[from] tonic::transport::Error),
+    #[error(transparent)]
+    Runtime(#[from] tokio::task::JoinError),
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+    #[error(transparent)]
+    Supervisor(#[from] supervisor::Error),
+    #[error(transparent)]
+    Reflection(#[from] tonic_reflection::server::Error),
+    #[error(transparent)]
+    Http(#[from] hyper::Error),
+    #[error(transparent)]
+    Metrics(#[from] prometheus::Error),
+    #[error(transparent)]
+    Telemetry(#[from] telemetry::Error),
+    #[error(transparent)]
+    Tls(#[from] tls::Error),
+}
+
+/// Resolves on the first SIGINT or SIGTERM
+///
+/// Handlers are installed before return, so a signal which comes right after
+/// this call is not lost and doesn't kill the process
+fn shutdown_signal() -> Result<impl Future<Output = ()>, std::io::Error> {
+    let mut interrupt = signal(SignalKind::interrupt())?;
+    let mut terminate = signal(SignalKind::terminate())?;
+
+    Ok(async move {
+        tokio::select! {
+            _ = interrupt.recv() => tracing::info!("SIGINT received"),
+            _ = terminate.recv() => tracing::info!("SIGTERM received"),
+        }
+    })
+}
+
+impl App {
+    async fn new(config: &Configuration, log_filter: LogFilter) -> Result<Self, Error> {
+        let tls = tls::server_config(
+            config.tls_cert.as_deref(),
+            config.tls_key.as_deref(),
+            config.tls_client_ca.as_deref(),
+        )?;
+
+        let posts_storage = Arc::new(
+            posts_storage::sqlite::connect(&config.sqlite_connect_str)
+                .await
+                .expect("Failed to connect database"),
+        );
+
+        sqlx::migrate!()
+            .run(&mut posts_storage.acquire().await?)
+            .await
+            .unwrap();
+        let backfilled = posts_storage::sqlite::backfill_domains(&posts_storage).await?;
+        if backfilled > 0 {
+            tracing::info!(posts = backfilled, "domains of crawled posts are stored");
+        }
+        let api_storage = Arc::new(
+            posts_storage::sqlite::connect_read_only(
+                &config.sqlite_connect_str,
+                config.api_db_connections,
+            )
+            .await?,
+        );
+
+        let fetcher = Fetcher::new(
+            Duration::from_millis(config.scrapper_timeout_millis),
+            RetryPolicy {
+                max_retries: config.fetch_max_retries,
+                base_delay: Duration::from_millis(config.fetch_retry_base_millis),
+                max_delay: Duration::from_millis(config.fetch_retry_max_millis),
+            },
+            CircuitBreaker::new(
+                Duration::from_secs(config.circuit_breaker_cooldown_secs),
+                config.circuit_breaker_max_trips,
+            ),
+        );
+
+        let scrapper = hackernews_scrapper::HackernewsScraper::new(
+            config.hackernews_url.clone(),
+            Arc::new(fetcher),
+        );
+        let latest_snapshot = watch::channel(posts_storage.get_latest_snapshot().await?).0;
+
+        let metrics = Metrics::default();
+        let storage_metrics = StorageMetrics::default();
+        let api_metrics = ApiMetrics::default();
+        scrapper.fetcher().stats().register(metrics.registry())?;
+        scrapper.stats().register(metrics.registry())?;
+        storage_metrics.register(metrics.registry())?;
+        api_metrics.register(metrics.registry())?;
+
+        let auth = Arc::new(Auth::new(config.api_keys.clone()));
+        auth.register(metrics.registry())?;
+        if !auth.is_enabled() {
+            tracing::warn!("no api keys are configured, grpc api and feeds are open to everyone");
+        }
+        let rate_limiter = Arc::new(RateLimiter::new(
+            Limits {
+                requests_per_minute: config.peer_requests_per_minute,
+                max_concurrent_calls: config.peer_max_concurrent_calls,
+            },
+            auth.clone(),
+        ));
+        rate_limiter.register(metrics.registry())?;
+        metrics
+            .registry()
+            .register(Box::new(SnapshotAge::new(latest_snapshot.subscribe())))?;
+        let health = Arc::new(Health::default());
+        metrics
+            .registry()
+            .register(Box::new(ComponentHealth::new(health.subscribe())))?;
+
+        Ok(Self {
+            posts_storage,
+            api_storage,
+            scrapper,
+            crawler: Arc::new(CrawlerControl::new(config.crawl_queue_capacity)),
+            bind_address: config.bind_address,
+            tls,
+            http_address: config.http_address,
+            feeds_address: config.feeds_address,
+            crawl_schedule: config.crawl_schedule.clone(),
+            shutdown_timeout: Duration::from_secs(config.shutdown_timeout_secs),
+            shutdown: CancellationToken::new(),
+            restart_policy: RestartPolicy {
+                max_restarts: config.restart_max,
+                window: Duration::from_secs(config.restart_window_secs),
+                backoff: Duration::from_secs(config.restart_backoff_secs),
+            },
+            health,
+            latest_snapshot,
+            metrics,
+            storage_metrics,
+            api_metrics,
+            auth,
+            rate_limiter,
+            log_filter,
+        })
+    }
+
+    /// Token which stops the app: no new crawls, in-flight snapshot and
+    /// open grpc streams get `shutdown_timeout` to finish
+    pub fn shutdown_token(&self) -> CancellationToken {
+        self.shutdown.clone()
+    }
+
+    pub fn health(&self) -> Arc<Health> {
+        self.health.clone()
+    }
+
+    /// Crawl pages of `target` into one snapshot, which is committed only when crawl is over
+    #[tracing::instrument(name = "crawl", skip_all, fields(snapshot_time, listing = %target.listing))]
+    async fn crawl(&self, target: &CrawlTarget) -> Result<(), Error> {
+        let snapshot_time = chrono::Local::now().naive_utc();
+        tracing::Span::current().record("snapshot_time", tracing::field::display(snapshot_time));
+        let snapshot = Measured::new(
+            self.posts_storage.begin_snapshot().await?,
+            self.storage_metrics.clone(),
+        );
+        let (mut collector, cut_short) = self.scrapper.new_collector(snapshot_time, target);
+
+        while let Some(output) = collector.next().await {
+            match output {
+                Ok((page, post)) => {
+                    self.crawler.set_page(page);
+                    snapshot
+                        .insert_post(post, target.is_first_page(page))
+                        .await?
+                }
+                Err(err) => {
+                    tracing::warn!(%err, "failed to crawl");
+                    self.crawler.set_error(err.to_string());
+                    cut_short.set();
+                }
+            }
+        }
+
+        if cut_short.is_set() {
+            // A partial snapshot would look like posts dropped off the listing
+            tracing::error!("crawl was cut short, rolling back");
+            drop(snapshot);
+        } else {
+            snapshot.into_inner().commit().await?;
+            // Only a crawl of HN first page makes a snapshot of top posts
+            if target.is_first_page(target.first_page.get()) {
+                self.latest_snapshot.send_replace(Some(snapshot_time));
+            }
+        }
+
+        let stats = self.scrapper.fetcher().stats();
+        tracing::info!(
+            requests = stats.requests.get(),
+            retries = stats.retries.get(),
+            failures = stats.failures.get(),
+            breaker_trips = stats.breaker_trips.get(),
+            "crawl finished"
+        );
+        // A new crawl cycle gets a new chance, even if HN stopped us in this one
+        self.scrapper.fetcher().breaker().reset();
+
+        Ok(())
+    }
+
+    /// Crawl, which is rolled back on pause and gets `shutdown_timeout`
+    /// to finish on shutdown. Returns `false` if it was rolled back on pause
+    async fn crawl_interruptible(&self, target: &CrawlTarget) -> Result<bool, Error> {
+        let crawl = self.crawl(target);
+        tokio::pin!(crawl);
+
+        tokio::select! {
+            result = &mut crawl => result.map(|()| true),
+            _ = self.crawler.wait_paused(true) => {
+                tracing::warn!("crawler is paused, in-flight snapshot is rolled back");
+                Ok(false)
+            }
+            _ = self.shutdown.cancelled() => {
+                tracing::info!("waiting for in-flight snapshot before shutdown");
+                match tokio::time::timeout(self.shutdown_timeout, crawl).await {
+                    Ok(result) => result.map(|()| true),
+                    Err(_) => {
+                        tracing::warn!("in-flight snapshot is rolled back");
+                        Ok(true)
+                    }
+                }
+            }
+        }
+    }
+
+    /// Crawl by `crawl_schedule` and crawls triggered by admin api in
+    /// between. A crawl which is due or rolled back while the crawler is
+    /// paused starts right after resume
+    async fn run_crawler(&self) -> Result<(), Error> {
+        let mut queue = self.crawler.queue().await;
+        let mut scheduler = Scheduler::new(self.crawl_schedule.clone());
+        let mut next = None;
+
+        while !self.shutdown.is_cancelled() {
+            let target = match next.take() {
+                Some(target) => target,
+                None => tokio::select! {
+                    target = scheduler.next() => target,
+                    Some(target) = queue.recv() => target,
+                    _ = self.shutdown.cancelled() => break,
+                },
+            };
+
+            tokio::select! {
+                _ = self.crawler.wait_paused(false) => {}
+                _ = self.shutdown.cancelled() => break,
+            }
+
+            self.crawler.start_crawl(target.clone());
+            let result = self.crawl_interruptible(&target).await;
+            self.crawler.finish_crawl();
+            match result {
+                Ok(true) => {}
+                Ok(false) => next = Some(target),
+                Err(err) => {
+                    self.crawler.set_error(err.to_string());
+                    return Err(err);
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn run_api(
+        &self,
+        health_server: HealthServer<impl tonic_health::proto::health_server::Health>,
+    ) -> Result<(), Error> {
+        let reflection_server = tonic_reflection::server::Builder::configure()
+            .register_encoded_file_descriptor_set(proto::FILE_DESCRIPTOR_SET)
+            .register_encoded_file_descriptor_set(
+                tonic_health::proto::GRPC_HEALTH_V1_FILE_DESCRIPTOR_SET,
+            )
+            .build()?;
+
+        let mut builder = tonic::transport::Server::builder();
+        if let Some(tls) = &self.tls {
+            builder = builder.tls_config(tls.clone())?;
+        }
+
+        let server = builder
+            .accept_http1(true)
+            .trace_fn(telemetry::grpc_span)
+            .layer(self.rate_limiter.layer())
+            .add_service(PostServiceServer::with_interceptor(
+                api::Server {
+                    posts_storage: self.api_storage.clone(),
+                    metrics: self.api_metrics.clone(),
+                },
+                self.auth.interceptor(Scope::Read),
+            ))
+            .add_service(AdminServiceServer::with_interceptor(
+                admin::Server {
+                    crawler: self.crawler.clone(),
+                    latest_snapshot: self.latest_snapshot.subscribe(),
+                },
+                self.auth.interceptor(Scope::Admin),
+            ))
+            .add_service(health_server)
+            .add_service(reflection_server)
+            .serve_with_shutdown(self.bind_address, self.shutdown.clone().cancelled_owned());
+
+        let drain_deadline = async {
+            self.shutdown.cancelled().await;
+            tracing::info!("draining grpc streams");
+            tokio::time::sleep(self.shutdown_timeout).await;
+        };
+
+        tokio::select! {
+            result = server => Ok(result?),
+            _ = drain_deadline => {
+                tracing::warn!("grpc streams were not drained in time, aborting them");
+                Ok(())
+            }
+        }
+    }
+
+    async fn run_http(&self) -> Result<(), Error> {
+        let server = Arc::new(http::Server {
+            metrics: self.metrics.clone(),
+            log_filter: self.log_filter.clone(),
+            auth: self.auth.clone(),
+        });
+
+        Ok(server
+            .serve(self.http_address, self.shutdown.clone())
+            .await?)
+    }
+
+    async fn run_feeds(&self) -> Result<(), Error> {
+        let server = Arc::new(http::FeedsServer {
+            posts_storage: self.api_storage.clone(),
+            auth: self.auth.clone(),
+            rate_limiter: self.rate_limiter.clone(),
+        });
+
+        Ok(server
+            .serve(self.feeds_address, self.shutdown.clone())
+            .await?)
+    }
+
+    /// Supervise one component and stop the whole app if it fails for good,
+    /// so the failure is not hidden behind the other healthy component
+    async fn run_supervised<F, Fut>(&self, component: Component, factory: F) -> Result<(), Error>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<(), Error>>,
+    {
+        let result = supervise(
+            component,
+            &self.health,
+            &self.restart_policy,
+            &self.shutdown,
+            factory,
+        )
+        .await;
+
+        if result.is_err() {
+            self.shutdown.cancel();
+        }
+
+        Ok(result?)
+    }
+
+    pub async fn run(self) -> Result<(), Error> {
+        let services = [
+            ServiceReadiness {
+                service: <PostServiceServer<api::Server<Storage>> as NamedService>::NAME,
+                components: &[Component::Api],
+                needs_snapshot: true,
+            },
+            ServiceReadiness {
+                service: <AdminServiceServer<admin::Server> as NamedService>::NAME,
+                components: &[Component::Api],
+                needs_snapshot: false,
+            },
+        ];
+        let (mut reporter, health_server) = tonic_health::server::health_reporter();
+        readiness::set_status(&mut reporter, &services, ServingStatus::NotServing).await;
+
+        let (crawler, api, http, feeds, ()) = tokio::join!(
+            self.run_supervised(Component::Crawler, || self.run_crawler()),
+            self.run_supervised(Component::Api, || self.run_api(health_server.clone())),
+            self.run_supervised(Component::Http, || self.run_http()),
+            self.run_supervised(Component::Feeds, || self.run_feeds()),
+            readiness::report_readiness(
+                reporter,
+                &services,
+                self.health.subscribe(),
+                self.latest_snapshot.subscribe(),
+                &self.shutdown,
+            ),
+        );
+
+        self.api_storage.close().await;
+        self.posts_storage.close().await;
+        tracing::info!("shutdown completed");
+
+        crawler.and(api).and(http).and(feeds)
+    }
+}
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let args = Args::parse();
+    let config = Configuration::builder().file("config.toml").env().load()?;
+    if let Some(command) = args.command {
+        return Ok(run_command(command, &config.sqlite_connect_str).await?);
+    }
+
+    let telemetry = telemetry::init(
+        config.log_format,
+        &config.log_filter,
+        config.otlp_endpoint.as_ref(),
+        &config.otel_service_name,
+    )?;
+
+    let app = App::new(&config, telemetry.log_filter()).await?;
+
+    let shutdown = app.shutdown_token();
+    let signal = shutdown_signal()?;
+    tokio::spawn(async move {
+        signal.await;
+        shutdown.cancel();
+    });
+
+    let health = app.health();
+    let result = app.run().await;
+    for (component, status) in health.unhealthy() {
+        tracing::error!("{component} stopped unhealthy: {status:?}");
+    }
+    telemetry.shutdown();
+
+    Ok(result?)
+}
+
+#[cfg(test)]
+mod tests {
+    use hackernews_crawler::core::{CrawlProgress, Listing};
+
+    use crate::fetcher::tests::{http_response, scripted_server};
+
+    use super::*;
+
+    fn get_config(hackernews_url: Url) -> Configuration {
+        let path = std::env::temp_dir().join(format!(
+            "hackernews-crawler-test-{}.db",
+            rand::random::<u64>()
+        ));
+
+        Configuration {
+            bind_address: "127.0.0.1:0".parse().unwrap(),
+            tls_cert: None,
+            tls_key: None,
+            tls_client_ca: None,
+            http_address: "127.0.0.1:0".parse().unwrap(),
+            feeds_address: "127.0.0.1:0".parse().unwrap(),
+            sqlite_connect_str: format!("sqlite:{}?mode=rwc", path.display()),
+            api_db_connections: 2,
+            peer_requests_per_minute: 600,
+            peer_max_concurrent_calls: 8,
+            scrapper_timeout_millis: 20,
+            crawl_schedule: "top:1-10@1h".parse().unwrap(),
+            crawl_queue_capacity: 1,
+            shutdown_timeout_secs: 1,
+            hackernews_url,
+            fetch_max_retries: 0,
+            fetch_retry_base_millis: 1,
+            fetch_retry_max_millis: 1,
+            circuit_breaker_cooldown_secs: 1,
+            circuit_breaker_max_trips: 1,
+            restart_max: 0,
+            restart_window_secs: 60,
+            restart_backoff_secs: 1,
+            otlp_endpoint: None,
+            api_keys: Vec::new(),
+            otel_service_name: "test".to_owned(),
+            log_format: LogFormat::Text,
+            log_filter: "info".to_owned(),
+        }
+    }
+
+    /// Filter of logs which are not installed in tests
+    fn get_log_filter() -> LogFilter {
+        LogFilter::new("info").unwrap().1
+    }
+
+    #[tokio::test]
+    async fn test_crawl_spans() {
+        let hackernews_url = scripted_server(vec![http_response(
+            "200 OK",
+            &[],
+            include_str!("../fixtures/first_page.html"),
+        )])
+        .await;
+        let config = Configuration {
+            scrapper_timeout_millis: 0,
+            ..get_config(hackernews_url)
+        };
+        let app = App::new(&config, get_log_filter()).await.unwrap();
+
+        let (subscriber, recorder) = telemetry::tests::in_memory_subscriber();
+        let _guard = tracing::subscriber::set_default(subscriber);
+        app.crawl(&CrawlTarget::default()).await.unwrap();
+
+        let spans = recorder.finished_spans();
+        let crawl = telemetry::tests::find_spans(&spans, "crawl")[0];
+        let pages = telemetry::tests::find_spans(&spans, "fetch_page");
+        let items = telemetry::tests::find_spans(&spans, "fetch_item");
+        let inserts = telemetry::tests::find_spans(&spans, "insert_post");
+
+        assert_eq!(pages.len(), 10);
+        assert_eq!(items.len(), 300);
+        assert_eq!(inserts.len(), 300);
+        for span in pages.iter().chain(&items).chain(&inserts) {
+            assert_eq!(span.parent_span_id, crawl.span_context.span_id());
+            assert_eq!(span.span_context.trace_id(), crawl.span_context.trace_id());
+        }
+    }
+
+    /// Poll condition, which is changed by the app in background
+    async fn wait_until(condition: impl Fn() -> bool) {
+        tokio::time::timeout(Duration::from_secs(30), async {
+            while !condition() {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .expect("condition is not met in time")
+    }
+
+    async fn count_posts(posts_storage: &Storage) -> i64 {
+        let (posts,): (i64,) = sqlx::query_as(r#"SELECT COUNT(*) FROM "posts""#)
+            .fetch_one(posts_storage)
+            .await
+            .unwrap();
+        posts
+    }
+
+    #[tokio::test]
+    async fn test_pause_and_trigger_crawl() {
+        let hackernews_url = scripted_server(vec![http_response(
+            "200 OK",
+            &[],
+            include_str!("../fixtures/first_page.html"),
+        )])
+        .await;
+        let config = Configuration {
+            scrapper_timeout_millis: 5,
+            ..get_config(hackernews_url)
+        };
+
+        let app = App::new(&config, get_log_filter()).await.unwrap();
+        let crawler = app.crawler.clone();
+        let posts_storage = app.posts_storage.clone();
+        let shutdown = app.shutdown_token();
+
+        let (result, ()) = tokio::join!(app.run(), async {
+            wait_until(|| {
+                matches!(
+                    crawler.status().crawl,
+                    Some(CrawlProgress { page: Some(_), .. })
+                )
+            })
+            .await;
+            crawler.pause();
+            wait_until(|| crawler.status().crawl.is_none()).await;
+            assert_eq!(count_posts(&posts_storage).await, 0);
+            assert!(crawler.trigger(CrawlTarget::default()).is_err());
+
+            // Rolled back crawl starts again on resume, the triggered one waits for it
+            crawler.resume();
+            crawler
+                .trigger(CrawlTarget::new(Listing::Ask, 2, 2).unwrap())
+                .unwrap();
+            wait_until(|| {
+                matches!(&crawler.status().crawl, Some(CrawlProgress { target, .. }) if target.listing == Listing::Ask)
+            })
+            .await;
+            assert_eq!(count_posts(&posts_storage).await, 30);
+
+            wait_until(|| crawler.status().crawl.is_none()).await;
+            shutdown.cancel();
+        });
+        result.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_rate_limited_crawl_is_rolled_back() {
+        // HN serves its rate limit page as 200 OK
+        let hackernews_url = scripted_server(vec![http_response(
+            "200 OK",
+            &[],
+            &format!(
+                "<html><body>{}</body></html>",
+                hackernews_scrapper::RATE_LIMIT_MARKER
+            ),
+        )])
+        .await;
+        let config = Configuration {
+            scrapper_timeout_millis: 0,
+            circuit_breaker_max_trips: 2,
+            ..get_config(hackernews_url)
+        };
+        let app = App::new(&config, get_log_filter()).await.unwrap();
+
+        tokio::time::timeout(Duration::from_secs(30), app.crawl(&CrawlTarget::default()))
+            .await
+            .expect("crawl is not stopped by the circuit breaker")
+            .unwrap();
+
+        let status = app.crawler.status();
+        assert!(status
+            .last_error
+            .unwrap()
+            .contains("circuit breaker is open after 2 consecutive trips"));
+        assert_eq!(count_posts(&app.posts_storage).await, 0);
+        assert!(app.latest_snapshot.borrow().is_none());
+        // The breaker is ready for the next crawl
+        assert!(!app.scrapper.fetcher().breaker().is_exhausted());
+    }
+
+    #[tokio::test]
+    async fn test_crawl_with_failed_fetches_is_rolled_back() {
+        let hackernews_url = scripted_server(vec![
+            http_response("200 OK", &[], include_str!("../fixtures/first_page.html")),
+            http_response("404 Not Found", &[], ""),
+        ])
+        .await;
+        let config = Configuration {
+            scrapper_timeout_millis: 0,
+            ..get_config(hackernews_url)
+        };
+        let app = App::new(&config, get_log_filter()).await.unwrap();
+
+        app.crawl(&CrawlTarget::new(Listing::Top, 1, 2).unwrap())
+            .await
+            .unwrap();
+
+        assert!(app.crawler.status().last_error.is_some());
+        assert_eq!(count_posts(&app.posts_storage).await, 0);
+        assert!(app.latest_snapshot.borrow().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_shutdown_on_sigterm() {
+        let hackernews_url = scripted_server(vec![http_response(
+            "200 OK",
+            &[],
+            include_str!("../fixtures/first_page.html"),
+        )])
+        .await;
+        let config = get_config(hackernews_url);
+
+        let app = App::new(&config, get_log_filter()).await.unwrap();
+        let posts_storage = app.posts_storage.clone();
+
+        let shutdown = app.shutdown_token();
+        let signal = shutdown_signal().unwrap();
+        tokio::spawn(async move {
+            signal.await;
+            shutdown.cancel();
+        });
+
+        let (result, ()) = tokio::join!(
+            tokio::time::timeout(Duration::from_secs(10), app.run()),
+            async {
+                // Full crawl takes several seconds, so signal lands in the middle of it
+                tokio::time::sleep(Duration::from_millis(500)).await;
+                let status = std::process::Command::new("kill")
+                    .args(["-TERM", &std::process::id().to_string()])
+                    .status()
+                    .unwrap();
+                assert!(status.success());
+            }
+        );
+        result.expect("app didn't stop in time").unwrap();
+        assert!(posts_storage.is_closed());
+
+        let storage = posts_storage::sqlite::connect(&config.sqlite_connect_str)
+            .await
+            .unwrap();
+        let (posts,): (i64,) = sqlx::query_as(r#"SELECT COUNT(*) FROM "posts""#)
+            .fetch_one(&storage)
+            .await
+            .unwrap();
+        assert_eq!(posts, 0, "partial snapshot must be rolled back");
+    }
+
+    #[tokio::test]
+    async fn test_api_failure_stops_app() {
+        let hackernews_url = scripted_server(vec![http_response(
+            "200 OK",
+            &[],
+            include_str!("../fixtures/first_page.html"),
+        )])
+        .await;
+        let occupied = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let config = Configuration {
+            bind_address: occupied.local_addr().unwrap(),
+            ..get_config(hackernews_url)
+        };
+
+        let app = App::new(&config, get_log_filter()).await.unwrap();
+        let health = app.health();
+
+        let result = tokio::time::timeout(Duration::from_secs(10), app.run())
+            .await
+            .expect("crawler must be stopped after api failure");
+
+        assert!(matches!(
+            result,
+            Err(Error::Supervisor(supervisor::Error {
+                component: Component::Api,
+                ..
+            }))
+        ));
+        assert_eq!(
+            health
+                .unhealthy()
+                .into_iter()
+                .map(|(component, _)| component)
+                .collect::<Vec<_>>(),
+            vec![Component::Api]
+        );
+    }
+}
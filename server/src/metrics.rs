@@ -0,0 +1,294 @@
+use std::{collections::BTreeMap, time::Instant};
+
+use async_trait::async_trait;
+use prometheus::{
+    core::{Collector, Desc},
+    proto::MetricFamily,
+    Encoder, Gauge, Histogram, HistogramOpts, IntCounterVec, IntGaugeVec, Opts, Registry,
+    TextEncoder,
+};
+use tokio::sync::watch;
+
+use crate::{
+    posts_storage::{Error, InsertPost},
+    supervisor::{Component, Status},
+};
+use hackernews_crawler::core::{DateTime, Post};
+
+/// Prometheus registry with all metrics of the app
+#[derive(Debug, Default, Clone)]
+pub struct Metrics {
+    registry: Registry,
+}
+
+impl Metrics {
+    pub fn registry(&self) -> &Registry {
+        &self.registry
+    }
+
+    /// Metrics in prometheus text exposition format
+    pub fn encode(&self) -> Result<String, prometheus::Error> {
+        let mut buffer = Vec::new();
+        TextEncoder::new().encode(&self.registry.gather(), &mut buffer)?;
+        String::from_utf8(buffer).map_err(|err| prometheus::Error::Msg(err.to_string()))
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct StorageMetrics {
+    pub insert_duration: Histogram,
+    pub inserted_posts: IntCounterVec,
+}
+
+impl Default for StorageMetrics {
+    fn default() -> Self {
+        Self {
+            insert_duration: Histogram::with_opts(HistogramOpts::new(
+                "storage_insert_duration_seconds",
+                "Duration of one post insert",
+            ))
+            .unwrap(),
+            inserted_posts: IntCounterVec::new(
+                Opts::new("storage_inserted_posts_total", "Inserted posts rows"),
+                &["first_page"],
+            )
+            .unwrap(),
+        }
+    }
+}
+
+impl StorageMetrics {
+    pub fn register(&self, registry: &Registry) -> Result<(), prometheus::Error> {
+        registry.register(Box::new(self.insert_duration.clone()))?;
+        registry.register(Box::new(self.inserted_posts.clone()))
+    }
+}
+
+/// [`InsertPost`] decorator, which measures every insert
+pub struct Measured<S> {
+    inner: S,
+    metrics: StorageMetrics,
+}
+
+impl<S> Measured<S> {
+    pub fn new(inner: S, metrics: StorageMetrics) -> Self {
+        Self { inner, metrics }
+    }
+
+    pub fn into_inner(self) -> S {
+        self.inner
+    }
+}
+
+#[async_trait]
+impl<S> InsertPost for Measured<S>
+where
+    S: InsertPost + Send + Sync,
+{
+    type Error = S::Error;
+
+    async fn insert_post<'l>(&'l self, post: Post, is_first_page: bool) -> Result<(), Error> {
+        let started = Instant::now();
+        let result = self.inner.insert_post(post, is_first_page).await;
+        self.metrics
+            .insert_duration
+            .observe(started.elapsed().as_secs_f64());
+
+        if result.is_ok() {
+            self.metrics
+                .inserted_posts
+                .with_label_values(&[&is_first_page.to_string()])
+                .inc();
+        }
+
+        result
+    }
+}
+
+/// Gauge with age of the latest complete snapshot, calculated on every scrape
+#[derive(Debug)]
+pub struct SnapshotAge {
+    latest_snapshot: watch::Receiver<Option<DateTime>>,
+    gauge: Gauge,
+}
+
+impl SnapshotAge {
+    pub fn new(latest_snapshot: watch::Receiver<Option<DateTime>>) -> Self {
+        Self {
+            latest_snapshot,
+            gauge: Gauge::new(
+                "snapshot_age_seconds",
+                "Age of the latest complete snapshot, NaN if there is none",
+            )
+            .unwrap(),
+        }
+    }
+}
+
+impl Collector for SnapshotAge {
+    fn desc(&self) -> Vec<&Desc> {
+        self.gauge.desc()
+    }
+
+    fn collect(&self) -> Vec<MetricFamily> {
+        let age = match *self.latest_snapshot.borrow() {
+            Some(latest_snapshot) => {
+                (chrono::Local::now().naive_utc() - latest_snapshot).num_milliseconds() as f64
+                    / 1000.0
+            }
+            None => f64::NAN,
+        };
+        self.gauge.set(age);
+        self.gauge.collect()
+    }
+}
+
+/// Gauge per supervised component, 1 while it is healthy, calculated on every scrape
+#[derive(Debug)]
+pub struct ComponentHealth {
+    components: watch::Receiver<BTreeMap<Component, Status>>,
+    gauge: IntGaugeVec,
+}
+
+impl ComponentHealth {
+    pub fn new(components: watch::Receiver<BTreeMap<Component, Status>>) -> Self {
+        Self {
+            components,
+            gauge: IntGaugeVec::new(
+                Opts::new(
+                    "component_healthy",
+                    "1 while the component is running, 0 while it restarts or failed",
+                ),
+                &["component"],
+            )
+            .unwrap(),
+        }
+    }
+}
+
+impl Collector for ComponentHealth {
+    fn desc(&self) -> Vec<&Desc> {
+        self.gauge.desc()
+    }
+
+    fn collect(&self) -> Vec<MetricFamily> {
+        let components = self.components.borrow();
+        for component in Component::ALL {
+            let is_healthy = components.get(&component).is_some_and(Status::is_healthy);
+            self.gauge
+                .with_label_values(&[&component.to_string()])
+                .set(is_healthy.into());
+        }
+        self.gauge.collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use super::*;
+
+    #[derive(Default)]
+    struct StorageMock {
+        inserted: Mutex<Vec<(Post, bool)>>,
+    }
+
+    #[async_trait]
+    impl InsertPost for StorageMock {
+        type Error = sqlx::Error;
+
+        async fn insert_post<'l>(&'l self, post: Post, is_first_page: bool) -> Result<(), Error> {
+            self.inserted.lock().unwrap().push((post, is_first_page));
+            Ok(())
+        }
+    }
+
+    fn get_post(post_id: i64) -> Post {
+        Post {
+            post_id,
+            title: "test".to_owned(),
+            author: "test".to_owned(),
+            url: "test".to_owned(),
+            link: None,
+            publication_moment: chrono::Local::now().naive_utc(),
+            last_snapshot_moment: chrono::Local::now().naive_utc(),
+            rank: None,
+            listing: None,
+            score: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_measured_insert() {
+        let metrics = Metrics::default();
+        let storage_metrics = StorageMetrics::default();
+        storage_metrics.register(metrics.registry()).unwrap();
+
+        let storage = Measured::new(StorageMock::default(), storage_metrics.clone());
+        storage.insert_post(get_post(1), true).await.unwrap();
+        storage.insert_post(get_post(2), false).await.unwrap();
+        storage.insert_post(get_post(3), false).await.unwrap();
+
+        assert_eq!(storage.into_inner().inserted.into_inner().unwrap().len(), 3);
+        assert_eq!(storage_metrics.insert_duration.get_sample_count(), 3);
+
+        let encoded = metrics.encode().unwrap();
+        assert!(encoded.contains(r#"storage_inserted_posts_total{first_page="true"} 1"#));
+        assert!(encoded.contains(r#"storage_inserted_posts_total{first_page="false"} 2"#));
+    }
+
+    #[test]
+    fn test_snapshot_age() {
+        let metrics = Metrics::default();
+        let (latest_snapshot, receiver) = watch::channel(None);
+        metrics
+            .registry()
+            .register(Box::new(SnapshotAge::new(receiver)))
+            .unwrap();
+
+        assert!(metrics
+            .encode()
+            .unwrap()
+            .contains("snapshot_age_seconds NaN"));
+
+        latest_snapshot.send_replace(Some(
+            chrono::Local::now().naive_utc() - chrono::Duration::seconds(120),
+        ));
+        let encoded = metrics.encode().unwrap();
+        let age: f64 = encoded
+            .lines()
+            .find_map(|line| line.strip_prefix("snapshot_age_seconds "))
+            .unwrap()
+            .parse()
+            .unwrap();
+        assert!((120.0..121.0).contains(&age));
+    }
+
+    #[test]
+    fn test_component_health() {
+        let metrics = Metrics::default();
+        let (components, receiver) = watch::channel(BTreeMap::new());
+        metrics
+            .registry()
+            .register(Box::new(ComponentHealth::new(receiver)))
+            .unwrap();
+
+        components.send_modify(|components| {
+            components.insert(Component::Api, Status::Running);
+            components.insert(
+                Component::Crawler,
+                Status::Restarting {
+                    restarts: 1,
+                    last_error: "database is locked".to_owned(),
+                },
+            );
+        });
+        let encoded = metrics.encode().unwrap();
+        assert!(encoded.contains(r#"component_healthy{component="api"} 1"#));
+        assert!(encoded.contains(r#"component_healthy{component="crawler"} 0"#));
+        // Not started yet
+        assert!(encoded.contains(r#"component_healthy{component="http"} 0"#));
+        assert!(encoded.contains(r#"component_healthy{component="feeds"} 0"#));
+    }
+}
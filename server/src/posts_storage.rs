@@ -0,0 +1,1782 @@
+use async_trait::async_trait;
+use futures::stream::BoxStream;
+
+use hackernews_crawler::core::{
+    DateTime, Digest, DigestPost, DigestSection, DomainPostRequest, DomainStats, Post, PostId,
+    PostSnapshot, PostStats, SnapshotChange, SnapshotDiff, TrendingPost, UserOrder,
+    UserPostRequest, UserStats,
+};
+
+#[async_trait]
+pub trait GetCurrentTopPosts {
+    type Error;
+    /// Posts of the latest first page snapshot by their rank there
+    async fn get_current_top_posts<'l>(
+        &'l self,
+    ) -> Result<BoxStream<'l, Result<Post, Self::Error>>, Self::Error>;
+}
+
+#[async_trait]
+pub trait GetUserPosts {
+    type Error;
+
+    async fn get_user_posts<'l>(
+        &'l self,
+        filter: UserPostRequest,
+    ) -> Result<BoxStream<'l, Result<Post, Self::Error>>, Self::Error>;
+}
+
+#[async_trait]
+pub trait GetDomainPosts {
+    type Error;
+
+    async fn get_domain_posts<'l>(
+        &'l self,
+        request: DomainPostRequest,
+    ) -> Result<BoxStream<'l, Result<Post, Self::Error>>, Self::Error>;
+}
+
+#[async_trait]
+pub trait GetTopDomains {
+    type Error;
+
+    /// Domains ranked by their appearances at the first page in `since..until`,
+    /// then by their posts there
+    async fn get_top_domains(
+        &self,
+        since: DateTime,
+        until: DateTime,
+        limit: u32,
+    ) -> Result<Vec<DomainStats>, Self::Error>;
+}
+
+#[async_trait]
+pub trait GetFirstPagePosts {
+    type Error;
+
+    /// The latest `limit` posts, which reached the first page, the ones
+    /// which reached it last go first
+    async fn get_first_page_posts(&self, limit: u32) -> Result<Vec<Post>, Self::Error>;
+}
+
+#[async_trait]
+pub trait GetLatestUserPosts {
+    type Error;
+
+    /// The latest `limit` posts of `filter`, newest first
+    async fn get_latest_user_posts(
+        &self,
+        filter: UserPostRequest,
+        limit: u32,
+    ) -> Result<Vec<Post>, Self::Error>;
+}
+
+#[async_trait]
+pub trait GetTrendingPosts {
+    type Error;
+
+    /// Posts, which were never at the first page, ranked by points per hour
+    /// between their first and latest snapshots in `since..=until`. Only posts
+    /// of the latest snapshot in the window count, others fell off the listing
+    async fn get_trending_posts(
+        &self,
+        since: DateTime,
+        until: DateTime,
+        limit: u32,
+    ) -> Result<Vec<TrendingPost>, Self::Error>;
+}
+
+#[async_trait]
+pub trait DiffSnapshots {
+    type Error;
+
+    /// Compares the `top` listing at `from` and `to`, every page of it taken
+    /// from the latest snapshot at or before them, which has the page,
+    /// `None` if there is no snapshot before one of them
+    async fn diff_snapshots(
+        &self,
+        from: DateTime,
+        to: DateTime,
+    ) -> Result<Option<SnapshotDiff>, Self::Error>;
+}
+
+#[async_trait]
+pub trait GetDigest {
+    type Error;
+
+    /// The best `limit` posts of each listing crawled in `since..until`,
+    /// ranked by their best rank there, then by minutes at the first page
+    async fn get_digest(
+        &self,
+        since: DateTime,
+        until: DateTime,
+        limit: u32,
+    ) -> Result<Digest, Self::Error>;
+}
+
+#[async_trait]
+pub trait GetPostStats {
+    type Error;
+
+    /// `None` if the post was never crawled
+    async fn get_post_stats(&self, post_id: PostId) -> Result<Option<PostStats>, Self::Error>;
+}
+
+#[async_trait]
+pub trait GetPostHistory {
+    type Error;
+
+    /// Samples of the post by time, empty if it was never crawled
+    async fn get_post_history(&self, post_id: PostId) -> Result<Vec<PostSnapshot>, Self::Error>;
+}
+
+#[async_trait]
+pub trait GetUserStats {
+    type Error;
+
+    /// Stats of all crawled posts of `user`, `None` if there are none
+    async fn get_user_stats(&self, user: String) -> Result<Option<UserStats>, Self::Error>;
+}
+
+#[async_trait]
+pub trait GetTopUsers {
+    type Error;
+
+    /// Authors of posts published in `since..until` ranked by `order`
+    async fn get_top_users(
+        &self,
+        since: DateTime,
+        until: DateTime,
+        order: UserOrder,
+        limit: u32,
+    ) -> Result<Vec<UserStats>, Self::Error>;
+}
+
+#[async_trait]
+pub trait InsertPost {
+    type Error;
+
+    async fn insert_post<'l>(&'l self, post: Post, is_first_page: bool) -> Result<(), Error>;
+}
+
+#[async_trait]
+pub trait GetLatestSnapshot {
+    async fn get_latest_snapshot(&self) -> Result<Option<DateTime>, Error>;
+}
+
+#[async_trait]
+pub trait BeginSnapshot {
+    type Snapshot: InsertPost + Send + Sync;
+
+    async fn begin_snapshot(&self) -> Result<Self::Snapshot, Error>;
+}
+
+pub mod sqlite {
+    use std::{str::FromStr, time::Duration};
+
+    use async_trait::async_trait;
+    use futures::stream::BoxStream;
+    use sqlx::{
+        sqlite::{SqliteConnectOptions, SqliteJournalMode, SqlitePoolOptions},
+        Sqlite, SqliteExecutor, Transaction,
+    };
+    use tokio::sync::Mutex;
+
+    pub use sqlx::sqlite::SqlitePool;
+
+    use crate::dump::Record;
+
+    use super::*;
+
+    /// Connect with WAL journal, so API readers aren't blocked by a long
+    /// snapshot transaction of the crawler
+    pub async fn connect(connect_str: &str) -> Result<SqlitePool, sqlx::Error> {
+        SqlitePool::connect_with(
+            SqliteConnectOptions::from_str(connect_str)?
+                .journal_mode(SqliteJournalMode::Wal)
+                .busy_timeout(Duration::from_secs(30)),
+        )
+        .await
+    }
+
+    /// Separate read-only pool of api, so api calls can't take connections
+    /// the crawler needs for its writes. It must be opened after migrations
+    pub async fn connect_read_only(
+        connect_str: &str,
+        max_connections: u32,
+    ) -> Result<SqlitePool, sqlx::Error> {
+        SqlitePoolOptions::new()
+            .max_connections(max_connections)
+            .acquire_timeout(Duration::from_secs(5))
+            .connect_with(
+                SqliteConnectOptions::from_str(connect_str)?
+                    .read_only(true)
+                    .busy_timeout(Duration::from_secs(30)),
+            )
+            .await
+    }
+
+    #[async_trait]
+    impl GetCurrentTopPosts for SqlitePool {
+        type Error = sqlx::Error;
+
+        async fn get_current_top_posts<'l>(
+            &'l self,
+        ) -> Result<BoxStream<'l, Result<Post, Self::Error>>, Self::Error> {
+            Ok(Box::pin(
+                sqlx::query_as::<_, Post>(r#"
+                    SELECT "posts".*, "ps"."rank", "ps"."listing"
+                    FROM "posts"
+                    INNER JOIN 
+                        "first_page_posts" AS "fpp" ON "posts"."post_id" = "fpp"."post_id" 
+                        AND "fpp"."snapshot_moment" = (SELECT MAX("snapshot_moment") FROM "first_page_posts")
+                    LEFT JOIN "post_snapshots" AS "ps" ON "ps"."post_id" = "posts"."post_id"
+                        AND "ps"."snapshot_moment" = "fpp"."snapshot_moment"
+                        AND "ps"."listing" = 'top'
+                    ORDER BY "ps"."rank" IS NULL, "ps"."rank", "posts"."post_id"
+                "#).fetch(self),
+            ))
+        }
+    }
+    #[async_trait]
+    impl GetUserPosts for SqlitePool {
+        type Error = sqlx::Error;
+
+        async fn get_user_posts<'l>(
+            &'l self,
+            filter: UserPostRequest,
+        ) -> Result<BoxStream<'l, Result<Post, Self::Error>>, Self::Error> {
+            Ok(Box::pin(
+                    sqlx::query_as::<_, Post>(
+                    r#"SELECT "posts".*
+                        FROM "posts"
+                        LEFT JOIN (
+                            SELECT "post_id", SUM("minutes") AS "minutes" FROM "first_page_dwell" GROUP BY "post_id"
+                        ) AS "dwell" ON "dwell"."post_id" = "posts"."post_id"
+                        WHERE "author" = ?1
+                          AND CASE ?2
+                                  WHEN 'WasAtFirstPage' THEN "dwell"."post_id" IS NOT NULL
+                                  WHEN 'All' THEN TRUE
+                                  ELSE FALSE
+                          END
+                        ORDER BY CASE ?2 WHEN 'WasAtFirstPage' THEN "dwell"."minutes" END DESC,
+                                 "publication_moment"
+                        "#,
+                    )
+                    .bind(filter.get_user().to_string())
+                    .bind(<&'static str>::from(filter))
+                .fetch(self)
+            ))
+        }
+    }
+
+    #[async_trait]
+    impl GetDomainPosts for SqlitePool {
+        type Error = sqlx::Error;
+
+        async fn get_domain_posts<'l>(
+            &'l self,
+            request: DomainPostRequest,
+        ) -> Result<BoxStream<'l, Result<Post, Self::Error>>, Self::Error> {
+            Ok(Box::pin(
+                sqlx::query_as::<_, Post>(
+                    r#"SELECT *
+                        FROM "posts"
+                        WHERE "domain" = ?1
+                          AND (NOT ?2 OR "post_id" IN (SELECT "post_id" FROM "first_page_posts"))
+                        ORDER BY "publication_moment"
+                    "#,
+                )
+                .bind(request.domain)
+                .bind(request.was_at_first_page)
+                .fetch(self),
+            ))
+        }
+    }
+
+    #[async_trait]
+    impl GetTopDomains for SqlitePool {
+        type Error = sqlx::Error;
+
+        async fn get_top_domains(
+            &self,
+            since: DateTime,
+            until: DateTime,
+            limit: u32,
+        ) -> Result<Vec<DomainStats>, Self::Error> {
+            sqlx::query_as::<_, DomainStats>(
+                r#"SELECT "posts"."domain" AS "domain",
+                        COUNT(DISTINCT "posts"."post_id") AS "posts",
+                        COUNT(*) AS "appearances",
+                        MAX("fpp"."snapshot_moment") AS "last_seen"
+                    FROM "first_page_posts" AS "fpp"
+                    INNER JOIN "posts" ON "posts"."post_id" = "fpp"."post_id"
+                    WHERE "posts"."domain" IS NOT NULL
+                      AND "fpp"."snapshot_moment" >= ?1
+                      AND "fpp"."snapshot_moment" < ?2
+                    GROUP BY "posts"."domain"
+                    ORDER BY "appearances" DESC, "posts" DESC, "domain"
+                    LIMIT ?3
+                "#,
+            )
+            .bind(since)
+            .bind(until)
+            .bind(limit)
+            .fetch_all(self)
+            .await
+        }
+    }
+
+    #[async_trait]
+    impl GetFirstPagePosts for SqlitePool {
+        type Error = sqlx::Error;
+
+        async fn get_first_page_posts(&self, limit: u32) -> Result<Vec<Post>, Self::Error> {
+            sqlx::query_as::<_, Post>(
+                r#"SELECT "posts".*
+                    FROM "posts"
+                    INNER JOIN (
+                        SELECT "post_id", MIN("snapshot_moment") AS "reached"
+                        FROM "first_page_posts"
+                        GROUP BY "post_id"
+                    ) AS "fpp" ON "fpp"."post_id" = "posts"."post_id"
+                    ORDER BY "fpp"."reached" DESC, "posts"."publication_moment" DESC
+                    LIMIT ?1
+                "#,
+            )
+            .bind(limit)
+            .fetch_all(self)
+            .await
+        }
+    }
+
+    #[async_trait]
+    impl GetLatestUserPosts for SqlitePool {
+        type Error = sqlx::Error;
+
+        async fn get_latest_user_posts(
+            &self,
+            filter: UserPostRequest,
+            limit: u32,
+        ) -> Result<Vec<Post>, Self::Error> {
+            sqlx::query_as::<_, Post>(
+                r#"SELECT *
+                    FROM "posts"
+                    WHERE "author" = ?1
+                      AND CASE ?2
+                              WHEN 'WasAtFirstPage' THEN "post_id" IN (SELECT "post_id" FROM "first_page_posts")
+                              WHEN 'All' THEN TRUE
+                              ELSE FALSE
+                      END
+                    ORDER BY "publication_moment" DESC
+                    LIMIT ?3
+                "#,
+            )
+            .bind(filter.get_user().to_string())
+            .bind(<&'static str>::from(filter))
+            .bind(limit)
+            .fetch_all(self)
+            .await
+        }
+    }
+
+    #[async_trait]
+    impl GetTrendingPosts for SqlitePool {
+        type Error = sqlx::Error;
+
+        async fn get_trending_posts(
+            &self,
+            since: DateTime,
+            until: DateTime,
+            limit: u32,
+        ) -> Result<Vec<TrendingPost>, Self::Error> {
+            sqlx::query_as::<_, TrendingPost>(
+                r#"WITH "samples" AS (
+                        SELECT * FROM "post_snapshots"
+                        WHERE "listing" = 'top' AND "snapshot_moment" >= ?1 AND "snapshot_moment" <= ?2
+                          AND "post_id" NOT IN (SELECT "post_id" FROM "first_page_posts")
+                    ),
+                    "bounds" AS (
+                        SELECT "post_id",
+                            MIN("snapshot_moment") AS "first_moment",
+                            MAX("snapshot_moment") AS "last_moment"
+                        FROM "samples"
+                        GROUP BY "post_id"
+                        HAVING "last_moment" = (SELECT MAX("snapshot_moment") FROM "samples")
+                           AND "first_moment" < "last_moment"
+                    )
+                    SELECT "posts"."post_id", "posts"."title", "posts"."author", "posts"."url",
+                        "posts"."link", "posts"."publication_moment", "posts"."last_snapshot_moment",
+                        "last"."rank" AS "rank",
+                        "last"."score" AS "score",
+                        ("last"."score" - "first"."score") / "hours" AS "points_per_hour",
+                        ("first"."rank" - "last"."rank") / "hours" AS "rank_velocity"
+                    FROM (
+                        SELECT *,
+                            (julianday("last_moment") - julianday("first_moment")) * 24 AS "hours"
+                        FROM "bounds"
+                    ) AS "bounds"
+                    INNER JOIN "samples" AS "first"
+                        ON "first"."post_id" = "bounds"."post_id"
+                        AND "first"."snapshot_moment" = "bounds"."first_moment"
+                    INNER JOIN "samples" AS "last"
+                        ON "last"."post_id" = "bounds"."post_id"
+                        AND "last"."snapshot_moment" = "bounds"."last_moment"
+                    INNER JOIN "posts" ON "posts"."post_id" = "bounds"."post_id"
+                    ORDER BY "points_per_hour" DESC, "rank_velocity" DESC, "posts"."post_id"
+                    LIMIT ?3
+                "#,
+            )
+            .bind(since)
+            .bind(until)
+            .bind(limit)
+            .fetch_all(self)
+            .await
+        }
+    }
+
+    #[async_trait]
+    impl DiffSnapshots for SqlitePool {
+        type Error = sqlx::Error;
+
+        async fn diff_snapshots(
+            &self,
+            from: DateTime,
+            to: DateTime,
+        ) -> Result<Option<SnapshotDiff>, Self::Error> {
+            let snapshot_before = |moment| {
+                sqlx::query_scalar::<_, Option<DateTime>>(
+                    r#"SELECT MAX("snapshot_moment") FROM "post_snapshots"
+                        WHERE "listing" = 'top' AND "rank" IS NOT NULL AND "snapshot_moment" <= ?1"#,
+                )
+                .bind(moment)
+                .fetch_one(self)
+            };
+            let (Some(from), Some(to)) = (snapshot_before(from).await?, snapshot_before(to).await?)
+            else {
+                return Ok(None);
+            };
+
+            // Crawls may cover different pages, e.g. page 1 every minute and
+            // pages 2-10 every ten minutes, so every page of a side comes from
+            // the latest snapshot, which has it. Pages only one side has are
+            // ignored instead of being reported as left.
+            let changes = sqlx::query_as::<_, SnapshotChange>(
+                r#"WITH "moments"("side", "moment") AS (VALUES ('old', ?1), ('new', ?2)),
+                    "pages" AS (
+                        SELECT DISTINCT "snapshot_moment", ("rank" - 1) / 30 AS "page"
+                        FROM "post_snapshots"
+                        WHERE "listing" = 'top' AND "rank" IS NOT NULL AND "snapshot_moment" <= MAX(?1, ?2)
+                    ),
+                    "latest" AS (
+                        SELECT "moments"."side", "pages"."page", MAX("pages"."snapshot_moment") AS "snapshot_moment"
+                        FROM "moments"
+                        INNER JOIN "pages" ON "pages"."snapshot_moment" <= "moments"."moment"
+                        GROUP BY "moments"."side", "pages"."page"
+                    ),
+                    "listing" AS (
+                        SELECT "latest"."side", "latest"."page", "post_snapshots"."post_id", "post_snapshots"."rank",
+                            ROW_NUMBER() OVER (
+                                PARTITION BY "latest"."side", "post_snapshots"."post_id"
+                                ORDER BY "latest"."snapshot_moment" DESC
+                            ) AS "sample"
+                        FROM "latest"
+                        INNER JOIN "post_snapshots" ON "post_snapshots"."snapshot_moment" = "latest"."snapshot_moment"
+                            AND "post_snapshots"."listing" = 'top'
+                            AND ("post_snapshots"."rank" - 1) / 30 = "latest"."page"
+                    ),
+                    "old_top" AS (
+                        SELECT "post_id", "rank" FROM "listing"
+                        WHERE "side" = 'old' AND "sample" = 1
+                            AND "page" IN (SELECT "page" FROM "latest" WHERE "side" = 'new')
+                    ),
+                    "new_top" AS (
+                        SELECT "post_id", "rank" FROM "listing"
+                        WHERE "side" = 'new' AND "sample" = 1
+                            AND "page" IN (SELECT "page" FROM "latest" WHERE "side" = 'old')
+                    ),
+                    "changes" AS (
+                        SELECT "old_top"."post_id", "old_top"."rank" AS "old_rank", "new_top"."rank" AS "new_rank"
+                        FROM "old_top"
+                        LEFT JOIN "new_top" ON "new_top"."post_id" = "old_top"."post_id"
+                        UNION ALL
+                        SELECT "post_id", NULL, "rank" FROM "new_top"
+                        WHERE "post_id" NOT IN (SELECT "post_id" FROM "old_top")
+                    )
+                    SELECT "posts".*, "changes"."old_rank", "changes"."new_rank"
+                    FROM "changes"
+                    INNER JOIN "posts" ON "posts"."post_id" = "changes"."post_id"
+                    WHERE "changes"."old_rank" IS NOT "changes"."new_rank"
+                    ORDER BY "changes"."new_rank" IS NULL, "changes"."new_rank", "changes"."old_rank"
+                "#,
+            )
+            .bind(from)
+            .bind(to)
+            .fetch_all(self)
+            .await?;
+
+            Ok(Some(SnapshotDiff { from, to, changes }))
+        }
+    }
+
+    #[async_trait]
+    impl GetDigest for SqlitePool {
+        type Error = sqlx::Error;
+
+        async fn get_digest(
+            &self,
+            since: DateTime,
+            until: DateTime,
+            limit: u32,
+        ) -> Result<Digest, Self::Error> {
+            let posts = sqlx::query_as::<_, DigestPost>(
+                r#"WITH "ranks" AS (
+                        SELECT "listing", "post_id", MIN("rank") AS "best_rank"
+                        FROM "post_snapshots"
+                        WHERE "listing" IS NOT NULL AND "rank" IS NOT NULL
+                          AND "snapshot_moment" >= ?1 AND "snapshot_moment" < ?2
+                        GROUP BY "listing", "post_id"
+                    ),
+                    "dwell" AS (
+                        SELECT "post_id", SUM("minutes") AS "minutes"
+                        FROM "first_page_dwell"
+                        WHERE "snapshot_moment" >= ?1 AND "snapshot_moment" < ?2
+                        GROUP BY "post_id"
+                    ),
+                    "ranked" AS (
+                        SELECT "ranks".*,
+                            CAST(COALESCE("dwell"."minutes", 0) AS REAL) AS "front_page_minutes",
+                            ROW_NUMBER() OVER (
+                                PARTITION BY "ranks"."listing"
+                                ORDER BY "ranks"."best_rank", "dwell"."minutes" DESC, "ranks"."post_id"
+                            ) AS "place"
+                        FROM "ranks"
+                        LEFT JOIN "dwell" ON "dwell"."post_id" = "ranks"."post_id"
+                    )
+                    SELECT "posts".*, "ranked"."listing", "ranked"."best_rank", "ranked"."front_page_minutes"
+                    FROM "ranked"
+                    INNER JOIN "posts" ON "posts"."post_id" = "ranked"."post_id"
+                    WHERE "ranked"."place" <= ?3
+                    ORDER BY "ranked"."place"
+                "#,
+            )
+            .bind(since)
+            .bind(until)
+            .bind(limit)
+            .fetch_all(self)
+            .await?;
+
+            let mut sections: Vec<DigestSection> = Vec::new();
+            for post in posts {
+                let listing = post.post.listing.unwrap_or_default();
+                match sections
+                    .iter_mut()
+                    .find(|section| section.listing == listing)
+                {
+                    Some(section) => section.posts.push(post),
+                    None => sections.push(DigestSection {
+                        listing,
+                        posts: vec![post],
+                    }),
+                }
+            }
+            sections.sort_by_key(|section| section.listing);
+
+            Ok(Digest {
+                since,
+                until,
+                sections,
+            })
+        }
+    }
+
+    #[async_trait]
+    impl GetPostStats for SqlitePool {
+        type Error = sqlx::Error;
+
+        async fn get_post_stats(&self, post_id: PostId) -> Result<Option<PostStats>, Self::Error> {
+            sqlx::query_as::<_, PostStats>(
+                r#"SELECT "posts"."post_id",
+                        MIN("dwell"."snapshot_moment") AS "first_seen",
+                        MAX("dwell"."snapshot_moment") AS "last_seen",
+                        CAST(COALESCE(SUM("dwell"."minutes"), 0) AS REAL) AS "front_page_minutes",
+                        COUNT("dwell"."snapshot_moment") AS "appearances",
+                        (
+                            SELECT MIN("rank") FROM "post_snapshots" WHERE "post_id" = ?1 AND "listing" = 'top'
+                        ) AS "best_rank"
+                    FROM "posts"
+                    LEFT JOIN "first_page_dwell" AS "dwell" ON "dwell"."post_id" = "posts"."post_id"
+                    WHERE "posts"."post_id" = ?1
+                    GROUP BY "posts"."post_id"
+                "#,
+            )
+            .bind(post_id)
+            .fetch_optional(self)
+            .await
+        }
+    }
+
+    #[async_trait]
+    impl GetPostHistory for SqlitePool {
+        type Error = sqlx::Error;
+
+        /// Moments of first page snapshots are merged with samples, old
+        /// first page snapshots have no samples
+        async fn get_post_history(
+            &self,
+            post_id: PostId,
+        ) -> Result<Vec<PostSnapshot>, Self::Error> {
+            sqlx::query_as::<_, PostSnapshot>(
+                r#"SELECT "moments"."snapshot_moment",
+                        "ps"."listing",
+                        "ps"."rank",
+                        "ps"."score",
+                        EXISTS(
+                            SELECT 1 FROM "first_page_posts" AS "fpp"
+                            WHERE "fpp"."post_id" = ?1 AND "fpp"."snapshot_moment" = "moments"."snapshot_moment"
+                        ) AS "was_at_first_page"
+                    FROM (
+                        SELECT "snapshot_moment" FROM "post_snapshots" WHERE "post_id" = ?1
+                        UNION
+                        SELECT "snapshot_moment" FROM "first_page_posts" WHERE "post_id" = ?1
+                    ) AS "moments"
+                    LEFT JOIN "post_snapshots" AS "ps" ON "ps"."post_id" = ?1
+                        AND "ps"."snapshot_moment" = "moments"."snapshot_moment"
+                    ORDER BY "moments"."snapshot_moment"
+                "#,
+            )
+            .bind(post_id)
+            .fetch_all(self)
+            .await
+        }
+    }
+
+    /// Stats per author of posts published in `?1..?2`, an unset bound or
+    /// author `?3` doesn't filter
+    const USER_STATS: &str = r#"
+        SELECT "posts"."author" AS "user",
+            COUNT(*) AS "posts",
+            COUNT("dwell"."post_id") AS "first_page_posts",
+            MIN("ranks"."best_rank") AS "best_rank",
+            CAST(COALESCE(SUM("dwell"."minutes"), 0) AS REAL) AS "front_page_minutes",
+            AVG("posts"."score") AS "average_score"
+        FROM "posts"
+        LEFT JOIN (
+            SELECT "post_id", SUM("minutes") AS "minutes" FROM "first_page_dwell" GROUP BY "post_id"
+        ) AS "dwell" ON "dwell"."post_id" = "posts"."post_id"
+        LEFT JOIN (
+            SELECT "post_id", MIN("rank") AS "best_rank" FROM "post_snapshots"
+            WHERE "listing" = 'top'
+            GROUP BY "post_id"
+        ) AS "ranks" ON "ranks"."post_id" = "posts"."post_id"
+        WHERE (?1 IS NULL OR "posts"."publication_moment" >= ?1)
+          AND (?2 IS NULL OR "posts"."publication_moment" < ?2)
+          AND (?3 IS NULL OR "posts"."author" = ?3)
+        GROUP BY "posts"."author"
+    "#;
+
+    #[async_trait]
+    impl GetUserStats for SqlitePool {
+        type Error = sqlx::Error;
+
+        async fn get_user_stats(&self, user: String) -> Result<Option<UserStats>, Self::Error> {
+            sqlx::query_as::<_, UserStats>(USER_STATS)
+                .bind(None::<DateTime>)
+                .bind(None::<DateTime>)
+                .bind(user)
+                .fetch_optional(self)
+                .await
+        }
+    }
+
+    #[async_trait]
+    impl GetTopUsers for SqlitePool {
+        type Error = sqlx::Error;
+
+        async fn get_top_users(
+            &self,
+            since: DateTime,
+            until: DateTime,
+            order: UserOrder,
+            limit: u32,
+        ) -> Result<Vec<UserStats>, Self::Error> {
+            let order_by = match order {
+                UserOrder::Posts => r#""posts" DESC"#,
+                UserOrder::FirstPagePosts => r#""first_page_posts" DESC"#,
+                UserOrder::BestRank => r#""best_rank" IS NULL, "best_rank""#,
+                UserOrder::FrontPageMinutes => r#""front_page_minutes" DESC"#,
+                UserOrder::AverageScore => r#""average_score" IS NULL, "average_score" DESC"#,
+            };
+            sqlx::query_as::<_, UserStats>(&format!(
+                r#"{USER_STATS} ORDER BY {order_by}, "posts" DESC, "user" LIMIT ?4"#
+            ))
+            .bind(since)
+            .bind(until)
+            .bind(None::<String>)
+            .bind(limit)
+            .fetch_all(self)
+            .await
+        }
+    }
+
+    /// Store domains of posts crawled before domains were stored
+    pub async fn backfill_domains(pool: &SqlitePool) -> Result<u64, sqlx::Error> {
+        let links: Vec<(i64, String)> = sqlx::query_as(
+            r#"SELECT "post_id", "link" FROM "posts"
+                WHERE "domain" IS NULL AND "link" LIKE 'http%'"#,
+        )
+        .fetch_all(pool)
+        .await?;
+
+        let mut transaction = pool.begin().await?;
+        let mut updated = 0;
+        for (post_id, link) in links {
+            let Some(domain) = hackernews_crawler::core::link_domain(&link) else {
+                continue;
+            };
+            updated += sqlx::query(r#"UPDATE "posts" SET "domain" = ?1 WHERE "post_id" = ?2"#)
+                .bind(domain)
+                .bind(post_id)
+                .execute(&mut transaction)
+                .await?
+                .rows_affected();
+        }
+        transaction.commit().await?;
+
+        Ok(updated)
+    }
+
+    /// Records of posts with their samples in `since..until`, in order of
+    /// snapshots. Moments of posts without samples are records too, so
+    /// posts crawled before samples were stored aren't lost
+    pub fn export_records<'l>(
+        pool: &'l SqlitePool,
+        since: Option<DateTime>,
+        until: Option<DateTime>,
+    ) -> BoxStream<'l, Result<Record, sqlx::Error>> {
+        sqlx::query_as(
+            r#"
+                WITH "moments" AS (
+                    SELECT "post_id", "snapshot_moment" FROM "post_snapshots"
+                    UNION
+                    SELECT "post_id", "snapshot_moment" FROM "first_page_posts"
+                    UNION
+                    SELECT "post_id", "last_snapshot_moment" FROM "posts"
+                    WHERE "last_snapshot_moment" IS NOT NULL
+                )
+                SELECT "p"."post_id", "p"."title", "p"."author", "p"."url", "p"."link", "p"."domain", "p"."publication_moment",
+                       "m"."snapshot_moment",
+                       EXISTS (
+                           SELECT 1 FROM "first_page_posts" AS "fpp"
+                           WHERE "fpp"."post_id" = "m"."post_id" AND "fpp"."snapshot_moment" = "m"."snapshot_moment"
+                       ) AS "was_at_first_page",
+                       "ps"."listing", "ps"."rank", "ps"."score"
+                FROM "moments" AS "m"
+                         INNER JOIN "posts" AS "p" ON "p"."post_id" = "m"."post_id"
+                         LEFT JOIN "post_snapshots" AS "ps"
+                                   ON "ps"."post_id" = "m"."post_id" AND "ps"."snapshot_moment" = "m"."snapshot_moment"
+                WHERE (?1 IS NULL OR "m"."snapshot_moment" >= ?1)
+                  AND (?2 IS NULL OR "m"."snapshot_moment" < ?2)
+                ORDER BY "m"."snapshot_moment", "p"."post_id"
+            "#,
+        )
+        .bind(since)
+        .bind(until)
+        .fetch(pool)
+    }
+
+    /// Stores the record through `posts_view`, like the crawler does.
+    /// Records, which are stored already, are skipped, so an import can be
+    /// repeated. `false` if the record was skipped
+    pub async fn import_record(
+        transaction: &mut Transaction<'_, Sqlite>,
+        record: Record,
+    ) -> Result<bool, sqlx::Error> {
+        let has_sample =
+            record.was_at_first_page || record.rank.is_some() || record.score.is_some();
+        let is_stored: bool = sqlx::query_scalar(
+            r#"
+                SELECT EXISTS (SELECT 1 FROM "first_page_posts" WHERE "post_id" = ?1 AND "snapshot_moment" = ?2)
+                    OR EXISTS (SELECT 1 FROM "post_snapshots" WHERE "post_id" = ?1 AND "snapshot_moment" = ?2)
+                    OR (NOT ?3 AND EXISTS (
+                        SELECT 1 FROM "posts" WHERE "post_id" = ?1 AND "last_snapshot_moment" >= ?2
+                    ))
+            "#,
+        )
+        .bind(record.post_id)
+        .bind(record.snapshot_moment)
+        .bind(has_sample)
+        .fetch_one(&mut *transaction)
+        .await?;
+        if is_stored {
+            return Ok(false);
+        }
+
+        let is_first_page = record.was_at_first_page;
+        insert_post(&mut *transaction, record.into(), is_first_page).await?;
+        Ok(true)
+    }
+
+    #[tracing::instrument(skip_all, fields(post_id = post.post_id, is_first_page))]
+    async fn insert_post<'e>(
+        executor: impl SqliteExecutor<'e>,
+        post: Post,
+        is_first_page: bool,
+    ) -> Result<(), sqlx::Error> {
+        let domain = post.domain();
+        sqlx::query!(
+            r#"
+                INSERT INTO
+                    "posts_view" ("post_id", "title", "author", "url", "link", "domain", "publication_moment", "last_snapshot_moment", "was_at_first_page", "rank", "listing", "score")
+                VALUES
+                    (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12);
+            "#,
+            post.post_id,
+            post.title,
+            post.author,
+            post.url,
+            post.link,
+            domain,
+            post.publication_moment,
+            post.last_snapshot_moment,
+            is_first_page,
+            post.rank,
+            post.listing,
+            post.score,
+        )
+        .execute(executor)
+        .await?;
+
+        Ok(())
+    }
+
+    #[async_trait]
+    impl InsertPost for SqlitePool {
+        type Error = sqlx::Error;
+
+        async fn insert_post<'l>(
+            &'l self,
+            post: Post,
+            is_first_page: bool,
+        ) -> Result<(), Self::Error> {
+            insert_post(self, post, is_first_page).await
+        }
+    }
+
+    /// All posts of one crawl, they become visible together on
+    /// [`Snapshot::commit`] and are rolled back if the snapshot is dropped
+    pub struct Snapshot {
+        transaction: Mutex<Transaction<'static, Sqlite>>,
+    }
+
+    impl Snapshot {
+        pub async fn commit(self) -> Result<(), sqlx::Error> {
+            self.transaction.into_inner().commit().await
+        }
+    }
+
+    #[async_trait]
+    impl InsertPost for Snapshot {
+        type Error = sqlx::Error;
+
+        async fn insert_post<'l>(
+            &'l self,
+            post: Post,
+            is_first_page: bool,
+        ) -> Result<(), Self::Error> {
+            insert_post(&mut *self.transaction.lock().await, post, is_first_page).await
+        }
+    }
+
+    #[async_trait]
+    impl GetLatestSnapshot for SqlitePool {
+        async fn get_latest_snapshot(&self) -> Result<Option<DateTime>, sqlx::Error> {
+            sqlx::query_scalar(r#"SELECT MAX("snapshot_moment") FROM "first_page_posts""#)
+                .fetch_one(self)
+                .await
+        }
+    }
+
+    #[async_trait]
+    impl BeginSnapshot for SqlitePool {
+        type Snapshot = Snapshot;
+
+        async fn begin_snapshot(&self) -> Result<Self::Snapshot, sqlx::Error> {
+            Ok(Snapshot {
+                transaction: Mutex::new(self.begin().await?),
+            })
+        }
+    }
+
+    #[cfg(test)]
+    mod test {
+        use futures::StreamExt;
+
+        use hackernews_crawler::core::Listing;
+
+        use super::*;
+
+        fn get_rnd_post() -> Post {
+            use rand::Rng;
+            let mut rnd = rand::thread_rng();
+
+            Post {
+                post_id: rnd.gen(),
+                title: "test".to_owned(),
+                author: "test".to_owned(),
+                url: "test".to_owned(),
+                link: None,
+                publication_moment: chrono::Local::now().naive_utc(),
+                last_snapshot_moment: chrono::Local::now().naive_utc(),
+                rank: None,
+                listing: None,
+                score: None,
+            }
+        }
+
+        const DAY: i64 = 24 * 60;
+
+        /// Moment `minutes` after noon of the first test day
+        fn moment(minutes: i64) -> DateTime {
+            chrono::NaiveDate::from_ymd_opt(2023, 1, 1)
+                .unwrap()
+                .and_hms_opt(12, 0, 0)
+                .unwrap()
+                + chrono::Duration::minutes(minutes)
+        }
+
+        /// Post published at `moment(0)` and sampled at `moment(minutes)`,
+        /// `rank` is at the front page listing
+        fn post(post_id: PostId, minutes: i64, rank: Option<i64>, score: Option<i64>) -> Post {
+            Post {
+                post_id,
+                publication_moment: moment(0),
+                last_snapshot_moment: moment(minutes),
+                rank,
+                listing: rank.map(|_| Listing::Top),
+                score,
+                ..get_rnd_post()
+            }
+        }
+
+        async fn get_storage() -> SqlitePool {
+            let storage = SqlitePool::connect(":memory:").await.unwrap();
+
+            sqlx::migrate!()
+                .run(&mut storage.acquire().await.unwrap())
+                .await
+                .unwrap();
+
+            storage
+        }
+
+        /// Snapshot transaction needs a second connection to observe it,
+        /// so in-memory database isn't enough here
+        async fn get_file_storage() -> SqlitePool {
+            let path = std::env::temp_dir().join(format!(
+                "hackernews-crawler-test-{}.db",
+                rand::random::<u64>()
+            ));
+            let storage = connect(&format!("sqlite:{}?mode=rwc", path.display()))
+                .await
+                .unwrap();
+
+            sqlx::migrate!()
+                .run(&mut storage.acquire().await.unwrap())
+                .await
+                .unwrap();
+
+            storage
+        }
+
+        async fn get_top_posts_ids(storage: &SqlitePool) -> Vec<i64> {
+            storage
+                .get_current_top_posts()
+                .await
+                .unwrap()
+                .map(Result::unwrap)
+                .map(|post| post.post_id)
+                .collect::<Vec<_>>()
+                .await
+        }
+
+        #[tokio::test]
+        async fn test_snapshot_commit() {
+            let storage = get_file_storage().await;
+
+            let snapshot = storage.begin_snapshot().await.unwrap();
+            let last_snapshot_moment = chrono::Local::now().naive_utc();
+            for post in (0..10).map(|post_id| Post {
+                post_id,
+                last_snapshot_moment,
+                ..get_rnd_post()
+            }) {
+                snapshot.insert_post(post, true).await.unwrap();
+            }
+            assert_eq!(
+                get_top_posts_ids(&storage).await,
+                Vec::<i64>::new(),
+                "snapshot is visible before commit"
+            );
+
+            snapshot.commit().await.unwrap();
+            assert_eq!(
+                get_top_posts_ids(&storage).await,
+                (0..10).collect::<Vec<_>>()
+            );
+        }
+
+        #[tokio::test]
+        async fn test_snapshot_rollback() {
+            let storage = get_file_storage().await;
+
+            let snapshot = storage.begin_snapshot().await.unwrap();
+            let last_snapshot_moment = chrono::Local::now().naive_utc();
+            for post in (0..10).map(|post_id| Post {
+                post_id,
+                last_snapshot_moment,
+                ..get_rnd_post()
+            }) {
+                snapshot.insert_post(post, true).await.unwrap();
+            }
+            drop(snapshot);
+
+            assert_eq!(get_top_posts_ids(&storage).await, Vec::<i64>::new());
+        }
+
+        #[tokio::test]
+        async fn test_read_only_pool() {
+            let path = std::env::temp_dir().join(format!(
+                "hackernews-crawler-test-{}.db",
+                rand::random::<u64>()
+            ));
+            let connect_str = format!("sqlite:{}?mode=rwc", path.display());
+            let storage = connect(&connect_str).await.unwrap();
+            sqlx::migrate!()
+                .run(&mut storage.acquire().await.unwrap())
+                .await
+                .unwrap();
+            let api_storage = connect_read_only(&connect_str, 1).await.unwrap();
+
+            assert!(api_storage.insert_post(get_rnd_post(), true).await.is_err());
+
+            // Api pool is exhausted, but the crawler still writes
+            let _reader = api_storage.acquire().await.unwrap();
+            let snapshot = storage.begin_snapshot().await.unwrap();
+            snapshot.insert_post(get_rnd_post(), true).await.unwrap();
+            snapshot.commit().await.unwrap();
+        }
+
+        fn get_link_post(post_id: PostId, link: &str, minutes: i64) -> Post {
+            Post {
+                link: Some(link.to_owned()),
+                publication_moment: moment(minutes),
+                ..post(post_id, minutes, None, None)
+            }
+        }
+
+        #[tokio::test]
+        async fn test_domains() {
+            let storage = get_storage().await;
+
+            // Post 1 is at the first page in three snapshots
+            for (post, is_first_page) in [
+                (get_link_post(1, "https://blog.example.com/a", 0), true),
+                (get_link_post(1, "https://blog.example.com/a", DAY), true),
+                (
+                    get_link_post(1, "https://blog.example.com/a", 2 * DAY),
+                    true,
+                ),
+                (get_link_post(2, "https://www.example.com/b", DAY), false),
+                (get_link_post(3, "https://other.org/c", DAY), true),
+                (get_link_post(4, "https://other.org/d", 2 * DAY), true),
+                (get_link_post(5, "https://third.net/", 4 * DAY), true),
+                (get_link_post(6, "item?id=6", DAY), true),
+            ] {
+                storage.insert_post(post, is_first_page).await.unwrap();
+            }
+
+            let domain_posts = |was_at_first_page| {
+                let storage = &storage;
+                async move {
+                    storage
+                        .get_domain_posts(DomainPostRequest {
+                            domain: "example.com".to_owned(),
+                            was_at_first_page,
+                        })
+                        .await
+                        .unwrap()
+                        .map(|post| post.unwrap().post_id)
+                        .collect::<Vec<_>>()
+                        .await
+                }
+            };
+            assert_eq!(domain_posts(false).await, vec![1, 2]);
+            assert_eq!(domain_posts(true).await, vec![1]);
+
+            let top_domains = storage
+                .get_top_domains(moment(0), moment(3 * DAY), 10)
+                .await
+                .unwrap()
+                .into_iter()
+                .map(|stats| {
+                    (
+                        stats.domain,
+                        stats.posts,
+                        stats.appearances,
+                        stats.last_seen,
+                    )
+                })
+                .collect::<Vec<_>>();
+            // Appearances outrank distinct posts
+            assert_eq!(
+                top_domains,
+                vec![
+                    ("example.com".to_owned(), 1, 3, moment(2 * DAY)),
+                    ("other.org".to_owned(), 2, 2, moment(2 * DAY)),
+                ]
+            );
+            assert_eq!(
+                storage
+                    .get_top_domains(moment(0), moment(3 * DAY), 1)
+                    .await
+                    .unwrap()
+                    .len(),
+                1
+            );
+        }
+
+        #[tokio::test]
+        async fn test_backfill_domains() {
+            let storage = get_storage().await;
+            sqlx::query(
+                r#"INSERT INTO "posts" ("post_id", "title", "author", "url", "link", "publication_moment")
+                    VALUES (1, '', '', '', 'https://www.example.com/', '2023-01-01 00:00:00'),
+                           (2, '', '', '', 'item?id=2', '2023-01-01 00:00:00')"#,
+            )
+            .execute(&storage)
+            .await
+            .unwrap();
+
+            assert_eq!(backfill_domains(&storage).await.unwrap(), 1);
+            assert_eq!(backfill_domains(&storage).await.unwrap(), 0);
+            let domains: Vec<(Option<String>,)> =
+                sqlx::query_as(r#"SELECT "domain" FROM "posts" ORDER BY "post_id""#)
+                    .fetch_all(&storage)
+                    .await
+                    .unwrap();
+            assert_eq!(domains, vec![(Some("example.com".to_owned()),), (None,)]);
+        }
+
+        #[tokio::test]
+        async fn test_user_stats() {
+            let storage = get_storage().await;
+            let user_post = |author: &str, post| Post {
+                author: author.to_owned(),
+                ..post
+            };
+
+            // Post 1 is at the first page for 10 + 20 minutes
+            for (post, is_first_page) in [
+                (user_post("alice", post(1, 0, Some(3), Some(10))), true),
+                (user_post("alice", post(2, 0, Some(31), Some(4))), false),
+                (user_post("alice", post(1, 10, Some(1), Some(50))), true),
+                (user_post("alice", post(1, 30, Some(40), Some(60))), false),
+                (user_post("bob", post(3, 30, Some(2), Some(100))), true),
+                (
+                    Post {
+                        publication_moment: moment(4 * DAY),
+                        ..user_post("carol", post(4, 4 * DAY, Some(1), None))
+                    },
+                    true,
+                ),
+            ] {
+                storage.insert_post(post, is_first_page).await.unwrap();
+            }
+
+            let alice = storage
+                .get_user_stats("alice".to_owned())
+                .await
+                .unwrap()
+                .unwrap();
+            assert_eq!(
+                (
+                    alice.posts,
+                    alice.first_page_posts,
+                    alice.best_rank,
+                    alice.average_score
+                ),
+                (2, 1, Some(1), Some(32.0))
+            );
+            assert!((alice.front_page_minutes - 30.0).abs() < 1e-6);
+            // The next first page snapshot is days later, it counts as an hour
+            let bob = storage.get_user_stats("bob".to_owned()).await.unwrap();
+            assert!((bob.unwrap().front_page_minutes - 60.0).abs() < 1e-6);
+            assert_eq!(
+                storage.get_user_stats("nobody".to_owned()).await.unwrap(),
+                None
+            );
+
+            let top_users = |order| {
+                let storage = &storage;
+                async move {
+                    storage
+                        .get_top_users(moment(0), moment(3 * DAY), order, 10)
+                        .await
+                        .unwrap()
+                        .into_iter()
+                        .map(|stats| stats.user)
+                        .collect::<Vec<_>>()
+                }
+            };
+            assert_eq!(
+                top_users(UserOrder::FrontPageMinutes).await,
+                ["bob", "alice"]
+            );
+            assert_eq!(top_users(UserOrder::Posts).await, ["alice", "bob"]);
+            assert_eq!(top_users(UserOrder::BestRank).await, ["alice", "bob"]);
+            assert_eq!(top_users(UserOrder::AverageScore).await, ["bob", "alice"]);
+            assert_eq!(
+                storage
+                    .get_top_users(moment(0), moment(3 * DAY), UserOrder::Posts, 1)
+                    .await
+                    .unwrap()
+                    .len(),
+                1
+            );
+        }
+
+        #[tokio::test]
+        async fn test_trending_posts() {
+            let storage = get_storage().await;
+
+            for (post, is_first_page) in [
+                (post(1, 0, Some(3), Some(100)), true),
+                (post(2, 0, Some(40), Some(10)), false),
+                (post(4, 0, Some(60), Some(1)), false),
+                (post(6, 0, Some(45), None), false),
+                (post(3, 30, Some(50), Some(5)), false),
+                (post(4, 30, Some(90), Some(1)), false),
+                (post(1, 60, Some(33), Some(200)), false),
+                (post(2, 60, Some(35), Some(40)), false),
+                (post(3, 60, Some(32), Some(65)), false),
+                (post(5, 60, Some(31), Some(50)), false),
+                (post(6, 60, Some(44), None), false),
+            ] {
+                storage.insert_post(post, is_first_page).await.unwrap();
+            }
+
+            // 1 was at the first page, 4 fell off the listing, 5 has one snapshot
+            let trending = storage
+                .get_trending_posts(moment(0), moment(60), 10)
+                .await
+                .unwrap();
+            assert_eq!(
+                trending
+                    .iter()
+                    .map(|trending| trending.post.post_id)
+                    .collect::<Vec<_>>(),
+                vec![3, 2, 6]
+            );
+            let rising = &trending[0];
+            assert_eq!((rising.post.rank, rising.post.score), (Some(32), Some(65)));
+            assert!((rising.points_per_hour.unwrap() - 120.0).abs() < 1e-3);
+            assert!((rising.rank_velocity.unwrap() - 36.0).abs() < 1e-3);
+            assert_eq!(trending[2].points_per_hour, None);
+
+            let trending = storage
+                .get_trending_posts(moment(30), moment(60), 10)
+                .await
+                .unwrap();
+            assert_eq!(
+                trending
+                    .iter()
+                    .map(|trending| trending.post.post_id)
+                    .collect::<Vec<_>>(),
+                vec![3]
+            );
+        }
+
+        #[tokio::test]
+        async fn test_diff_snapshots() {
+            let storage = get_storage().await;
+
+            // The second crawl covers ranks 1-3 only, so post 4 didn't leave
+            for post in [
+                post(1, 0, Some(1), None),
+                post(2, 0, Some(2), None),
+                post(3, 0, Some(3), None),
+                post(4, 0, Some(31), None),
+                post(2, 60, Some(1), None),
+                post(1, 60, Some(2), None),
+                post(5, 60, Some(3), None),
+            ] {
+                storage.insert_post(post, false).await.unwrap();
+            }
+
+            let diff = storage
+                .diff_snapshots(moment(5), moment(65))
+                .await
+                .unwrap()
+                .unwrap();
+            assert_eq!((diff.from, diff.to), (moment(0), moment(60)));
+            assert_eq!(
+                diff.changes
+                    .iter()
+                    .map(|change| (change.post.post_id, change.old_rank, change.new_rank))
+                    .collect::<Vec<_>>(),
+                vec![
+                    (2, Some(2), Some(1)),
+                    (1, Some(1), Some(2)),
+                    (5, None, Some(3)),
+                    (3, Some(3), None),
+                ]
+            );
+
+            let same = storage
+                .diff_snapshots(moment(60), moment(60))
+                .await
+                .unwrap()
+                .unwrap();
+            assert!(same.changes.is_empty());
+            assert_eq!(
+                storage
+                    .diff_snapshots(moment(-5), moment(60))
+                    .await
+                    .unwrap(),
+                None
+            );
+        }
+
+        #[tokio::test]
+        async fn test_diff_snapshots_by_pages() {
+            let storage = get_storage().await;
+
+            // Pages 1-2 at 0, page 1 at 10 and 30, page 2 at 20
+            for post in [
+                post(1, 0, Some(1), None),
+                post(2, 0, Some(2), None),
+                post(3, 0, Some(31), None),
+                post(4, 0, Some(32), None),
+                post(2, 10, Some(1), None),
+                post(1, 10, Some(2), None),
+                post(3, 20, Some(31), None),
+                post(5, 20, Some(32), None),
+                post(1, 30, Some(1), None),
+                post(6, 30, Some(2), None),
+            ] {
+                storage.insert_post(post, false).await.unwrap();
+            }
+
+            let changes = |diff: SnapshotDiff| {
+                diff.changes
+                    .iter()
+                    .map(|change| (change.post.post_id, change.old_rank, change.new_rank))
+                    .collect::<Vec<_>>()
+            };
+
+            // Page 1 comes from the crawl at 10, not from the one at 20
+            let diff = storage
+                .diff_snapshots(moment(5), moment(25))
+                .await
+                .unwrap()
+                .unwrap();
+            assert_eq!((diff.from, diff.to), (moment(0), moment(20)));
+            assert_eq!(
+                changes(diff),
+                vec![
+                    (2, Some(2), Some(1)),
+                    (1, Some(1), Some(2)),
+                    (5, None, Some(32)),
+                    (4, Some(32), None),
+                ]
+            );
+
+            // Page 2 comes from the crawl at 20, not from the one at 0
+            let diff = storage
+                .diff_snapshots(moment(5), moment(35))
+                .await
+                .unwrap()
+                .unwrap();
+            assert_eq!((diff.from, diff.to), (moment(0), moment(30)));
+            assert_eq!(
+                changes(diff),
+                vec![
+                    (6, None, Some(2)),
+                    (5, None, Some(32)),
+                    (2, Some(2), None),
+                    (4, Some(32), None),
+                ]
+            );
+        }
+
+        #[tokio::test]
+        async fn test_digest() {
+            let storage = get_storage().await;
+            let ask_post = |post_id, minutes, rank| Post {
+                listing: Some(Listing::Ask),
+                ..post(post_id, minutes, Some(rank), None)
+            };
+
+            // Posts 1 and 2 both reached rank 1, but 2 stayed there longer
+            for (post, is_first_page) in [
+                (post(1, 0, Some(1), None), true),
+                (post(2, 0, Some(2), None), true),
+                (post(3, 0, Some(40), None), false),
+                (post(2, 10, Some(1), None), true),
+                (ask_post(4, 15, 2), false),
+                (ask_post(5, 15, 1), false),
+                (post(1, 30, Some(5), None), true),
+                (post(2, 30, Some(3), None), true),
+                (post(6, 120, Some(1), None), true),
+            ] {
+                storage.insert_post(post, is_first_page).await.unwrap();
+            }
+
+            let digest = storage.get_digest(moment(0), moment(60), 2).await.unwrap();
+            assert_eq!(
+                digest
+                    .sections
+                    .iter()
+                    .map(|section| (
+                        section.listing,
+                        section
+                            .posts
+                            .iter()
+                            .map(|post| (post.post.post_id, post.best_rank))
+                            .collect::<Vec<_>>()
+                    ))
+                    .collect::<Vec<_>>(),
+                vec![
+                    (Listing::Top, vec![(2, 1), (1, 1)]),
+                    (Listing::Ask, vec![(5, 1), (4, 2)]),
+                ]
+            );
+            let minutes = digest.sections[0].posts[0].front_page_minutes;
+            // 10 + 20 minutes, then an hour till the snapshot of post 6 at most
+            assert!((minutes - 90.0).abs() < 1e-3);
+        }
+
+        #[tokio::test]
+        async fn test_post_stats() {
+            let storage = get_storage().await;
+            let user_post = |post_id: PostId, minutes, rank| Post {
+                author: "test_post_stats".to_owned(),
+                publication_moment: moment(-post_id),
+                ..post(post_id, minutes, rank, None)
+            };
+
+            // Post 1 was published later, but stayed at the first page longer
+            for (post, is_first_page) in [
+                (user_post(1, 0, Some(5)), true),
+                (user_post(2, 0, Some(2)), true),
+                (user_post(3, 0, Some(31)), false),
+                (user_post(1, 10, Some(3)), true),
+                (user_post(2, 10, Some(30)), false),
+                (user_post(1, 20, Some(40)), false),
+                (user_post(4, 30, None), true),
+            ] {
+                storage.insert_post(post, is_first_page).await.unwrap();
+            }
+
+            let stats = storage.get_post_stats(1).await.unwrap().unwrap();
+            assert_eq!(
+                (
+                    stats.first_seen,
+                    stats.last_seen,
+                    stats.appearances,
+                    stats.best_rank
+                ),
+                (Some(moment(0)), Some(moment(10)), 2, Some(3))
+            );
+            // The gap till the snapshot of post 4 counts too
+            assert!((stats.front_page_minutes - 30.0).abs() < 1e-6);
+
+            let never = storage.get_post_stats(3).await.unwrap().unwrap();
+            assert_eq!(
+                (
+                    never.first_seen,
+                    never.appearances,
+                    never.front_page_minutes
+                ),
+                (None, 0, 0.0)
+            );
+            assert_eq!(storage.get_post_stats(5).await.unwrap(), None);
+
+            let user_posts = |filter| {
+                let storage = &storage;
+                async move {
+                    storage
+                        .get_user_posts(filter)
+                        .await
+                        .unwrap()
+                        .map(|post| post.unwrap().post_id)
+                        .collect::<Vec<_>>()
+                        .await
+                }
+            };
+            let user = || "test_post_stats".to_owned();
+            assert_eq!(
+                user_posts(UserPostRequest::WasAtFirstPage { user: user() }).await,
+                vec![1, 2, 4]
+            );
+            assert_eq!(
+                user_posts(UserPostRequest::All { user: user() }).await,
+                vec![4, 3, 2, 1]
+            );
+        }
+
+        #[tokio::test]
+        async fn test_consistency() {
+            let storage = get_storage().await;
+
+            let post = Post {
+                author: "test_consistency".to_owned(),
+                publication_moment: chrono::Local::now().naive_utc(),
+                ..get_rnd_post()
+            };
+
+            let fp_post = Post {
+                author: "test_consistency".to_owned(),
+                publication_moment: chrono::Local::now().naive_utc(),
+                ..get_rnd_post()
+            };
+
+            storage.insert_post(post.clone(), false).await.unwrap();
+            storage.insert_post(fp_post.clone(), true).await.unwrap();
+
+            let posts = storage
+                .get_user_posts(UserPostRequest::All {
+                    user: post.author.clone(),
+                })
+                .await
+                .unwrap()
+                .map(Result::unwrap)
+                .collect::<Vec<_>>()
+                .await;
+            assert_eq!(posts, vec![post, fp_post.clone()]);
+
+            let posts = storage
+                .get_user_posts(UserPostRequest::WasAtFirstPage {
+                    user: fp_post.author.clone(),
+                })
+                .await
+                .unwrap()
+                .map(Result::unwrap)
+                .collect::<Vec<_>>()
+                .await;
+            assert_eq!(posts, vec![fp_post]);
+        }
+
+        #[tokio::test]
+        async fn test_first_page_posts() {
+            let storage = get_storage().await;
+
+            for (post_id, minutes, is_first_page) in [
+                (1, 0, true),
+                (2, 0, false),
+                (3, 10, true),
+                (1, 10, true),
+                (4, 20, true),
+            ] {
+                storage
+                    .insert_post(post(post_id, minutes, None, None), is_first_page)
+                    .await
+                    .unwrap();
+            }
+
+            let posts_ids =
+                |posts: Vec<Post>| posts.iter().map(|post| post.post_id).collect::<Vec<_>>();
+            assert_eq!(
+                posts_ids(storage.get_first_page_posts(10).await.unwrap()),
+                vec![4, 3, 1]
+            );
+            assert_eq!(
+                posts_ids(storage.get_first_page_posts(2).await.unwrap()),
+                vec![4, 3]
+            );
+        }
+
+        #[tokio::test]
+        async fn test_latest_user_posts() {
+            let storage = get_storage().await;
+
+            for (post_id, minutes, is_first_page) in
+                [(1, 0, true), (2, 10, false), (3, 20, true), (4, 30, false)]
+            {
+                let post = Post {
+                    publication_moment: moment(minutes),
+                    ..post(post_id, minutes, None, None)
+                };
+                storage.insert_post(post, is_first_page).await.unwrap();
+            }
+
+            let posts_ids =
+                |posts: Vec<Post>| posts.iter().map(|post| post.post_id).collect::<Vec<_>>();
+            let user = || "test".to_owned();
+            assert_eq!(
+                posts_ids(
+                    storage
+                        .get_latest_user_posts(UserPostRequest::All { user: user() }, 3)
+                        .await
+                        .unwrap()
+                ),
+                vec![4, 3, 2]
+            );
+            assert_eq!(
+                posts_ids(
+                    storage
+                        .get_latest_user_posts(UserPostRequest::WasAtFirstPage { user: user() }, 10)
+                        .await
+                        .unwrap()
+                ),
+                vec![3, 1]
+            );
+            assert!(storage
+                .get_latest_user_posts(
+                    UserPostRequest::All {
+                        user: "pg".to_owned()
+                    },
+                    10
+                )
+                .await
+                .unwrap()
+                .is_empty());
+        }
+
+        #[tokio::test]
+        async fn test_post_history() {
+            let storage = get_storage().await;
+
+            for (minutes, rank, score, is_first_page) in [
+                (0, Some(40), Some(3), false),
+                (10, Some(12), Some(20), false),
+                (20, None, None, true),
+                (30, Some(2), Some(95), true),
+            ] {
+                storage
+                    .insert_post(post(1, minutes, rank, score), is_first_page)
+                    .await
+                    .unwrap();
+            }
+
+            let snapshot = |minutes, rank: Option<i64>, score, was_at_first_page| PostSnapshot {
+                snapshot_moment: moment(minutes),
+                listing: rank.map(|_| Listing::Top),
+                rank,
+                score,
+                was_at_first_page,
+            };
+            assert_eq!(
+                storage.get_post_history(1).await.unwrap(),
+                vec![
+                    snapshot(0, Some(40), Some(3), false),
+                    snapshot(10, Some(12), Some(20), false),
+                    snapshot(20, None, None, true),
+                    snapshot(30, Some(2), Some(95), true),
+                ]
+            );
+            assert!(storage.get_post_history(2).await.unwrap().is_empty());
+        }
+
+        #[tokio::test]
+        async fn test_top_posts_ranks() {
+            let storage = get_storage().await;
+            let last_snapshot_moment = chrono::Local::now().naive_utc();
+
+            for (post_id, rank) in [(10, Some(3)), (20, Some(1)), (30, None), (40, Some(2))] {
+                let post = Post {
+                    post_id,
+                    last_snapshot_moment,
+                    rank,
+                    listing: Some(Listing::Top),
+                    ..get_rnd_post()
+                };
+                storage.insert_post(post, true).await.unwrap();
+            }
+
+            let posts = storage
+                .get_current_top_posts()
+                .await
+                .unwrap()
+                .map(Result::unwrap)
+                .map(|post| (post.post_id, post.rank, post.listing))
+                .collect::<Vec<_>>()
+                .await;
+            // Unranked posts go last
+            assert_eq!(
+                posts,
+                vec![
+                    (20, Some(1), Some(Listing::Top)),
+                    (40, Some(2), Some(Listing::Top)),
+                    (10, Some(3), Some(Listing::Top)),
+                    (30, None, None),
+                ]
+            );
+        }
+
+        #[tokio::test]
+        async fn test_first_page() {
+            let storage = get_storage().await;
+
+            let last_snapshot_moment = chrono::Local::now().naive_utc();
+            for post in (0..100).map(|post_id| Post {
+                post_id,
+                last_snapshot_moment,
+                ..get_rnd_post()
+            }) {
+                storage
+                    .insert_post(post.clone(), post.post_id < 50)
+                    .await
+                    .unwrap();
+            }
+
+            {
+                let top_posts_ids = storage
+                    .get_current_top_posts()
+                    .await
+                    .unwrap()
+                    .map(Result::unwrap)
+                    .map(|post| post.post_id)
+                    .collect::<Vec<_>>()
+                    .await;
+                assert_eq!(
+                    top_posts_ids,
+                    (0..50).collect::<Vec<_>>(),
+                    "failed to validate top page after first snapshot"
+                );
+            }
+
+            let last_snapshot_moment = chrono::Local::now().naive_utc();
+            for post in (100..200).map(|post_id| Post {
+                post_id,
+                last_snapshot_moment,
+                ..get_rnd_post()
+            }) {
+                storage
+                    .insert_post(post.clone(), post.post_id >= 150)
+                    .await
+                    .unwrap();
+            }
+
+            {
+                let top_posts_ids = storage
+                    .get_current_top_posts()
+                    .await
+                    .unwrap()
+                    .map(Result::unwrap)
+                    .map(|post| post.post_id)
+                    .collect::<Vec<_>>()
+                    .await;
+                assert_eq!(
+                    top_posts_ids,
+                    (150..200).collect::<Vec<_>>(),
+                    "failed to validate top page after second snapshot"
+                );
+            }
+        }
+    }
+}
+
+pub type Storage = sqlite::SqlitePool;
+pub type Error = sqlx::Error;
@@ -0,0 +1,498 @@
+use std::{
+    collections::HashMap,
+    net::{IpAddr, SocketAddr},
+    pin::Pin,
+    sync::{Arc, Mutex},
+    task::{Context, Poll},
+    time::Duration,
+};
+
+use futures::future::BoxFuture;
+use hyper::{
+    body::HttpBody,
+    header::AUTHORIZATION,
+    http::{HeaderMap, Request, Response},
+    Body,
+};
+use prometheus::{IntCounterVec, Opts, Registry};
+use tokio::{
+    sync::{OwnedSemaphorePermit, Semaphore},
+    time::Instant,
+};
+use tonic::{
+    body::BoxBody,
+    transport::server::{TcpConnectInfo, TlsConnectInfo},
+    Status,
+};
+use tower::{Layer, Service};
+
+use crate::auth::Auth;
+
+/// Peers are forgotten after this idle time, their quota is full by then anyway
+const PEER_IDLE: Duration = Duration::from_secs(60);
+/// Idle peers are forgotten only when there are more of them
+const MAX_PEERS: usize = 1024;
+
+/// Token bucket, which is refilled by `requests_per_minute` smoothly
+#[derive(Debug)]
+pub struct Quota {
+    requests_per_minute: f64,
+    tokens: f64,
+    updated: Instant,
+}
+
+impl Quota {
+    pub fn new(requests_per_minute: u32) -> Self {
+        Self {
+            requests_per_minute: requests_per_minute.into(),
+            tokens: requests_per_minute.into(),
+            updated: Instant::now(),
+        }
+    }
+
+    pub fn try_acquire(&mut self) -> bool {
+        let now = Instant::now();
+        let refill = (now - self.updated).as_secs_f64() * self.requests_per_minute / 60.0;
+        self.tokens = (self.tokens + refill).min(self.requests_per_minute);
+        self.updated = now;
+
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            true
+        } else {
+            false
+        }
+    }
+}
+
+/// Limits of every peer
+#[derive(Debug, Clone, Copy)]
+pub struct Limits {
+    pub requests_per_minute: u32,
+    /// Calls in flight, a stream is in flight till its last message
+    pub max_concurrent_calls: u32,
+}
+
+/// Client of the api: a configured api key or an ip address
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum Peer {
+    ApiKey(String),
+    Ip(IpAddr),
+    Unknown,
+}
+
+#[derive(Debug)]
+struct PeerState {
+    quota: Quota,
+    calls: Arc<Semaphore>,
+}
+
+/// Per-peer request rate and concurrent calls of the grpc server and feeds
+#[derive(Debug)]
+pub struct RateLimiter {
+    limits: Limits,
+    auth: Arc<Auth>,
+    peers: Mutex<HashMap<Peer, PeerState>>,
+    rejected: IntCounterVec,
+}
+
+impl RateLimiter {
+    pub fn new(limits: Limits, auth: Arc<Auth>) -> Self {
+        Self {
+            limits,
+            auth,
+            peers: Mutex::default(),
+            rejected: IntCounterVec::new(
+                Opts::new(
+                    "grpc_rate_limited_total",
+                    "Grpc calls and feed requests rejected by per-peer limits",
+                ),
+                &["reason"],
+            )
+            .unwrap(),
+        }
+    }
+
+    pub fn register(&self, registry: &Registry) -> Result<(), prometheus::Error> {
+        registry.register(Box::new(self.rejected.clone()))
+    }
+
+    pub fn layer(self: &Arc<Self>) -> RateLimitLayer {
+        RateLimitLayer {
+            limiter: self.clone(),
+        }
+    }
+
+    /// Calls with a configured api key are limited per key, other ones per ip
+    fn peer<B>(&self, request: &Request<B>, remote_addr: Option<SocketAddr>) -> Peer {
+        let key_name = request
+            .headers()
+            .get(AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| self.auth.key_name(value));
+
+        match (key_name, remote_addr) {
+            (Some(name), _) => Peer::ApiKey(name.to_owned()),
+            (None, Some(addr)) => Peer::Ip(addr.ip()),
+            (None, None) => Peer::Unknown,
+        }
+    }
+
+    /// Permit of a plain http request, e.g. of a feed, which shares the
+    /// limits of its peer with grpc calls
+    #[allow(clippy::result_large_err)]
+    pub fn admit_http<B>(
+        &self,
+        request: &Request<B>,
+        remote_addr: SocketAddr,
+    ) -> Result<OwnedSemaphorePermit, Status> {
+        self.admit(self.peer(request, Some(remote_addr)))
+    }
+
+    /// Permit of a call, which must be held till the response is sent
+    #[allow(clippy::result_large_err)]
+    fn admit(&self, peer: Peer) -> Result<OwnedSemaphorePermit, Status> {
+        let mut peers = self.peers.lock().expect("peers lock poisoned");
+        if peers.len() >= MAX_PEERS {
+            peers.retain(|_, state| {
+                state.quota.updated.elapsed() < PEER_IDLE
+                    || state.calls.available_permits() < self.limits.max_concurrent_calls as usize
+            });
+        }
+
+        let state = peers.entry(peer.clone()).or_insert_with(|| PeerState {
+            quota: Quota::new(self.limits.requests_per_minute),
+            calls: Arc::new(Semaphore::new(self.limits.max_concurrent_calls as usize)),
+        });
+
+        let permit = state.calls.clone().try_acquire_owned().map_err(|_| {
+            self.reject(&peer, "concurrency");
+            Status::resource_exhausted("too many concurrent calls")
+        })?;
+        if !state.quota.try_acquire() {
+            self.reject(&peer, "rate");
+            return Err(Status::resource_exhausted("too many requests"));
+        }
+        Ok(permit)
+    }
+
+    fn reject(&self, peer: &Peer, reason: &str) {
+        tracing::warn!(?peer, reason, "call is rate limited");
+        self.rejected.with_label_values(&[reason]).inc();
+    }
+}
+
+/// Tls connections wrap tcp info of the connection
+fn grpc_remote_addr<B>(request: &Request<B>) -> Option<SocketAddr> {
+    let extensions = request.extensions();
+    extensions
+        .get::<TcpConnectInfo>()
+        .or_else(|| {
+            extensions
+                .get::<TlsConnectInfo<TcpConnectInfo>>()
+                .map(TlsConnectInfo::get_ref)
+        })
+        .and_then(TcpConnectInfo::remote_addr)
+}
+
+/// Health checks are never limited, so probes don't fail under load
+fn is_exempt<B>(request: &Request<B>) -> bool {
+    request.uri().path().starts_with("/grpc.health.v1.Health/")
+}
+
+#[derive(Debug, Clone)]
+pub struct RateLimitLayer {
+    limiter: Arc<RateLimiter>,
+}
+
+impl<S> Layer<S> for RateLimitLayer {
+    type Service = RateLimit<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        RateLimit {
+            inner,
+            limiter: self.limiter.clone(),
+        }
+    }
+}
+
+/// Rejects calls over peer limits with `RESOURCE_EXHAUSTED`
+#[derive(Debug, Clone)]
+pub struct RateLimit<S> {
+    inner: S,
+    limiter: Arc<RateLimiter>,
+}
+
+impl<S> Service<Request<Body>> for RateLimit<S>
+where
+    S: Service<Request<Body>, Response = Response<BoxBody>>,
+    S::Future: Send + 'static,
+{
+    type Response = Response<BoxBody>;
+    type Error = S::Error;
+    type Future = BoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: Request<Body>) -> Self::Future {
+        let permit = if is_exempt(&request) {
+            None
+        } else {
+            match self
+                .limiter
+                .admit(self.limiter.peer(&request, grpc_remote_addr(&request)))
+            {
+                Ok(permit) => Some(permit),
+                Err(status) => return Box::pin(async move { Ok(status.to_http()) }),
+            }
+        };
+
+        let response = self.inner.call(request);
+        Box::pin(async move {
+            Ok(response.await?.map(|body| {
+                WithPermit {
+                    body,
+                    _permit: permit,
+                }
+                .boxed_unsync()
+            }))
+        })
+    }
+}
+
+/// Response body, which holds the call permit till it's sent or dropped
+struct WithPermit<B> {
+    body: B,
+    _permit: Option<OwnedSemaphorePermit>,
+}
+
+impl<B: HttpBody + Unpin> HttpBody for WithPermit<B> {
+    type Data = B::Data;
+    type Error = B::Error;
+
+    fn poll_data(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Self::Data, Self::Error>>> {
+        Pin::new(&mut self.body).poll_data(cx)
+    }
+
+    fn poll_trailers(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Result<Option<HeaderMap>, Self::Error>> {
+        Pin::new(&mut self.body).poll_trailers(cx)
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.body.is_end_stream()
+    }
+
+    fn size_hint(&self) -> hyper::body::SizeHint {
+        self.body.size_hint()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use hyper::http::uri::PathAndQuery;
+    use tokio::net::TcpListener;
+    use tokio_stream::wrappers::TcpListenerStream;
+    use tonic::{
+        codec::ProstCodec,
+        transport::{Certificate, Channel, ClientTlsConfig},
+        Code,
+    };
+
+    use super::*;
+    use crate::{
+        auth::{ApiKey, Scope},
+        tls::{self, tests::Pki},
+    };
+
+    fn get_limiter(requests_per_minute: u32, max_concurrent_calls: u32) -> Arc<RateLimiter> {
+        Arc::new(RateLimiter::new(
+            Limits {
+                requests_per_minute,
+                max_concurrent_calls,
+            },
+            Arc::new(Auth::new(vec![ApiKey {
+                name: "reader".to_owned(),
+                key: "read-key".to_owned(),
+                scopes: vec![Scope::Read],
+                requests_per_minute: None,
+            }])),
+        ))
+    }
+
+    fn get_request(path: &str, authorization: Option<&str>) -> Request<Body> {
+        let mut request = Request::builder().uri(path);
+        if let Some(authorization) = authorization {
+            request = request.header(AUTHORIZATION, authorization);
+        }
+        request.body(Body::empty()).unwrap()
+    }
+
+    type EmptyService = tower::util::ServiceFn<
+        fn(Request<Body>) -> futures::future::Ready<Result<Response<BoxBody>, Status>>,
+    >;
+
+    /// Service with empty responses behind the limiter
+    fn get_service(limiter: &Arc<RateLimiter>) -> RateLimit<EmptyService> {
+        let empty: fn(_) -> _ =
+            |_| futures::future::ready(Ok(Response::new(tonic::body::empty_body())));
+        limiter.layer().layer(tower::service_fn(empty))
+    }
+
+    fn code(response: &Response<BoxBody>) -> Code {
+        response
+            .headers()
+            .get("grpc-status")
+            .map(|code| Code::from_bytes(code.as_bytes()))
+            .unwrap_or(Code::Ok)
+    }
+
+    #[test]
+    fn test_peer() {
+        let limiter = get_limiter(1, 1);
+        let peer = |authorization| {
+            let request = get_request("/", authorization);
+            limiter.peer(&request, grpc_remote_addr(&request))
+        };
+
+        assert_eq!(
+            peer(Some("Bearer read-key")),
+            Peer::ApiKey("reader".to_owned())
+        );
+        // Unknown keys are limited by ip, so they can't bypass limits
+        assert_eq!(peer(Some("Bearer wrong-key")), Peer::Unknown);
+        assert_eq!(peer(None), Peer::Unknown);
+
+        let remote_addr = "10.0.0.1:4242".parse().unwrap();
+        assert_eq!(
+            limiter.peer(&get_request("/", None), Some(remote_addr)),
+            Peer::Ip(remote_addr.ip())
+        );
+    }
+
+    #[tokio::test]
+    async fn test_peer_over_tls() {
+        let pki = Pki::generate();
+        let config = tls::server_config(
+            Some(&pki.path("server.pem")),
+            Some(&pki.path("server.key")),
+            None,
+        )
+        .unwrap()
+        .unwrap();
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        let (_, health_server) = tonic_health::server::health_reporter();
+
+        let limiter = get_limiter(100, 2);
+        tokio::spawn(
+            tonic::transport::Server::builder()
+                .tls_config(config)
+                .unwrap()
+                .layer(limiter.layer())
+                .add_service(health_server)
+                .serve_with_incoming(TcpListenerStream::new(listener)),
+        );
+
+        let channel = Channel::from_shared(format!("https://{address}"))
+            .unwrap()
+            .tls_config(
+                ClientTlsConfig::new()
+                    .ca_certificate(Certificate::from_pem(&pki.ca))
+                    .domain_name("localhost"),
+            )
+            .unwrap()
+            .connect()
+            .await
+            .unwrap();
+        // Any limited path will do, the limiter runs before routing
+        let mut grpc = tonic::client::Grpc::new(channel);
+        grpc.ready().await.unwrap();
+        let status = grpc
+            .unary::<(), (), _>(
+                tonic::Request::new(()),
+                PathAndQuery::from_static("/hackernews.Unknown/Call"),
+                ProstCodec::default(),
+            )
+            .await
+            .unwrap_err();
+        assert_eq!(status.code(), Code::Unimplemented);
+
+        let peers = limiter.peers.lock().unwrap();
+        assert_eq!(
+            peers.keys().collect::<Vec<_>>(),
+            [&Peer::Ip([127, 0, 0, 1].into())]
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_request_rate() {
+        let limiter = get_limiter(2, 10);
+        let first = || Peer::Ip([10, 0, 0, 1].into());
+        let code = |result: Result<_, Status>| result.map(drop).map_err(|status| status.code());
+
+        assert_eq!(code(limiter.admit(first())), Ok(()));
+        assert_eq!(code(limiter.admit(first())), Ok(()));
+        assert_eq!(code(limiter.admit(first())), Err(Code::ResourceExhausted));
+
+        // Other peers have their own quota
+        assert_eq!(code(limiter.admit(Peer::Ip([10, 0, 0, 2].into()))), Ok(()));
+        assert_eq!(
+            code(limiter.admit(Peer::ApiKey("reader".to_owned()))),
+            Ok(())
+        );
+
+        tokio::time::advance(Duration::from_secs(30)).await;
+        assert_eq!(code(limiter.admit(first())), Ok(()));
+        assert_eq!(limiter.rejected.with_label_values(&["rate"]).get(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_calls() {
+        let limiter = get_limiter(100, 2);
+        let mut service = get_service(&limiter);
+        let mut call = || {
+            service.call(get_request(
+                "/hackernews_proxy.PostService/GetTopPosts",
+                None,
+            ))
+        };
+
+        let first = call().await.unwrap();
+        let second = call().await.unwrap();
+        assert_eq!(code(&first), Code::Ok);
+        assert_eq!(code(&second), Code::Ok);
+        assert_eq!(code(&call().await.unwrap()), Code::ResourceExhausted);
+
+        // The call is over, when its response body is dropped
+        drop(first);
+        assert_eq!(code(&call().await.unwrap()), Code::Ok);
+        assert_eq!(
+            limiter.rejected.with_label_values(&["concurrency"]).get(),
+            1
+        );
+    }
+
+    #[tokio::test]
+    async fn test_health_is_exempt() {
+        let limiter = get_limiter(1, 1);
+        let mut service = get_service(&limiter);
+
+        let mut responses = Vec::new();
+        for _ in 0..5 {
+            let response = service
+                .call(get_request("/grpc.health.v1.Health/Check", None))
+                .await
+                .unwrap();
+            assert_eq!(code(&response), Code::Ok);
+            responses.push(response);
+        }
+    }
+}
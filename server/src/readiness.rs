@@ -0,0 +1,273 @@
+use std::collections::BTreeMap;
+
+use tokio::sync::watch;
+use tokio_util::sync::CancellationToken;
+use tonic_health::{server::HealthReporter, ServingStatus};
+
+use crate::supervisor::{Component, Status};
+use hackernews_crawler::core::DateTime;
+
+/// Overall server health by the gRPC health checking protocol
+const SERVER: &str = "";
+
+/// gRPC service, which is `SERVING` only when the components it needs are
+/// healthy, so one stuck component doesn't take the others out of rotation
+#[derive(Debug, Clone, Copy)]
+pub struct ServiceReadiness<'s> {
+    pub service: &'s str,
+    pub components: &'s [Component],
+    /// Service answers from stored posts, so it waits for the first snapshot
+    pub needs_snapshot: bool,
+}
+
+/// Health service name of a supervised component, e.g. `component.crawler`
+pub fn component_service(component: Component) -> String {
+    format!("component.{component}")
+}
+
+/// Keep `grpc.health.v1.Health` statuses of `services` and of every
+/// supervised component up to date
+///
+/// The overall status is `SERVING` while every component is healthy. On
+/// shutdown all statuses are switched to `NOT_SERVING`, so the orchestrator
+/// stops routing new calls to us
+pub async fn report_readiness(
+    mut reporter: HealthReporter,
+    services: &[ServiceReadiness<'_>],
+    mut components: watch::Receiver<BTreeMap<Component, Status>>,
+    mut latest_snapshot: watch::Receiver<Option<DateTime>>,
+    shutdown: &CancellationToken,
+) {
+    loop {
+        let statuses = {
+            let components = components.borrow();
+            let has_snapshot = latest_snapshot.borrow().is_some();
+            // Components, which haven't started yet, aren't ready
+            let is_healthy =
+                |component: &Component| components.get(component).is_some_and(Status::is_healthy);
+
+            let mut statuses = vec![(SERVER.to_owned(), Component::ALL.iter().all(is_healthy))];
+            statuses.extend(
+                Component::ALL
+                    .iter()
+                    .map(|component| (component_service(*component), is_healthy(component))),
+            );
+            statuses.extend(services.iter().map(|readiness| {
+                (
+                    readiness.service.to_owned(),
+                    (has_snapshot || !readiness.needs_snapshot)
+                        && readiness.components.iter().all(is_healthy),
+                )
+            }));
+            statuses
+        };
+        for (service, is_ready) in statuses {
+            let status = match is_ready {
+                true => ServingStatus::Serving,
+                false => ServingStatus::NotServing,
+            };
+            reporter.set_service_status(service, status).await;
+        }
+
+        tokio::select! {
+            Ok(()) = components.changed() => {}
+            Ok(()) = latest_snapshot.changed() => {}
+            _ = shutdown.cancelled() => break,
+        }
+    }
+
+    set_status(&mut reporter, services, ServingStatus::NotServing).await;
+}
+
+/// Set the same status for the overall health, every component and `services`
+pub async fn set_status(
+    reporter: &mut HealthReporter,
+    services: &[ServiceReadiness<'_>],
+    status: ServingStatus,
+) {
+    reporter.set_service_status(SERVER, status).await;
+    for component in Component::ALL {
+        reporter
+            .set_service_status(component_service(component), status)
+            .await;
+    }
+    for readiness in services {
+        reporter.set_service_status(readiness.service, status).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use tokio::net::TcpListener;
+    use tokio_stream::wrappers::TcpListenerStream;
+    use tonic::transport::Channel;
+    use tonic_health::proto::{
+        health_check_response::ServingStatus as ProtoStatus, health_client::HealthClient,
+        HealthCheckRequest,
+    };
+
+    use crate::supervisor::Health;
+
+    use super::*;
+
+    const SERVICE: &str = "hackernews_proxy.PostService";
+    const ADMIN: &str = "hackernews_proxy.AdminService";
+
+    const SERVICES: [ServiceReadiness<'static>; 2] = [
+        ServiceReadiness {
+            service: SERVICE,
+            components: &[Component::Api],
+            needs_snapshot: true,
+        },
+        ServiceReadiness {
+            service: ADMIN,
+            components: &[Component::Api],
+            needs_snapshot: false,
+        },
+    ];
+
+    async fn check(client: &mut HealthClient<Channel>, service: &str) -> ProtoStatus {
+        client
+            .check(HealthCheckRequest {
+                service: service.to_owned(),
+            })
+            .await
+            .unwrap()
+            .into_inner()
+            .status()
+    }
+
+    /// Status is updated in background, so poll it for a while
+    async fn wait_statuses(client: &mut HealthClient<Channel>, expected: &[(&str, ProtoStatus)]) {
+        for _ in 0..100 {
+            let mut statuses = Vec::new();
+            for (service, _) in expected {
+                statuses.push((*service, check(client, service).await));
+            }
+            if statuses == expected {
+                return;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        panic!("statuses didn't become {expected:?}");
+    }
+
+    #[tokio::test]
+    async fn test_report_readiness() {
+        use ProtoStatus::{NotServing, Serving};
+
+        let (mut reporter, health_server) = tonic_health::server::health_reporter();
+        set_status(&mut reporter, &SERVICES, ServingStatus::NotServing).await;
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(
+            tonic::transport::Server::builder()
+                .add_service(health_server)
+                .serve_with_incoming(TcpListenerStream::new(listener)),
+        );
+        let mut client = HealthClient::connect(format!("http://{addr}"))
+            .await
+            .unwrap();
+
+        let health = Health::default();
+        let (latest_snapshot, latest_snapshot_receiver) = watch::channel(None);
+        let shutdown = CancellationToken::new();
+
+        let reporting = tokio::spawn({
+            let components = health.subscribe();
+            let shutdown = shutdown.clone();
+            async move {
+                report_readiness(
+                    reporter,
+                    &SERVICES,
+                    components,
+                    latest_snapshot_receiver,
+                    &shutdown,
+                )
+                .await
+            }
+        });
+
+        for component in Component::ALL {
+            health.set(component, Status::Running);
+        }
+        wait_statuses(
+            &mut client,
+            &[
+                (SERVER, Serving),
+                ("component.api", Serving),
+                ("component.feeds", Serving),
+                (SERVICE, NotServing),
+                (ADMIN, Serving),
+            ],
+        )
+        .await;
+
+        latest_snapshot.send_replace(Some(chrono::Local::now().naive_utc()));
+        wait_statuses(&mut client, &[(SERVER, Serving), (SERVICE, Serving)]).await;
+
+        // Restarting crawler doesn't take the read api out of rotation
+        health.set(
+            Component::Crawler,
+            Status::Restarting {
+                restarts: 1,
+                last_error: "database is locked".to_owned(),
+            },
+        );
+        wait_statuses(
+            &mut client,
+            &[
+                (SERVER, NotServing),
+                ("component.crawler", NotServing),
+                ("component.api", Serving),
+                (SERVICE, Serving),
+                (ADMIN, Serving),
+            ],
+        )
+        .await;
+
+        health.set(
+            Component::Api,
+            Status::Failed {
+                last_error: "address in use".to_owned(),
+            },
+        );
+        wait_statuses(
+            &mut client,
+            &[
+                ("component.api", NotServing),
+                (SERVICE, NotServing),
+                (ADMIN, NotServing),
+            ],
+        )
+        .await;
+
+        health.set(Component::Crawler, Status::Running);
+        health.set(Component::Api, Status::Running);
+        wait_statuses(
+            &mut client,
+            &[
+                (SERVER, Serving),
+                ("component.crawler", Serving),
+                (SERVICE, Serving),
+            ],
+        )
+        .await;
+
+        shutdown.cancel();
+        reporting.await.unwrap();
+        wait_statuses(
+            &mut client,
+            &[
+                (SERVER, NotServing),
+                ("component.http", NotServing),
+                (SERVICE, NotServing),
+                (ADMIN, NotServing),
+            ],
+        )
+        .await;
+    }
+}
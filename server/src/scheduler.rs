@@ -0,0 +1,263 @@
+use std::{
+    str::FromStr,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use serde::{de, Deserialize, Deserializer};
+use tokio::time::Instant;
+
+use hackernews_crawler::core::{CrawlTarget, Listing};
+
+#[derive(thiserror::Error, Debug, PartialEq, Eq)]
+#[error("wrong schedule `{schedule}`, expected `<listing>:<first>[-<last>]@<number><s|m|h>`, e.g. `top:2-10@10m`")]
+pub struct ParseError {
+    schedule: String,
+}
+
+/// Crawl of `target` at every wall-clock boundary of `every`,
+/// e.g. at :00 and :30 seconds of each minute for `every` of 30s
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Schedule {
+    pub target: CrawlTarget,
+    pub every: Duration,
+}
+
+fn parse_duration(duration: &str) -> Option<Duration> {
+    let unit = match duration.chars().last()? {
+        's' => 1,
+        'm' => 60,
+        'h' => 60 * 60,
+        _ => return None,
+    };
+    let value: u64 = duration[..duration.len() - 1].parse().ok()?;
+    Some(Duration::from_secs(value * unit)).filter(|duration| !duration.is_zero())
+}
+
+impl FromStr for Schedule {
+    type Err = ParseError;
+
+    fn from_str(schedule: &str) -> Result<Self, Self::Err> {
+        let parse = || {
+            let (target, every) = schedule.trim().split_once('@')?;
+            let (listing, pages) = target.split_once(':')?;
+            let (first_page, last_page) = pages.split_once('-').unwrap_or((pages, pages));
+
+            Some(Schedule {
+                target: CrawlTarget::new(
+                    Listing::from_str(listing).ok()?,
+                    first_page.parse().ok()?,
+                    last_page.parse().ok()?,
+                )?,
+                every: parse_duration(every)?,
+            })
+        };
+
+        parse().ok_or_else(|| ParseError {
+            schedule: schedule.to_owned(),
+        })
+    }
+}
+
+/// Comma separated list of [`Schedule`], e.g. `top:1@1m,top:2-10@10m,new:1@30s`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CrawlSchedule(pub Vec<Schedule>);
+
+impl FromStr for CrawlSchedule {
+    type Err = ParseError;
+
+    fn from_str(schedules: &str) -> Result<Self, Self::Err> {
+        schedules
+            .split(',')
+            .map(Schedule::from_str)
+            .collect::<Result<_, _>>()
+            .map(CrawlSchedule)
+    }
+}
+
+impl<'de> Deserialize<'de> for CrawlSchedule {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer)?
+            .parse()
+            .map_err(de::Error::custom)
+    }
+}
+
+/// Time till the next multiple of `every` since unix epoch
+fn till_boundary(wall_clock: SystemTime, every: Duration) -> Duration {
+    let since_epoch = wall_clock.duration_since(UNIX_EPOCH).unwrap_or_default();
+    let every_nanos = every.as_nanos();
+    let past_boundary = since_epoch.as_nanos() % every_nanos;
+    Duration::from_nanos(((every_nanos - past_boundary) % every_nanos) as u64)
+}
+
+/// First multiple of `every` after `boundary`, which is strictly later than `now`
+fn next_boundary(boundary: Instant, every: Duration, now: Instant) -> Instant {
+    if boundary > now {
+        return boundary;
+    }
+    let missed = (now - boundary).as_nanos() / every.as_nanos() + 1;
+    boundary + every * missed as u32
+}
+
+#[derive(Debug)]
+struct Entry {
+    schedule: Schedule,
+    due: Instant,
+    /// Some wall-clock boundary of the schedule on monotonic clock
+    boundary: Instant,
+}
+
+/// Produces due crawls of [`CrawlSchedule`]
+///
+/// Every schedule is due once right after start, so fresh server gets
+/// a snapshot, and then at its wall-clock boundaries. Boundaries missed
+/// during a long crawl collapse into one crawl, so the cadence doesn't
+/// drift with crawl duration
+#[derive(Debug)]
+pub struct Scheduler {
+    entries: Vec<Entry>,
+}
+
+impl Scheduler {
+    pub fn new(schedule: CrawlSchedule) -> Self {
+        Self::with_wall_clock(schedule, SystemTime::now())
+    }
+
+    fn with_wall_clock(schedule: CrawlSchedule, wall_clock: SystemTime) -> Self {
+        let now = Instant::now();
+        Self {
+            entries: schedule
+                .0
+                .into_iter()
+                .map(|schedule| Entry {
+                    due: now,
+                    boundary: now + till_boundary(wall_clock, schedule.every),
+                    schedule,
+                })
+                .collect(),
+        }
+    }
+
+    /// Wait for the next due crawl, schedules due at the same time are
+    /// returned in the configured order. Cancel safe
+    pub async fn next(&mut self) -> CrawlTarget {
+        let entry = match self.entries.iter_mut().min_by_key(|entry| entry.due) {
+            Some(entry) => entry,
+            None => return std::future::pending().await,
+        };
+        tokio::time::sleep_until(entry.due).await;
+
+        entry.due = next_boundary(entry.boundary, entry.schedule.every, Instant::now());
+        entry.boundary = entry.due;
+        entry.schedule.target.clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn get_target(listing: Listing, first_page: usize, last_page: usize) -> CrawlTarget {
+        CrawlTarget::new(listing, first_page, last_page).unwrap()
+    }
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(
+            "top:1@1m, top:2-10@10m,new:1@30s".parse(),
+            Ok(CrawlSchedule(vec![
+                Schedule {
+                    target: get_target(Listing::Top, 1, 1),
+                    every: Duration::from_secs(60),
+                },
+                Schedule {
+                    target: get_target(Listing::Top, 2, 10),
+                    every: Duration::from_secs(600),
+                },
+                Schedule {
+                    target: get_target(Listing::New, 1, 1),
+                    every: Duration::from_secs(30),
+                },
+            ]))
+        );
+
+        for wrong in [
+            "",
+            "top:1",
+            "top@1m",
+            "best:1@1m",
+            "top:0@1m",
+            "top:3-2@1m",
+            "top:1@0s",
+            "top:1@1d",
+            "top:1@m",
+        ] {
+            assert!(
+                wrong.parse::<CrawlSchedule>().is_err(),
+                "`{wrong}` must be rejected"
+            );
+        }
+    }
+
+    #[test]
+    fn test_till_boundary() {
+        let minute = Duration::from_secs(60);
+        assert_eq!(
+            till_boundary(UNIX_EPOCH + Duration::from_secs(600), minute),
+            Duration::ZERO
+        );
+        assert_eq!(
+            till_boundary(UNIX_EPOCH + Duration::from_millis(600_500), minute),
+            Duration::from_millis(59_500)
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_wall_clock_alignment() {
+        let start = Instant::now();
+        let mut scheduler = Scheduler::with_wall_clock(
+            "top:1@1m,new:1@30s".parse().unwrap(),
+            UNIX_EPOCH + Duration::from_secs(3600 + 10),
+        );
+
+        let mut crawls = Vec::new();
+        while crawls.len() < 7 {
+            let target = scheduler.next().await;
+            crawls.push(((Instant::now() - start).as_secs(), target.listing));
+        }
+
+        assert_eq!(
+            crawls,
+            vec![
+                // Due on start
+                (0, Listing::Top),
+                (0, Listing::New),
+                // Wall clock xx:00:30
+                (20, Listing::New),
+                // xx:01:00
+                (50, Listing::Top),
+                (50, Listing::New),
+                (80, Listing::New),
+                (110, Listing::Top),
+            ]
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_overrun_does_not_drift() {
+        let start = Instant::now();
+        let mut scheduler = Scheduler::with_wall_clock("top:1@1m".parse().unwrap(), UNIX_EPOCH);
+
+        scheduler.next().await;
+        // Crawl takes 2.5 boundaries
+        tokio::time::sleep(Duration::from_secs(150)).await;
+
+        // Missed boundaries collapse into one crawl right away
+        scheduler.next().await;
+        assert_eq!((Instant::now() - start).as_secs(), 150);
+
+        // Next one is at the boundary, not a minute after the crawl
+        scheduler.next().await;
+        assert_eq!((Instant::now() - start).as_secs(), 180);
+    }
+}
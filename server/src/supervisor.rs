@@ -0,0 +1,308 @@
+use std::{
+    any::Any, collections::BTreeMap, fmt::Display, future::Future, panic::AssertUnwindSafe,
+    time::Duration,
+};
+
+use futures::FutureExt;
+use tokio::{sync::watch, time::Instant};
+use tokio_util::sync::CancellationToken;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, strum::Display)]
+#[strum(serialize_all = "lowercase")]
+pub enum Component {
+    Crawler,
+    Api,
+    Http,
+    Feeds,
+}
+
+impl Component {
+    pub const ALL: [Component; 4] = [
+        Component::Crawler,
+        Component::Api,
+        Component::Http,
+        Component::Feeds,
+    ];
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Status {
+    Running,
+    Restarting { restarts: u32, last_error: String },
+    Failed { last_error: String },
+    Stopped,
+}
+
+impl Status {
+    pub fn is_healthy(&self) -> bool {
+        matches!(self, Status::Running | Status::Stopped)
+    }
+}
+
+/// Current status of every supervised component
+#[derive(Debug)]
+pub struct Health {
+    statuses: watch::Sender<BTreeMap<Component, Status>>,
+}
+
+impl Default for Health {
+    fn default() -> Self {
+        Self {
+            statuses: watch::channel(BTreeMap::new()).0,
+        }
+    }
+}
+
+impl Health {
+    pub fn set(&self, component: Component, status: Status) {
+        if self.get(component).as_ref() == Some(&status) {
+            return;
+        }
+
+        if status.is_healthy() {
+            tracing::info!("{component} is {status:?}");
+        } else {
+            tracing::error!("{component} is unhealthy: {status:?}");
+        }
+
+        self.statuses.send_modify(|statuses| {
+            statuses.insert(component, status);
+        });
+    }
+
+    pub fn get(&self, component: Component) -> Option<Status> {
+        self.statuses.borrow().get(&component).cloned()
+    }
+
+    pub fn unhealthy(&self) -> Vec<(Component, Status)> {
+        self.statuses
+            .borrow()
+            .iter()
+            .filter(|(_, status)| !status.is_healthy())
+            .map(|(component, status)| (*component, status.clone()))
+            .collect()
+    }
+
+    pub fn subscribe(&self) -> watch::Receiver<BTreeMap<Component, Status>> {
+        self.statuses.subscribe()
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct RestartPolicy {
+    /// How many restarts are allowed inside `window` before the failure is fatal
+    pub max_restarts: u32,
+    pub window: Duration,
+    pub backoff: Duration,
+}
+
+impl Default for RestartPolicy {
+    fn default() -> Self {
+        Self {
+            max_restarts: 5,
+            window: Duration::from_secs(600),
+            backoff: Duration::from_secs(1),
+        }
+    }
+}
+
+#[derive(thiserror::Error, Debug)]
+#[error("{component} failed {restarts} times in a row, last error: {last_error}")]
+pub struct Error {
+    pub component: Component,
+    pub restarts: u32,
+    pub last_error: String,
+}
+
+fn panic_message(panic: Box<dyn Any + Send>) -> String {
+    match panic.downcast::<String>() {
+        Ok(message) => format!("panic: {message}"),
+        Err(panic) => match panic.downcast::<&str>() {
+            Ok(message) => format!("panic: {message}"),
+            Err(_) => "panic".to_owned(),
+        },
+    }
+}
+
+/// Run `component` created by `factory`, restarting it on error or panic
+///
+/// The component stops for good when it returns `Ok(())` (expected only
+/// on shutdown) or when it fails more than `policy.max_restarts` times
+/// inside `policy.window`
+pub async fn supervise<F, Fut, E>(
+    component: Component,
+    health: &Health,
+    policy: &RestartPolicy,
+    shutdown: &CancellationToken,
+    mut factory: F,
+) -> Result<(), Error>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<(), E>>,
+    E: Display,
+{
+    let mut failures = Vec::<Instant>::new();
+
+    loop {
+        health.set(component, Status::Running);
+
+        let last_error = match AssertUnwindSafe(factory()).catch_unwind().await {
+            Ok(Ok(())) => {
+                health.set(component, Status::Stopped);
+                return Ok(());
+            }
+            Ok(Err(err)) => err.to_string(),
+            Err(panic) => panic_message(panic),
+        };
+
+        let now = Instant::now();
+        failures.retain(|failure| now.duration_since(*failure) < policy.window);
+        failures.push(now);
+        let restarts = failures.len() as u32;
+
+        if restarts > policy.max_restarts {
+            health.set(
+                component,
+                Status::Failed {
+                    last_error: last_error.clone(),
+                },
+            );
+            return Err(Error {
+                component,
+                restarts,
+                last_error,
+            });
+        }
+
+        health.set(
+            component,
+            Status::Restarting {
+                restarts,
+                last_error,
+            },
+        );
+
+        tokio::select! {
+            _ = tokio::time::sleep(policy.backoff) => {}
+            _ = shutdown.cancelled() => {
+                health.set(component, Status::Stopped);
+                return Ok(());
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    use super::*;
+
+    fn get_policy() -> RestartPolicy {
+        RestartPolicy {
+            max_restarts: 2,
+            window: Duration::from_secs(60),
+            backoff: Duration::from_secs(1),
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_restart_until_success() {
+        let health = Health::default();
+        let runs = AtomicU32::new(0);
+
+        supervise(
+            Component::Crawler,
+            &health,
+            &get_policy(),
+            &CancellationToken::new(),
+            || async {
+                match runs.fetch_add(1, Ordering::Relaxed) {
+                    0 => Err("database is gone"),
+                    1 => panic!("unexpected html"),
+                    _ => Ok(()),
+                }
+            },
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(runs.load(Ordering::Relaxed), 3);
+        assert_eq!(health.get(Component::Crawler), Some(Status::Stopped));
+        assert!(health.unhealthy().is_empty());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_give_up_after_max_restarts() {
+        let health = Health::default();
+
+        let err = supervise(
+            Component::Api,
+            &health,
+            &get_policy(),
+            &CancellationToken::new(),
+            || async { Err::<(), _>("address in use") },
+        )
+        .await
+        .unwrap_err();
+
+        assert_eq!(err.component, Component::Api);
+        assert_eq!(err.restarts, 3);
+        assert_eq!(err.last_error, "address in use");
+        assert_eq!(
+            health.unhealthy(),
+            vec![(
+                Component::Api,
+                Status::Failed {
+                    last_error: "address in use".to_owned()
+                }
+            )]
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_failures_outside_window_are_forgotten() {
+        let health = Health::default();
+        let runs = AtomicU32::new(0);
+
+        supervise(
+            Component::Crawler,
+            &health,
+            &get_policy(),
+            &CancellationToken::new(),
+            || async {
+                match runs.fetch_add(1, Ordering::Relaxed) {
+                    // Every run fails after a long successful work
+                    0..=5 => {
+                        tokio::time::sleep(Duration::from_secs(120)).await;
+                        Err("rare failure")
+                    }
+                    _ => Ok(()),
+                }
+            },
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(runs.load(Ordering::Relaxed), 7);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_shutdown_stops_restarts() {
+        let health = Health::default();
+        let shutdown = CancellationToken::new();
+        shutdown.cancel();
+
+        supervise(
+            Component::Crawler,
+            &health,
+            &get_policy(),
+            &shutdown,
+            || async { Err("failed during shutdown") },
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(health.get(Component::Crawler), Some(Status::Stopped));
+    }
+}
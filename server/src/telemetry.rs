@@ -0,0 +1,253 @@
+use hyper::{HeaderMap, Request};
+use opentelemetry::{
+    propagation::{Extractor, TextMapPropagator},
+    trace::TraceError,
+    KeyValue,
+};
+use opentelemetry_otlp::WithExportConfig;
+use opentelemetry_sdk::{propagation::TraceContextPropagator, trace::Tracer, Resource};
+use reqwest::Url;
+use serde::Deserialize;
+use tracing_opentelemetry::OpenTelemetrySpanExt;
+use tracing_subscriber::{
+    filter::ParseError, layer::SubscriberExt, reload, util::SubscriberInitExt, EnvFilter, Layer,
+    Registry,
+};
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error(transparent)]
+    Trace(#[from] TraceError),
+    #[error("wrong log filter: {0}")]
+    Filter(#[from] ParseError),
+    #[error(transparent)]
+    Reload(#[from] reload::Error),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogFormat {
+    Text,
+    /// One json object per line, event & span fields are json fields
+    Json,
+}
+
+/// [`EnvFilter`] of the installed logs, which can be replaced at runtime
+#[derive(Debug, Clone)]
+pub struct LogFilter {
+    handle: reload::Handle<EnvFilter, Registry>,
+}
+
+pub type LogFilterLayer = reload::Layer<EnvFilter, Registry>;
+
+impl LogFilter {
+    /// Filter by `directives` in `RUST_LOG` syntax, it works while the
+    /// returned layer is installed
+    pub fn new(directives: &str) -> Result<(LogFilterLayer, Self), Error> {
+        let (layer, handle) = reload::Layer::new(EnvFilter::try_new(directives)?);
+        Ok((layer, Self { handle }))
+    }
+
+    pub fn get(&self) -> Result<String, Error> {
+        Ok(self.handle.with_current(|filter| filter.to_string())?)
+    }
+
+    pub fn set(&self, directives: &str) -> Result<(), Error> {
+        let filter = EnvFilter::try_new(directives)?;
+        self.handle.reload(filter)?;
+        tracing::info!(directives, "log filter changed");
+        Ok(())
+    }
+}
+
+/// Installed tracing of the app, spans are flushed on [`Telemetry::shutdown`]
+pub struct Telemetry {
+    log_filter: LogFilter,
+    is_exported: bool,
+}
+
+impl Telemetry {
+    pub fn log_filter(&self) -> LogFilter {
+        self.log_filter.clone()
+    }
+
+    /// Export spans still waiting in the batch
+    pub fn shutdown(self) {
+        if self.is_exported {
+            opentelemetry::global::shutdown_tracer_provider();
+        }
+    }
+}
+
+fn otlp_tracer(endpoint: &Url, service_name: &str) -> Result<Tracer, TraceError> {
+    opentelemetry_otlp::new_pipeline()
+        .tracing()
+        .with_exporter(
+            opentelemetry_otlp::new_exporter()
+                .http()
+                .with_endpoint(endpoint.as_str()),
+        )
+        .with_trace_config(
+            opentelemetry_sdk::trace::config().with_resource(Resource::new([KeyValue::new(
+                "service.name",
+                service_name.to_owned(),
+            )])),
+        )
+        .install_batch(opentelemetry_sdk::runtime::Tokio)
+}
+
+/// Install logs to stdout, filtered by `log_filter` directives, and, when
+/// `otlp_endpoint` is set, export of spans by OTLP over http
+/// (e.g. `http://localhost:4318/v1/traces`)
+pub fn init(
+    log_format: LogFormat,
+    log_filter: &str,
+    otlp_endpoint: Option<&Url>,
+    service_name: &str,
+) -> Result<Telemetry, Error> {
+    let (filter_layer, log_filter) = LogFilter::new(log_filter)?;
+    let otel = otlp_endpoint
+        .map(|endpoint| otlp_tracer(endpoint, service_name))
+        .transpose()?
+        .map(|tracer| tracing_opentelemetry::layer().with_tracer(tracer));
+    let is_exported = otel.is_some();
+
+    let fmt = match log_format {
+        LogFormat::Text => tracing_subscriber::fmt::layer().boxed(),
+        LogFormat::Json => tracing_subscriber::fmt::layer().json().boxed(),
+    };
+
+    tracing_subscriber::registry()
+        .with(filter_layer)
+        .with(fmt)
+        .with(otel)
+        .init();
+
+    if let Some(endpoint) = otlp_endpoint {
+        tracing::info!(%endpoint, "spans are exported");
+    }
+
+    Ok(Telemetry {
+        log_filter,
+        is_exported,
+    })
+}
+
+/// Grpc metadata are plain http/2 headers
+struct HeaderExtractor<'h>(&'h HeaderMap);
+
+impl Extractor for HeaderExtractor<'_> {
+    fn get(&self, key: &str) -> Option<&str> {
+        self.0.get(key).and_then(|value| value.to_str().ok())
+    }
+
+    fn keys(&self) -> Vec<&str> {
+        self.0.keys().map(|key| key.as_str()).collect()
+    }
+}
+
+/// Span of one grpc call
+///
+/// When the caller sends W3C `traceparent` metadata, the span continues
+/// the caller's trace
+pub fn grpc_span(request: &Request<()>) -> tracing::Span {
+    let method = request.uri().path().trim_start_matches('/');
+    let span = tracing::info_span!(
+        "grpc",
+        otel.name = method,
+        otel.kind = "server",
+        rpc.system = "grpc",
+        rpc.method = method,
+    );
+    span.set_parent(TraceContextPropagator::new().extract(&HeaderExtractor(request.headers())));
+    span
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use opentelemetry::trace::{SpanId, TraceId, TracerProvider as _};
+    use opentelemetry_sdk::{
+        export::trace::SpanData, testing::trace::InMemorySpanExporter, trace::TracerProvider,
+    };
+    use tracing::Subscriber;
+
+    use super::*;
+
+    /// Keeps the provider alive, tracer holds only a weak link to it
+    pub struct SpanRecorder {
+        provider: TracerProvider,
+        exporter: InMemorySpanExporter,
+    }
+
+    impl SpanRecorder {
+        /// Spans are exported in background thread, so flush them first
+        pub fn finished_spans(&self) -> Vec<SpanData> {
+            self.provider.force_flush();
+            self.exporter.get_finished_spans().unwrap()
+        }
+    }
+
+    /// Subscriber which keeps every finished span in the returned recorder
+    pub fn in_memory_subscriber() -> (impl Subscriber + Send + Sync, SpanRecorder) {
+        let exporter = InMemorySpanExporter::default();
+        let provider = TracerProvider::builder()
+            .with_simple_exporter(exporter.clone())
+            .build();
+
+        let subscriber = tracing_subscriber::registry()
+            .with(tracing_opentelemetry::layer().with_tracer(provider.tracer("test")));
+        (subscriber, SpanRecorder { provider, exporter })
+    }
+
+    pub fn find_spans<'s>(spans: &'s [SpanData], name: &str) -> Vec<&'s SpanData> {
+        spans.iter().filter(|span| span.name == name).collect()
+    }
+
+    #[test]
+    fn test_grpc_span_continues_remote_trace() {
+        let (subscriber, recorder) = in_memory_subscriber();
+
+        tracing::subscriber::with_default(subscriber, || {
+            let request = Request::get("/hackernews_proxy.PostService/GetTopPosts")
+                .header(
+                    "traceparent",
+                    "00-0af7651916cd43dd8448eb211c80319c-b7ad6b7169203331-01",
+                )
+                .body(())
+                .unwrap();
+
+            grpc_span(&request).in_scope(|| tracing::info_span!("insert_post").in_scope(|| {}));
+        });
+
+        let spans = recorder.finished_spans();
+        let grpc = find_spans(&spans, "hackernews_proxy.PostService/GetTopPosts")[0];
+        assert_eq!(
+            grpc.span_context.trace_id(),
+            TraceId::from_hex("0af7651916cd43dd8448eb211c80319c").unwrap()
+        );
+        assert_eq!(
+            grpc.parent_span_id,
+            SpanId::from_hex("b7ad6b7169203331").unwrap()
+        );
+
+        let child = find_spans(&spans, "insert_post")[0];
+        assert_eq!(child.parent_span_id, grpc.span_context.span_id());
+        assert_eq!(child.span_context.trace_id(), grpc.span_context.trace_id());
+    }
+
+    #[test]
+    fn test_grpc_span_without_context() {
+        let (subscriber, recorder) = in_memory_subscriber();
+
+        tracing::subscriber::with_default(subscriber, || {
+            let request = Request::get("/hackernews_proxy.PostService/GetUserPosts")
+                .body(())
+                .unwrap();
+            drop(grpc_span(&request));
+        });
+
+        let spans = recorder.finished_spans();
+        let grpc = find_spans(&spans, "hackernews_proxy.PostService/GetUserPosts")[0];
+        assert_eq!(grpc.parent_span_id, SpanId::INVALID);
+    }
+}
@@ -0,0 +1,251 @@
+use std::path::{Path, PathBuf};
+
+use tonic::transport::{Certificate, Identity, ServerTlsConfig};
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("failed to read `{path}`: {source}")]
+    Read {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+    #[error("both TLS_CERT and TLS_KEY must be set for tls")]
+    MissingPair,
+    #[error(
+        "TLS_CLIENT_CA needs TLS_CERT and TLS_KEY, client certificates are checked only over tls"
+    )]
+    ClientCaWithoutTls,
+}
+
+fn read(path: &Path) -> Result<Vec<u8>, Error> {
+    std::fs::read(path).map_err(|source| Error::Read {
+        path: path.to_owned(),
+        source,
+    })
+}
+
+/// Tls of grpc server from PEM files, `None` for plain http/2
+///
+/// With `client_ca` only clients with a certificate signed by it are accepted
+pub fn server_config(
+    cert: Option<&Path>,
+    key: Option<&Path>,
+    client_ca: Option<&Path>,
+) -> Result<Option<ServerTlsConfig>, Error> {
+    let (cert, key) = match (cert, key, client_ca) {
+        (Some(cert), Some(key), _) => (cert, key),
+        (None, None, None) => return Ok(None),
+        (None, None, Some(_)) => return Err(Error::ClientCaWithoutTls),
+        _ => return Err(Error::MissingPair),
+    };
+
+    let config = ServerTlsConfig::new().identity(Identity::from_pem(read(cert)?, read(key)?));
+    Ok(Some(match client_ca {
+        Some(client_ca) => config.client_ca_root(Certificate::from_pem(read(client_ca)?)),
+        None => config,
+    }))
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use std::net::SocketAddr;
+
+    use rcgen::{
+        BasicConstraints, Certificate as Cert, CertificateParams, DnType, ExtendedKeyUsagePurpose,
+        IsCa,
+    };
+    use tokio::net::TcpListener;
+    use tokio_stream::wrappers::TcpListenerStream;
+    use tonic::transport::{Channel, ClientTlsConfig};
+    use tonic_health::proto::{health_client::HealthClient, HealthCheckRequest};
+
+    use super::*;
+
+    /// Self-signed CA with server and client certificates for `localhost`
+    pub(crate) struct Pki {
+        dir: PathBuf,
+        pub(crate) ca: String,
+    }
+
+    impl Pki {
+        pub(crate) fn generate() -> Self {
+            let dir = std::env::temp_dir().join(format!(
+                "hackernews-crawler-tls-{}-{}",
+                std::process::id(),
+                rand::random::<u64>()
+            ));
+            std::fs::create_dir_all(&dir).unwrap();
+
+            let mut params = CertificateParams::new(Vec::new());
+            params.is_ca = IsCa::Ca(BasicConstraints::Unconstrained);
+            params
+                .distinguished_name
+                .push(DnType::CommonName, "test ca");
+            let ca = Cert::from_params(params).unwrap();
+
+            for (name, usage) in [
+                ("server", ExtendedKeyUsagePurpose::ServerAuth),
+                ("client", ExtendedKeyUsagePurpose::ClientAuth),
+            ] {
+                let mut params = CertificateParams::new(vec!["localhost".to_owned()]);
+                params.distinguished_name.push(DnType::CommonName, name);
+                params.extended_key_usages = vec![usage];
+                let cert = Cert::from_params(params).unwrap();
+                std::fs::write(
+                    dir.join(format!("{name}.pem")),
+                    cert.serialize_pem_with_signer(&ca).unwrap(),
+                )
+                .unwrap();
+                std::fs::write(
+                    dir.join(format!("{name}.key")),
+                    cert.serialize_private_key_pem(),
+                )
+                .unwrap();
+            }
+
+            let pki = Self {
+                ca: ca.serialize_pem().unwrap(),
+                dir,
+            };
+            std::fs::write(pki.path("ca.pem"), &pki.ca).unwrap();
+            pki
+        }
+
+        pub(crate) fn path(&self, name: &str) -> PathBuf {
+            self.dir.join(name)
+        }
+
+        fn client_identity(&self) -> Identity {
+            Identity::from_pem(
+                std::fs::read(self.path("client.pem")).unwrap(),
+                std::fs::read(self.path("client.key")).unwrap(),
+            )
+        }
+    }
+
+    impl Drop for Pki {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.dir);
+        }
+    }
+
+    async fn serve(config: ServerTlsConfig) -> SocketAddr {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        let (_, health_server) = tonic_health::server::health_reporter();
+
+        tokio::spawn(
+            tonic::transport::Server::builder()
+                .tls_config(config)
+                .unwrap()
+                .add_service(health_server)
+                .serve_with_incoming(TcpListenerStream::new(listener)),
+        );
+        address
+    }
+
+    async fn check_health(
+        address: SocketAddr,
+        pki: &Pki,
+        identity: Option<Identity>,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let mut tls = ClientTlsConfig::new()
+            .ca_certificate(Certificate::from_pem(&pki.ca))
+            .domain_name("localhost");
+        if let Some(identity) = identity {
+            tls = tls.identity(identity);
+        }
+
+        let channel = Channel::from_shared(format!("https://{address}"))?
+            .tls_config(tls)?
+            .connect()
+            .await?;
+        HealthClient::new(channel)
+            .check(HealthCheckRequest {
+                service: String::new(),
+            })
+            .await?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_server_config() {
+        let pki = Pki::generate();
+        let (cert, key, ca) = (
+            pki.path("server.pem"),
+            pki.path("server.key"),
+            pki.path("ca.pem"),
+        );
+
+        assert!(server_config(None, None, None).unwrap().is_none());
+        assert!(server_config(Some(&cert), Some(&key), Some(&ca))
+            .unwrap()
+            .is_some());
+        assert!(matches!(
+            server_config(Some(&cert), None, None),
+            Err(Error::MissingPair)
+        ));
+        assert!(matches!(
+            server_config(None, None, Some(&ca)),
+            Err(Error::ClientCaWithoutTls)
+        ));
+        assert!(matches!(
+            server_config(Some(&cert), Some(&pki.path("missing.key")), None),
+            Err(Error::Read { .. })
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_tls() {
+        let pki = Pki::generate();
+        let config = server_config(
+            Some(&pki.path("server.pem")),
+            Some(&pki.path("server.key")),
+            None,
+        )
+        .unwrap()
+        .unwrap();
+        let address = serve(config).await;
+
+        check_health(address, &pki, None).await.unwrap();
+
+        // Plain http/2 is not served next to tls
+        let mut plain = HealthClient::new(
+            Channel::from_shared(format!("http://{address}"))
+                .unwrap()
+                .connect()
+                .await
+                .unwrap(),
+        );
+        assert!(plain
+            .check(HealthCheckRequest {
+                service: String::new()
+            })
+            .await
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn test_mtls() {
+        let pki = Pki::generate();
+        let config = server_config(
+            Some(&pki.path("server.pem")),
+            Some(&pki.path("server.key")),
+            Some(&pki.path("ca.pem")),
+        )
+        .unwrap()
+        .unwrap();
+        let address = serve(config).await;
+
+        assert!(check_health(address, &pki, None).await.is_err());
+        check_health(address, &pki, Some(pki.client_identity()))
+            .await
+            .unwrap();
+
+        // Client certificate of other CA is rejected
+        let other = Pki::generate();
+        assert!(check_health(address, &pki, Some(other.client_identity()))
+            .await
+            .is_err());
+    }
+}
@@ -1,8 +0,0 @@
-/// Core struct of crate
-pub mod hackernews_core;
-pub use hackernews_core as core;
-
-/// Module with protobuf and protobuf casts
-pub mod hackernews_proxy_proto;
-pub use hackernews_proxy_proto as proto;
-